@@ -86,7 +86,7 @@ fn setup(
             })),
             material: materials.add(StandardMaterial {
                 base_color_texture: Some(asset_server.load("models/Earth/earth_daymap.jpg")),
-                emissive: Color::rgba(1.0, 1.0, 1.0, 0.5),
+                emissive: Color::rgb_linear(128.0, 128.0, 128.0),
                 emissive_texture: Some(asset_server.load("models/Earth/earth_daymap.jpg")),
                 ..Default::default()
             }),
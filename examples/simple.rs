@@ -176,7 +176,7 @@ fn setup(
             })),
             material: materials.add(StandardMaterial {
                 base_color_texture: Some(asset_server.load("models/Earth/earth_daymap.jpg")),
-                emissive: Color::rgba(1.0, 1.0, 1.0, 0.5),
+                emissive: Color::rgb_linear(128.0, 128.0, 128.0),
                 emissive_texture: Some(asset_server.load("models/Earth/earth_daymap.jpg")),
                 ..Default::default()
             }),
@@ -198,7 +198,7 @@ fn setup(
             })),
             material: materials.add(StandardMaterial {
                 base_color_texture: Some(asset_server.load("models/Earth/earth_daymap.jpg")),
-                emissive: Color::rgba(1.0, 1.0, 1.0, 0.1),
+                emissive: Color::rgb_linear(25.0, 25.0, 25.0),
                 emissive_texture: Some(asset_server.load("models/Earth/earth_daymap.jpg")),
                 ..Default::default()
             }),
@@ -0,0 +1,185 @@
+use crate::{
+    mesh_material::area_light::{
+        HikariDiskLight, HikariRectLight, HikariSphereLight, HikariTubeLight,
+    },
+    warm_up::HikariPipelinesReady,
+    HikariSettings, RenderMode,
+};
+use bevy::{
+    asset::Asset,
+    ecs::query::QueryItem,
+    prelude::*,
+    render::extract_component::{ExtractComponent, ExtractComponentPlugin},
+};
+
+pub struct AccumulationPlugin;
+impl Plugin for AccumulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<HikariAccumulationProgress>()
+            .init_resource::<AccumulationReset>()
+            .add_plugin(ExtractComponentPlugin::<HikariAccumulationProgress>::default())
+            .add_system_to_stage(
+                CoreStage::Last,
+                scene_change_system.label(AccumulationSystems::DetectChanges),
+            )
+            .add_system_to_stage(
+                CoreStage::Last,
+                asset_change_system::<Mesh>.label(AccumulationSystems::DetectChanges),
+            )
+            .add_system_to_stage(
+                CoreStage::Last,
+                asset_change_system::<Image>.label(AccumulationSystems::DetectChanges),
+            )
+            .add_system_to_stage(
+                CoreStage::Last,
+                accumulation_progress_system.after(AccumulationSystems::DetectChanges),
+            );
+    }
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, SystemLabel)]
+pub enum AccumulationSystems {
+    DetectChanges,
+}
+
+/// Set if anything in the scene changed this frame, which restarts the accumulation.
+#[derive(Debug, Default, Resource, Deref, DerefMut)]
+pub struct AccumulationReset(pub bool);
+
+/// Progress of the reference image of a camera in [`RenderMode::Accumulation`] mode.
+#[derive(Debug, Default, Clone, Copy, Component, Reflect)]
+#[reflect(Component)]
+pub struct HikariAccumulationProgress {
+    /// Number of samples per pixel accumulated so far, including the current frame.
+    pub samples: usize,
+    /// Number of samples per pixel to accumulate.
+    pub target: usize,
+    /// Whether a new sample is accumulated in the current frame.
+    pub accumulating: bool,
+}
+
+impl HikariAccumulationProgress {
+    /// Whether the target sample count is reached.
+    pub fn is_complete(&self) -> bool {
+        self.samples >= self.target
+    }
+
+    /// Ratio of accumulated samples to the target sample count, from 0.0 to 1.0.
+    pub fn fraction(&self) -> f32 {
+        match self.target {
+            0 => 1.0,
+            target => (self.samples as f32 / target as f32).min(1.0),
+        }
+    }
+
+    fn advance(&mut self, target: usize, restart: bool) {
+        self.target = target;
+        if restart {
+            self.samples = 0;
+        }
+        self.accumulating = !self.is_complete();
+        if self.accumulating {
+            self.samples += 1;
+        }
+    }
+
+    /// Holds the accumulation at no samples, since nothing is rendered until the pipelines are compiled.
+    fn wait(&mut self, target: usize) {
+        self.target = target;
+        self.samples = 0;
+        self.accumulating = false;
+    }
+}
+
+impl ExtractComponent for HikariAccumulationProgress {
+    type Query = &'static Self;
+    type Filter = ();
+
+    fn extract_component(item: QueryItem<Self::Query>) -> Self {
+        *item
+    }
+}
+
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+fn scene_change_system(
+    mut reset: ResMut<AccumulationReset>,
+    ambient_light: Option<Res<AmbientLight>>,
+    changed: Query<
+        (),
+        Or<(
+            Changed<GlobalTransform>,
+            Changed<Visibility>,
+            Changed<Handle<Mesh>>,
+            Changed<DirectionalLight>,
+            Changed<HikariRectLight>,
+            Changed<HikariDiskLight>,
+            Changed<HikariSphereLight>,
+            Changed<HikariTubeLight>,
+        )>,
+    >,
+    removed_meshes: RemovedComponents<Handle<Mesh>>,
+    removed_rect_lights: RemovedComponents<HikariRectLight>,
+    removed_disk_lights: RemovedComponents<HikariDiskLight>,
+    removed_sphere_lights: RemovedComponents<HikariSphereLight>,
+    removed_tube_lights: RemovedComponents<HikariTubeLight>,
+) {
+    let ambient_changed = matches!(ambient_light, Some(light) if light.is_changed());
+    let removed = removed_meshes.iter().next().is_some()
+        || removed_rect_lights.iter().next().is_some()
+        || removed_disk_lights.iter().next().is_some()
+        || removed_sphere_lights.iter().next().is_some()
+        || removed_tube_lights.iter().next().is_some();
+    if ambient_changed || !changed.is_empty() || removed {
+        **reset = true;
+    }
+}
+
+/// Restarts the accumulation if any asset of the type is changed, or assigned to an entity.
+pub(crate) fn asset_change_system<T: Asset>(
+    mut reset: ResMut<AccumulationReset>,
+    mut events: EventReader<AssetEvent<T>>,
+    changed: Query<(), Changed<Handle<T>>>,
+) {
+    if events.iter().next().is_some() || !changed.is_empty() {
+        **reset = true;
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn accumulation_progress_system(
+    mut commands: Commands,
+    mut reset: ResMut<AccumulationReset>,
+    ready: Res<HikariPipelinesReady>,
+    mut cameras: Query<(
+        Entity,
+        &HikariSettings,
+        Option<&mut HikariAccumulationProgress>,
+    )>,
+    changed_cameras: Query<(), Or<(Changed<HikariSettings>, Changed<Projection>)>>,
+) {
+    for (entity, settings, progress) in &mut cameras {
+        let restart = **reset || changed_cameras.contains(entity);
+        match (settings.render_mode, progress) {
+            (RenderMode::Accumulation { samples }, Some(mut progress)) => match ready.is_ready() {
+                true => progress.advance(samples, restart),
+                false => progress.wait(samples),
+            },
+            (RenderMode::Accumulation { samples }, None) => {
+                let mut progress = HikariAccumulationProgress::default();
+                match ready.is_ready() {
+                    true => progress.advance(samples, true),
+                    false => progress.wait(samples),
+                }
+                commands.entity(entity).insert(progress);
+            }
+            (RenderMode::Realtime, Some(_)) => {
+                commands
+                    .entity(entity)
+                    .remove::<HikariAccumulationProgress>();
+            }
+            (RenderMode::Realtime, None) => {}
+        }
+    }
+
+    **reset = false;
+}
@@ -0,0 +1,285 @@
+use crate::{
+    light::LightTextures,
+    post_process::{is_denoising, DenoiseAccumulation, PostProcessTextures},
+    prepass::PrepassTextures,
+    HikariSettings, AOV_SHADER_HANDLE, WORKGROUP_SIZE,
+};
+use bevy::{
+    ecs::query::QueryItem,
+    prelude::*,
+    render::{
+        extract_component::{ExtractComponent, ExtractComponentPlugin},
+        render_asset::RenderAssets,
+        render_graph::{Node, NodeRunError, RenderGraphContext, SlotInfo, SlotType},
+        render_resource::*,
+        renderer::{RenderContext, RenderDevice},
+        RenderApp, RenderStage,
+    },
+    utils::HashMap,
+};
+
+pub const AOV_TEXTURE_FORMAT: TextureFormat = TextureFormat::Rgba32Float;
+
+pub struct AovPlugin;
+impl Plugin for AovPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(ExtractComponentPlugin::<HikariAovs>::default());
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<AovPipeline>()
+                .add_system_to_stage(RenderStage::Queue, queue_aov_bind_groups);
+        }
+    }
+}
+
+/// An arbitrary output variable produced by the pipeline.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum Aov {
+    /// Surface albedo, in output resolution. Alpha holds the reactivity used by temporal upscaling.
+    Albedo,
+    /// World space position, with depth in the alpha channel.
+    Position,
+    /// World space normal.
+    Normal,
+    /// Screen space velocity in the first two channels, and mesh uv in the last two.
+    VelocityUv,
+    /// Instance and material indices.
+    InstanceMaterial,
+    /// Raw direct lighting, in render resolution.
+    Direct,
+    /// Raw emissive lighting, in render resolution.
+    Emissive,
+    /// Raw indirect lighting, in render resolution.
+    Indirect,
+    /// Direct lighting as fed to tone mapping, which is denoised if denoising is enabled.
+    DenoisedDirect,
+    /// Emissive lighting as fed to tone mapping, which is denoised if denoising is enabled.
+    DenoisedEmissive,
+    /// Indirect lighting as fed to tone mapping, which is denoised if denoising is enabled.
+    DenoisedIndirect,
+    /// Luminance variance of direct lighting.
+    DirectVariance,
+    /// Luminance variance of emissive lighting.
+    EmissiveVariance,
+    /// Luminance variance of indirect lighting.
+    IndirectVariance,
+}
+
+/// Output variables of a camera to copy into user owned images every frame.
+/// Images must be in [`AOV_TEXTURE_FORMAT`] with storage binding usage, like the ones from [`HikariAovs::create_image`];
+/// other images are skipped. Inputs are resampled to the size of each image.
+#[derive(Debug, Default, Clone, Component, Deref, DerefMut)]
+pub struct HikariAovs(pub HashMap<Aov, Handle<Image>>);
+
+impl HikariAovs {
+    /// Creates an image that output variables can be copied into.
+    pub fn create_image(size: UVec2) -> Image {
+        let mut image = Image::new_fill(
+            Extent3d {
+                width: size.x.max(1),
+                height: size.y.max(1),
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &[0; 16],
+            AOV_TEXTURE_FORMAT,
+        );
+        image.texture_descriptor.usage = TextureUsages::TEXTURE_BINDING
+            | TextureUsages::STORAGE_BINDING
+            | TextureUsages::COPY_SRC
+            | TextureUsages::COPY_DST;
+        image
+    }
+}
+
+impl ExtractComponent for HikariAovs {
+    type Query = &'static Self;
+    type Filter = ();
+
+    fn extract_component(item: QueryItem<Self::Query>) -> Self {
+        item.clone()
+    }
+}
+
+#[derive(Resource)]
+pub struct AovPipeline {
+    pub layout: BindGroupLayout,
+    pub pipeline: CachedComputePipelineId,
+}
+
+impl FromWorld for AovPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+        let layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                // Input
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                // Output
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::StorageTexture {
+                        access: StorageTextureAccess::WriteOnly,
+                        format: AOV_TEXTURE_FORMAT,
+                        view_dimension: TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let mut pipeline_cache = world.resource_mut::<PipelineCache>();
+        let pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+            label: None,
+            layout: Some(vec![layout.clone()]),
+            shader: AOV_SHADER_HANDLE.typed(),
+            shader_defs: vec![],
+            entry_point: "aov".into(),
+        });
+
+        Self { layout, pipeline }
+    }
+}
+
+/// Bind groups of all output variables of a view, with the sizes of their target images.
+#[derive(Component)]
+pub struct AovBindGroups(pub Vec<(BindGroup, UVec2)>);
+
+#[allow(clippy::type_complexity)]
+fn queue_aov_bind_groups(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    pipeline: Res<AovPipeline>,
+    images: Res<RenderAssets<Image>>,
+    query: Query<(
+        Entity,
+        &HikariAovs,
+        &LightTextures,
+        &PostProcessTextures,
+        &PrepassTextures,
+        &HikariSettings,
+        Option<&DenoiseAccumulation>,
+    )>,
+) {
+    for (entity, aovs, light, post_process, prepass, settings, denoise_accumulation) in &query {
+        let denoise = is_denoising(settings, denoise_accumulation);
+        let [direct, emissive, indirect] =
+            post_process.tone_mapping_inputs(light, settings, denoise);
+        let image_view =
+            |handle: &Handle<Image>| images.get(handle).map(|image| &image.texture_view);
+
+        let bind_groups = aovs
+            .iter()
+            .filter_map(|(aov, handle)| {
+                let target = images
+                    .get(handle)
+                    .filter(|image| image.texture_format == AOV_TEXTURE_FORMAT)?;
+                let input = match aov {
+                    Aov::Albedo => &light.albedo,
+                    Aov::Position => image_view(&prepass.position)?,
+                    Aov::Normal => image_view(&prepass.normal)?,
+                    Aov::VelocityUv => image_view(&prepass.velocity_uv)?,
+                    Aov::InstanceMaterial => image_view(&prepass.instance_material)?,
+                    Aov::Direct => &light.render[0],
+                    Aov::Emissive => &light.render[1],
+                    Aov::Indirect => &light.render[2],
+                    Aov::DenoisedDirect => direct,
+                    Aov::DenoisedEmissive => emissive,
+                    Aov::DenoisedIndirect => indirect,
+                    Aov::DirectVariance => &light.variance[0],
+                    Aov::EmissiveVariance => &light.variance[1],
+                    Aov::IndirectVariance => &light.variance[2],
+                };
+
+                let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+                    label: None,
+                    layout: &pipeline.layout,
+                    entries: &[
+                        BindGroupEntry {
+                            binding: 0,
+                            resource: BindingResource::TextureView(input),
+                        },
+                        BindGroupEntry {
+                            binding: 1,
+                            resource: BindingResource::TextureView(&target.texture_view),
+                        },
+                    ],
+                });
+                Some((bind_group, target.size.as_uvec2()))
+            })
+            .collect();
+
+        commands.entity(entity).insert(AovBindGroups(bind_groups));
+    }
+}
+
+pub struct AovNode {
+    query: QueryState<&'static AovBindGroups>,
+}
+
+impl AovNode {
+    pub const IN_VIEW: &'static str = "view";
+
+    pub fn new(world: &mut World) -> Self {
+        Self {
+            query: world.query_filtered(),
+        }
+    }
+}
+
+impl Node for AovNode {
+    fn input(&self) -> Vec<SlotInfo> {
+        vec![SlotInfo::new(Self::IN_VIEW, SlotType::Entity)]
+    }
+
+    fn update(&mut self, world: &mut World) {
+        self.query.update_archetypes(world);
+    }
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let entity = graph.get_input_entity(Self::IN_VIEW)?;
+        let bind_groups = match self.query.get_manual(world, entity) {
+            Ok(query) => query,
+            Err(_) => return Ok(()),
+        };
+
+        let pipeline_id = world.resource::<AovPipeline>().pipeline;
+        let pipeline = match world
+            .resource::<PipelineCache>()
+            .get_compute_pipeline(pipeline_id)
+        {
+            Some(pipeline) => pipeline,
+            None => return Ok(()),
+        };
+
+        let mut pass = render_context
+            .command_encoder
+            .begin_compute_pass(&ComputePassDescriptor::default());
+        pass.set_pipeline(pipeline);
+
+        for (bind_group, size) in &bind_groups.0 {
+            pass.set_bind_group(0, bind_group, &[]);
+
+            let count = (*size + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE;
+            pass.dispatch_workgroups(count.x, count.y, 1);
+        }
+
+        Ok(())
+    }
+}
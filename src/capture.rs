@@ -0,0 +1,1012 @@
+use crate::{
+    denoiser::{DenoiserDevice, DenoiserError, DenoiserImage, HikariDenoiser},
+    light::LightTextures,
+    post_process::{is_denoising, DenoiseAccumulation, PostProcessTextures},
+    prepass::PrepassTextures,
+    HikariSettings, CAPTURE_SHADER_HANDLE, WORKGROUP_SIZE,
+};
+use bevy::{
+    prelude::*,
+    render::{
+        camera::ExtractedCamera,
+        render_asset::RenderAssets,
+        render_graph::{Node, NodeRunError, RenderGraphContext, SlotInfo, SlotType},
+        render_resource::*,
+        renderer::{RenderContext, RenderDevice},
+        texture::TextureCache,
+        Extract, RenderApp, RenderStage,
+    },
+    utils::HashMap,
+};
+use serde::Serialize;
+use std::{
+    fmt::{Display, Formatter},
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+pub const CAPTURE_TEXTURE_FORMAT: TextureFormat = TextureFormat::Rgba32Float;
+
+pub struct CapturePlugin;
+impl Plugin for CapturePlugin {
+    fn build(&self, app: &mut App) {
+        let results = CaptureResults::default();
+
+        app.add_event::<HikariCaptured>()
+            .insert_resource(results.clone())
+            .add_system_to_stage(CoreStage::First, capture_results_system);
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .insert_resource(results)
+                .init_resource::<CaptureJobs>()
+                .init_resource::<CapturePipeline>()
+                .init_resource::<SpecializedComputePipelines<CapturePipeline>>()
+                .add_system_to_stage(RenderStage::Extract, extract_capture_requests)
+                .add_system_to_stage(RenderStage::Prepare, prepare_capture_targets)
+                .add_system_to_stage(RenderStage::Queue, queue_capture_pipelines)
+                .add_system_to_stage(RenderStage::Queue, queue_capture_bind_groups)
+                .add_system_to_stage(RenderStage::Cleanup, readback_capture_system);
+        }
+    }
+}
+
+/// Requests the image of a camera to be read back from the GPU and written to disk.
+/// The component is removed once the capture is written, and a [`HikariCaptured`] event is sent.
+#[derive(Debug, Clone, Component)]
+pub struct HikariCapture {
+    /// Path of the image file to write.
+    pub path: PathBuf,
+    /// Which image of the camera to capture.
+    pub source: CaptureSource,
+    /// File format of the captured image.
+    pub format: CaptureFormat,
+    /// Denoiser applied to the captured image, if any, see [`HikariCapture::with_denoiser`].
+    pub denoiser: Option<Arc<dyn HikariDenoiser>>,
+}
+
+impl HikariCapture {
+    /// Captures the final output of the camera, in the format given by the extension of the path.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let format = CaptureFormat::from_path(&path);
+        Self {
+            path,
+            source: CaptureSource::Output,
+            format,
+            denoiser: None,
+        }
+    }
+
+    /// Captures the linear HDR image filtered by the given denoiser.
+    ///
+    /// In accumulation mode the real time denoiser is bypassed, so this is how converged images get their
+    /// remaining noise removed. A [`DenoiserDevice::Readback`] denoiser also reads back the albedo and normal
+    /// of the camera as its guides.
+    pub fn with_denoiser(mut self, denoiser: impl HikariDenoiser) -> Self {
+        self.source = CaptureSource::Hdr;
+        self.denoiser = Some(Arc::new(denoiser));
+        self
+    }
+
+    fn readback_denoiser(&self) -> Option<&dyn HikariDenoiser> {
+        self.denoiser
+            .as_deref()
+            .filter(|denoiser| denoiser.device() == DenoiserDevice::Readback)
+    }
+}
+
+/// Image of a camera to capture.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureSource {
+    /// The tone mapped and anti-aliased image, in the output resolution.
+    #[default]
+    Output,
+    /// The sum of all lighting passes in linear HDR before tone mapping, in the render resolution.
+    Hdr,
+}
+
+/// File format of a captured image.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureFormat {
+    /// 8-bit sRGB PNG. HDR values are clamped to 1.0.
+    #[default]
+    Png,
+    /// Uncompressed OpenEXR with 16-bit half float channels.
+    ExrHalf,
+    /// Uncompressed OpenEXR with 32-bit float channels.
+    ExrFloat,
+}
+
+impl CaptureFormat {
+    /// Half float EXR for `.exr` files, and PNG otherwise.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("exr") => Self::ExrHalf,
+            _ => Self::Png,
+        }
+    }
+}
+
+/// Sent when a [`HikariCapture`] is finished.
+#[derive(Debug)]
+pub struct HikariCaptured {
+    /// The camera entity.
+    pub entity: Entity,
+    /// Path of the written image file.
+    pub path: PathBuf,
+    /// Whether the image was read back and written successfully.
+    pub result: Result<(), CaptureError>,
+}
+
+/// Reasons for a capture to fail.
+#[derive(Debug)]
+pub enum CaptureError {
+    /// The image could not be read back from the GPU.
+    Readback,
+    /// The image could not be encoded.
+    Encode(image::ImageError),
+    /// The image file could not be written.
+    Io(std::io::Error),
+    /// The denoiser failed to filter the image.
+    Denoise(DenoiserError),
+}
+
+impl Display for CaptureError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CaptureError::Readback => write!(f, "failed to map the readback buffer"),
+            CaptureError::Encode(err) => write!(f, "failed to encode the image: {err}"),
+            CaptureError::Io(err) => write!(f, "failed to write the image: {err}"),
+            CaptureError::Denoise(err) => write!(f, "failed to denoise the image: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for CaptureError {}
+
+struct CapturedImage {
+    entity: Entity,
+    capture: HikariCapture,
+    size: UVec2,
+    pixels: Result<Vec<Vec4>, CaptureError>,
+    /// Albedo and normal guides, if the capture has a readback denoiser.
+    guides: Option<[Vec<Vec4>; 2]>,
+}
+
+/// Images read back in the render world, waiting to be written by the main world.
+#[derive(Default, Clone, Resource)]
+struct CaptureResults(Arc<Mutex<Vec<CapturedImage>>>);
+
+struct CaptureJob {
+    capture: HikariCapture,
+    clear_color: Vec4,
+    target: Option<CaptureTarget>,
+}
+
+/// Pending captures of the render world, keyed by camera entities.
+#[derive(Default, Resource, Deref, DerefMut)]
+struct CaptureJobs(HashMap<Entity, CaptureJob>);
+
+#[derive(Clone, Component)]
+pub struct CaptureTarget {
+    pub source: CaptureSource,
+    pub size: UVec2,
+    pub texture: Texture,
+    pub view: TextureView,
+    pub buffer: Buffer,
+    pub padded_bytes_per_row: u32,
+    /// Albedo and normal of the image, captured for readback denoisers.
+    pub guides: Option<[CaptureGuide; 2]>,
+    /// Set by the capture node once the texture is copied into the buffer.
+    copied: Arc<AtomicBool>,
+}
+
+/// A denoiser guide with the same size and layout as its [`CaptureTarget`].
+#[derive(Clone)]
+pub struct CaptureGuide {
+    pub texture: Texture,
+    pub view: TextureView,
+    pub buffer: Buffer,
+}
+
+#[derive(Resource)]
+pub struct CapturePipeline {
+    pub layout: BindGroupLayout,
+    pub guide_layout: BindGroupLayout,
+}
+
+impl FromWorld for CapturePipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+        let layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                // Output
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                // Direct Render
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                // Emissive Render
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                // Indirect Render
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                // Linear Sampler
+                BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+                // Capture
+                BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::StorageTexture {
+                        access: StorageTextureAccess::WriteOnly,
+                        format: CAPTURE_TEXTURE_FORMAT,
+                        view_dimension: TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let texture_entry = |binding| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: false },
+                view_dimension: TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let storage_entry = |binding| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::StorageTexture {
+                access: StorageTextureAccess::WriteOnly,
+                format: CAPTURE_TEXTURE_FORMAT,
+                view_dimension: TextureViewDimension::D2,
+            },
+            count: None,
+        };
+        let guide_layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                // Albedo
+                texture_entry(0),
+                // Normal
+                texture_entry(1),
+                // Albedo Guide
+                storage_entry(2),
+                // Normal Guide
+                storage_entry(3),
+            ],
+        });
+
+        Self {
+            layout,
+            guide_layout,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureEntryPoint {
+    CaptureOutput,
+    CaptureHdr,
+    CaptureGuides,
+}
+
+impl SpecializedComputePipeline for CapturePipeline {
+    type Key = CaptureEntryPoint;
+
+    fn specialize(&self, key: Self::Key) -> ComputePipelineDescriptor {
+        let entry_point = serde_variant::to_variant_name(&key).unwrap().into();
+        ComputePipelineDescriptor {
+            label: None,
+            layout: Some(vec![self.layout.clone(), self.guide_layout.clone()]),
+            shader: CAPTURE_SHADER_HANDLE.typed(),
+            shader_defs: vec![],
+            entry_point,
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct CachedCapturePipelines {
+    capture_output: CachedComputePipelineId,
+    capture_hdr: CachedComputePipelineId,
+    capture_guides: CachedComputePipelineId,
+}
+
+fn extract_capture_requests(
+    mut jobs: ResMut<CaptureJobs>,
+    requests: Extract<Query<(Entity, &HikariCapture), Changed<HikariCapture>>>,
+) {
+    for (entity, capture) in requests.iter() {
+        jobs.insert(
+            entity,
+            CaptureJob {
+                capture: capture.clone(),
+                clear_color: Vec4::ZERO,
+                target: None,
+            },
+        );
+    }
+}
+
+fn prepare_capture_targets(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    mut texture_cache: ResMut<TextureCache>,
+    mut jobs: ResMut<CaptureJobs>,
+    cameras: Query<(Entity, &ExtractedCamera, &HikariSettings)>,
+) {
+    for (entity, camera, settings) in &cameras {
+        let (job, size) = match (jobs.get_mut(&entity), camera.physical_target_size) {
+            (Some(job), Some(size)) if size.x > 0 && size.y > 0 => (job, size),
+            _ => continue,
+        };
+
+        let source = job.capture.source;
+        let size = match source {
+            CaptureSource::Output => size,
+            CaptureSource::Hdr => {
+                let scale = settings.upscale.ratio().recip();
+                (scale * size.as_vec2()).ceil().as_uvec2()
+            }
+        };
+
+        let mut create_texture = || {
+            texture_cache.get(
+                &render_device,
+                TextureDescriptor {
+                    label: None,
+                    size: Extent3d {
+                        width: size.x,
+                        height: size.y,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format: CAPTURE_TEXTURE_FORMAT,
+                    usage: TextureUsages::STORAGE_BINDING | TextureUsages::COPY_SRC,
+                },
+            )
+        };
+
+        // The readback buffers are kept across frames until the size changes.
+        let pixel_size = CAPTURE_TEXTURE_FORMAT.describe().block_size as usize;
+        let padded_bytes_per_row =
+            RenderDevice::align_copy_bytes_per_row(size.x as usize * pixel_size) as u32;
+        let previous = job
+            .target
+            .as_ref()
+            .filter(|target| target.size == size && target.source == source);
+        let create_buffer = |previous: Option<&Buffer>| match previous {
+            Some(buffer) => buffer.clone(),
+            None => render_device.create_buffer(&BufferDescriptor {
+                label: None,
+                size: (padded_bytes_per_row * size.y) as u64,
+                usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }),
+        };
+
+        let texture = create_texture();
+        let buffer = create_buffer(previous.map(|target| &target.buffer));
+
+        let guides = job.capture.readback_denoiser().map(|_| {
+            [0, 1].map(|id| {
+                let texture = create_texture();
+                let previous = previous
+                    .and_then(|target| target.guides.as_ref())
+                    .map(|guides| &guides[id].buffer);
+                CaptureGuide {
+                    texture: texture.texture,
+                    view: texture.default_view,
+                    buffer: create_buffer(previous),
+                }
+            })
+        });
+
+        let target = CaptureTarget {
+            source,
+            size,
+            texture: texture.texture,
+            view: texture.default_view,
+            buffer,
+            padded_bytes_per_row,
+            guides,
+            copied: default(),
+        };
+        job.clear_color = settings.clear_color.into();
+        job.target = Some(target.clone());
+
+        let mut entity = commands.entity(entity);
+        entity.insert(target);
+        if let Some(DenoiserDevice::Gpu) = job
+            .capture
+            .denoiser
+            .as_ref()
+            .map(|denoiser| denoiser.device())
+        {
+            entity.insert(DenoiseAccumulation);
+        }
+    }
+}
+
+fn queue_capture_pipelines(
+    mut commands: Commands,
+    pipeline: Res<CapturePipeline>,
+    mut pipelines: ResMut<SpecializedComputePipelines<CapturePipeline>>,
+    mut pipeline_cache: ResMut<PipelineCache>,
+) {
+    let capture_output = pipelines.specialize(
+        &mut pipeline_cache,
+        &pipeline,
+        CaptureEntryPoint::CaptureOutput,
+    );
+    let capture_hdr = pipelines.specialize(
+        &mut pipeline_cache,
+        &pipeline,
+        CaptureEntryPoint::CaptureHdr,
+    );
+    let capture_guides = pipelines.specialize(
+        &mut pipeline_cache,
+        &pipeline,
+        CaptureEntryPoint::CaptureGuides,
+    );
+
+    commands.insert_resource(CachedCapturePipelines {
+        capture_output,
+        capture_hdr,
+        capture_guides,
+    });
+}
+
+#[derive(Component)]
+pub struct CaptureBindGroup(pub BindGroup);
+
+#[derive(Component)]
+pub struct CaptureGuideBindGroup(pub BindGroup);
+
+#[allow(clippy::type_complexity)]
+fn queue_capture_bind_groups(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    pipeline: Res<CapturePipeline>,
+    images: Res<RenderAssets<Image>>,
+    query: Query<(
+        Entity,
+        &LightTextures,
+        &PostProcessTextures,
+        &PrepassTextures,
+        &HikariSettings,
+        &CaptureTarget,
+        Option<&DenoiseAccumulation>,
+    )>,
+) {
+    for (entity, light, post_process, prepass, settings, target, denoise_accumulation) in &query {
+        let denoise = is_denoising(settings, denoise_accumulation);
+        let [direct_render, emissive_render, indirect_render] =
+            post_process.tone_mapping_inputs(light, settings, denoise);
+
+        let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(post_process.output(settings)),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(direct_render),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::TextureView(emissive_render),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: BindingResource::TextureView(indirect_render),
+                },
+                BindGroupEntry {
+                    binding: 4,
+                    resource: BindingResource::Sampler(&post_process.linear_sampler),
+                },
+                BindGroupEntry {
+                    binding: 5,
+                    resource: BindingResource::TextureView(&target.view),
+                },
+            ],
+        });
+        commands.entity(entity).insert(CaptureBindGroup(bind_group));
+
+        let normal = images.get(&prepass.normal).map(|image| &image.texture_view);
+        if let (Some([albedo_guide, normal_guide]), Some(normal)) = (&target.guides, normal) {
+            let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+                label: None,
+                layout: &pipeline.guide_layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::TextureView(&light.albedo),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::TextureView(normal),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: BindingResource::TextureView(&albedo_guide.view),
+                    },
+                    BindGroupEntry {
+                        binding: 3,
+                        resource: BindingResource::TextureView(&normal_guide.view),
+                    },
+                ],
+            });
+            commands
+                .entity(entity)
+                .insert(CaptureGuideBindGroup(bind_group));
+        }
+    }
+}
+
+pub struct CaptureNode {
+    query: QueryState<(
+        &'static CaptureTarget,
+        &'static CaptureBindGroup,
+        Option<&'static CaptureGuideBindGroup>,
+    )>,
+}
+
+impl CaptureNode {
+    pub const IN_VIEW: &'static str = "view";
+
+    pub fn new(world: &mut World) -> Self {
+        Self {
+            query: world.query_filtered(),
+        }
+    }
+}
+
+impl Node for CaptureNode {
+    fn input(&self) -> Vec<SlotInfo> {
+        vec![SlotInfo::new(Self::IN_VIEW, SlotType::Entity)]
+    }
+
+    fn update(&mut self, world: &mut World) {
+        self.query.update_archetypes(world);
+    }
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let entity = graph.get_input_entity(Self::IN_VIEW)?;
+        let (target, bind_group, guide_bind_group) = match self.query.get_manual(world, entity) {
+            Ok(query) => query,
+            Err(_) => return Ok(()),
+        };
+
+        let pipelines = world.resource::<CachedCapturePipelines>();
+        let pipeline_cache = world.resource::<PipelineCache>();
+
+        let pipeline = match target.source {
+            CaptureSource::Output => pipelines.capture_output,
+            CaptureSource::Hdr => pipelines.capture_hdr,
+        };
+        let pipeline = match pipeline_cache.get_compute_pipeline(pipeline) {
+            Some(pipeline) => pipeline,
+            None => return Ok(()),
+        };
+
+        // The capture waits until the guides of its denoiser can be captured as well.
+        let guides = match (&target.guides, guide_bind_group) {
+            (None, _) => None,
+            (Some(guides), Some(guide_bind_group)) => {
+                match pipeline_cache.get_compute_pipeline(pipelines.capture_guides) {
+                    Some(pipeline) => Some((guides, guide_bind_group, pipeline)),
+                    None => return Ok(()),
+                }
+            }
+            (Some(_), None) => return Ok(()),
+        };
+
+        let count = (target.size + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE;
+        {
+            let mut pass = render_context
+                .command_encoder
+                .begin_compute_pass(&ComputePassDescriptor::default());
+
+            pass.set_bind_group(0, &bind_group.0, &[]);
+            pass.set_pipeline(pipeline);
+            pass.dispatch_workgroups(count.x, count.y, 1);
+
+            if let Some((_, guide_bind_group, pipeline)) = guides {
+                pass.set_bind_group(1, &guide_bind_group.0, &[]);
+                pass.set_pipeline(pipeline);
+                pass.dispatch_workgroups(count.x, count.y, 1);
+            }
+        }
+
+        let textures = std::iter::once((&target.texture, &target.buffer)).chain(
+            guides
+                .into_iter()
+                .flat_map(|(guides, _, _)| guides.iter())
+                .map(|guide| (&guide.texture, &guide.buffer)),
+        );
+        for (texture, buffer) in textures {
+            render_context.command_encoder.copy_texture_to_buffer(
+                ImageCopyTexture {
+                    texture,
+                    mip_level: 0,
+                    origin: Origin3d::ZERO,
+                    aspect: TextureAspect::All,
+                },
+                ImageCopyBuffer {
+                    buffer,
+                    layout: ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: NonZeroU32::new(target.padded_bytes_per_row),
+                        rows_per_image: None,
+                    },
+                },
+                Extent3d {
+                    width: target.size.x,
+                    height: target.size.y,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+        target.copied.store(true, Ordering::Release);
+
+        Ok(())
+    }
+}
+
+/// Maps the buffers copied this frame and hands the pixels over to the main world.
+fn readback_capture_system(
+    render_device: Res<RenderDevice>,
+    mut jobs: ResMut<CaptureJobs>,
+    results: Res<CaptureResults>,
+) {
+    let copied: Vec<_> = jobs
+        .iter()
+        .filter(
+            |(_, job)| matches!(&job.target, Some(target) if target.copied.load(Ordering::Acquire)),
+        )
+        .map(|(entity, _)| *entity)
+        .collect();
+
+    for entity in copied {
+        let job = match jobs.remove(&entity) {
+            Some(job) => job,
+            None => continue,
+        };
+        let target = match job.target {
+            Some(target) => target,
+            None => continue,
+        };
+
+        let pixels = read_buffer(&render_device, &target.buffer, &target).map(|pixels| {
+            pixels
+                .into_iter()
+                .map(|color| match target.source {
+                    // Same as the tone mapping pass, missed pixels show the clear color.
+                    CaptureSource::Hdr if color.w <= 0.0 => job.clear_color,
+                    _ => color,
+                })
+                .collect()
+        });
+        let guides = target.guides.as_ref().map(|guides| {
+            guides
+                .each_ref()
+                .map(|guide| read_buffer(&render_device, &guide.buffer, &target))
+        });
+        let (pixels, guides) = match guides {
+            Some([Ok(albedo), Ok(normal)]) => (pixels, Some([albedo, normal])),
+            Some(_) => (Err(CaptureError::Readback), None),
+            None => (pixels, None),
+        };
+
+        results.0.lock().unwrap().push(CapturedImage {
+            entity,
+            capture: job.capture,
+            size: target.size,
+            pixels,
+            guides,
+        });
+    }
+}
+
+/// Maps a readback buffer of the target and returns its pixels without row padding.
+fn read_buffer(
+    render_device: &RenderDevice,
+    buffer: &Buffer,
+    target: &CaptureTarget,
+) -> Result<Vec<Vec4>, CaptureError> {
+    let slice = buffer.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    render_device.map_buffer(&slice, MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    render_device.poll(wgpu::Maintain::Wait);
+
+    match receiver.recv() {
+        Ok(Ok(())) => {
+            let data = slice.get_mapped_range();
+            let pixels = data
+                .chunks_exact(target.padded_bytes_per_row as usize)
+                .flat_map(|row| {
+                    row.chunks_exact(16)
+                        .take(target.size.x as usize)
+                        .map(|pixel| {
+                            let channel = |index: usize| {
+                                let bytes = pixel[4 * index..4 * index + 4].try_into();
+                                f32::from_le_bytes(bytes.unwrap())
+                            };
+                            Vec4::new(channel(0), channel(1), channel(2), channel(3))
+                        })
+                })
+                .collect();
+            drop(data);
+            buffer.unmap();
+            Ok(pixels)
+        }
+        _ => Err(CaptureError::Readback),
+    }
+}
+
+fn capture_results_system(
+    mut commands: Commands,
+    results: Res<CaptureResults>,
+    mut events: EventWriter<HikariCaptured>,
+) {
+    let images: Vec<_> = results.0.lock().unwrap().drain(..).collect();
+    for CapturedImage {
+        entity,
+        capture,
+        size,
+        pixels,
+        guides,
+    } in images
+    {
+        let pixels = match (capture.readback_denoiser(), guides) {
+            (Some(denoiser), Some([albedo, normal])) => pixels.and_then(|color| {
+                let mut image = DenoiserImage {
+                    size,
+                    color,
+                    albedo,
+                    normal,
+                };
+                denoiser
+                    .denoise(&mut image)
+                    .map_err(CaptureError::Denoise)?;
+                Ok(image.color)
+            }),
+            _ => pixels,
+        };
+        let result = pixels.and_then(|pixels| match capture.format {
+            CaptureFormat::Png => write_png(&capture.path, size, &pixels),
+            CaptureFormat::ExrHalf => {
+                std::fs::write(&capture.path, encode_exr(size, &pixels, true))
+                    .map_err(CaptureError::Io)
+            }
+            CaptureFormat::ExrFloat => {
+                std::fs::write(&capture.path, encode_exr(size, &pixels, false))
+                    .map_err(CaptureError::Io)
+            }
+        });
+        if let Err(err) = &result {
+            warn!("Failed to capture {:?}: {}", capture.path, err);
+        }
+
+        if let Some(mut entity) = commands.get_entity(entity) {
+            entity.remove::<HikariCapture>();
+        }
+        events.send(HikariCaptured {
+            entity,
+            path: capture.path,
+            result,
+        });
+    }
+}
+
+fn write_png(path: &Path, size: UVec2, pixels: &[Vec4]) -> Result<(), CaptureError> {
+    let data: Vec<u8> = pixels
+        .iter()
+        .flat_map(|color| {
+            [color.x, color.y, color.z].map(|value| {
+                let value = linear_to_srgb(value.clamp(0.0, 1.0));
+                (value * 255.0).round() as u8
+            })
+        })
+        .collect();
+    image::save_buffer_with_format(
+        path,
+        &data,
+        size.x,
+        size.y,
+        image::ColorType::Rgb8,
+        image::ImageFormat::Png,
+    )
+    .map_err(|err| match err {
+        image::ImageError::IoError(err) => CaptureError::Io(err),
+        err => CaptureError::Encode(err),
+    })
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    match value <= 0.0031308 {
+        true => value * 12.92,
+        false => 1.055 * value.powf(1.0 / 2.4) - 0.055,
+    }
+}
+
+/// Encodes RGB channels of the pixels as a single part, uncompressed scanline OpenEXR image.
+fn encode_exr(size: UVec2, pixels: &[Vec4], half: bool) -> Vec<u8> {
+    fn attribute(out: &mut Vec<u8>, name: &str, ty: &str, value: &[u8]) {
+        out.extend_from_slice(name.as_bytes());
+        out.push(0);
+        out.extend_from_slice(ty.as_bytes());
+        out.push(0);
+        out.extend_from_slice(&(value.len() as i32).to_le_bytes());
+        out.extend_from_slice(value);
+    }
+
+    // Channels are stored in alphabetical order.
+    let channels = ["B", "G", "R"];
+    let (pixel_type, bytes_per_value) = match half {
+        true => (1i32, 2),
+        false => (2i32, 4),
+    };
+
+    let mut out = vec![];
+    out.extend_from_slice(&20000630i32.to_le_bytes());
+    out.extend_from_slice(&2i32.to_le_bytes());
+
+    let mut channel_list = vec![];
+    for name in channels {
+        channel_list.extend_from_slice(name.as_bytes());
+        channel_list.push(0);
+        channel_list.extend_from_slice(&pixel_type.to_le_bytes());
+        // Perceptually linear flag and reserved bytes.
+        channel_list.extend_from_slice(&[0; 4]);
+        // X and y sampling.
+        channel_list.extend_from_slice(&1i32.to_le_bytes());
+        channel_list.extend_from_slice(&1i32.to_le_bytes());
+    }
+    channel_list.push(0);
+
+    let window: Vec<u8> = [0, 0, size.x as i32 - 1, size.y as i32 - 1]
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect();
+
+    attribute(&mut out, "channels", "chlist", &channel_list);
+    attribute(&mut out, "compression", "compression", &[0]);
+    attribute(&mut out, "dataWindow", "box2i", &window);
+    attribute(&mut out, "displayWindow", "box2i", &window);
+    attribute(&mut out, "lineOrder", "lineOrder", &[0]);
+    attribute(&mut out, "pixelAspectRatio", "float", &1.0f32.to_le_bytes());
+    attribute(&mut out, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(
+        &mut out,
+        "screenWindowWidth",
+        "float",
+        &1.0f32.to_le_bytes(),
+    );
+    out.push(0);
+
+    // Each block holds one scanline, prefixed by its y coordinate and data size.
+    let width = size.x as usize;
+    let data_size = width * channels.len() * bytes_per_value;
+    let table_end = out.len() + 8 * size.y as usize;
+    for y in 0..size.y as usize {
+        let offset = table_end + y * (8 + data_size);
+        out.extend_from_slice(&(offset as u64).to_le_bytes());
+    }
+
+    for (y, row) in pixels.chunks_exact(width).enumerate() {
+        out.extend_from_slice(&(y as i32).to_le_bytes());
+        out.extend_from_slice(&(data_size as i32).to_le_bytes());
+        for channel in [2, 1, 0] {
+            for color in row {
+                let value = color[channel];
+                match half {
+                    true => out.extend_from_slice(&f32_to_f16(value).to_le_bytes()),
+                    false => out.extend_from_slice(&value.to_le_bytes()),
+                }
+            }
+        }
+    }
+
+    out
+}
+
+/// Converts a float to IEEE 754 half precision bits, rounding to nearest.
+pub(crate) fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x007f_ffff;
+
+    if exponent == 0xff {
+        // Infinity or NaN.
+        let nan = if mantissa != 0 { 0x0200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        // Overflows to infinity.
+        return sign | 0x7c00;
+    }
+    if exponent <= 0 {
+        if exponent < -10 {
+            // Underflows to zero.
+            return sign;
+        }
+        // Subnormal half, with the implicit leading bit made explicit.
+        let mantissa = mantissa | 0x0080_0000;
+        let shift = (14 - exponent) as u32;
+        let half = mantissa >> shift;
+        let round = (mantissa >> (shift - 1)) & 1;
+        return sign | (half + round) as u16;
+    }
+
+    // A carry from rounding correctly propagates into the exponent.
+    let half = ((exponent as u32) << 10) | (mantissa >> 13);
+    let round = (mantissa >> 12) & 1;
+    sign | (half + round) as u16
+}
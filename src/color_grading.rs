@@ -0,0 +1,139 @@
+use crate::capture::f32_to_f16;
+use bevy::{
+    asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset},
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+use std::fmt::{Display, Formatter};
+
+pub const COLOR_GRADING_LUT_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+/// Loads 3D LUTs from `.cube` files as 3D images in [`COLOR_GRADING_LUT_FORMAT`].
+///
+/// The LUT maps sRGB encoded display colors, with red varying fastest.
+/// Only 3D LUTs with the default `[0, 1]` domain are supported.
+#[derive(Default)]
+pub struct CubeLutLoader;
+
+impl AssetLoader for CubeLutLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let source = std::str::from_utf8(bytes).map_err(|_| CubeLutError::Encoding)?;
+            let image = parse_cube_lut(source)?;
+            load_context.set_default_asset(LoadedAsset::new(image));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["cube"]
+    }
+}
+
+/// Reasons for a `.cube` file to fail loading.
+#[derive(Debug)]
+pub enum CubeLutError {
+    /// The file is not valid UTF-8.
+    Encoding,
+    /// A line could not be parsed.
+    Syntax(usize),
+    /// The file has no `LUT_3D_SIZE`, or describes a 1D LUT.
+    MissingSize,
+    /// The domain is not `[0, 1]`.
+    UnsupportedDomain,
+    /// The number of entries doesn't match the size.
+    EntryCount { expected: usize, found: usize },
+}
+
+impl Display for CubeLutError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CubeLutError::Encoding => write!(f, "the file is not valid UTF-8"),
+            CubeLutError::Syntax(line) => write!(f, "invalid syntax at line {line}"),
+            CubeLutError::MissingSize => write!(f, "missing LUT_3D_SIZE"),
+            CubeLutError::UnsupportedDomain => write!(f, "only the [0, 1] domain is supported"),
+            CubeLutError::EntryCount { expected, found } => {
+                write!(f, "expected {expected} entries, found {found}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CubeLutError {}
+
+/// Parses the text of a `.cube` file into a 3D image.
+pub fn parse_cube_lut(source: &str) -> Result<Image, CubeLutError> {
+    let mut size = None;
+    let mut data = vec![];
+    let mut count = 0;
+
+    for (index, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let syntax_error = || CubeLutError::Syntax(index + 1);
+        let parse_floats = |tokens: &[&str]| {
+            tokens
+                .iter()
+                .map(|token| token.parse::<f32>().map_err(|_| syntax_error()))
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        let tokens: Vec<_> = line.split_whitespace().collect();
+        match tokens[0] {
+            "TITLE" => {}
+            "LUT_3D_SIZE" => {
+                let value = tokens.get(1).and_then(|token| token.parse::<usize>().ok());
+                size = Some(value.filter(|size| *size >= 2).ok_or_else(syntax_error)?);
+            }
+            "LUT_1D_SIZE" => return Err(CubeLutError::MissingSize),
+            "DOMAIN_MIN" | "DOMAIN_MAX" => {
+                let expected = if tokens[0] == "DOMAIN_MIN" { 0.0 } else { 1.0 };
+                let values = parse_floats(&tokens[1..])?;
+                if values.len() != 3 {
+                    return Err(syntax_error());
+                }
+                if values.iter().any(|value| *value != expected) {
+                    return Err(CubeLutError::UnsupportedDomain);
+                }
+            }
+            _ => {
+                let values = parse_floats(&tokens)?;
+                if values.len() != 3 {
+                    return Err(syntax_error());
+                }
+                for value in values.into_iter().chain([1.0]) {
+                    data.extend_from_slice(&f32_to_f16(value).to_le_bytes());
+                }
+                count += 1;
+            }
+        }
+    }
+
+    let size = size.ok_or(CubeLutError::MissingSize)?;
+    let expected = size * size * size;
+    if count != expected {
+        return Err(CubeLutError::EntryCount {
+            expected,
+            found: count,
+        });
+    }
+
+    let extent = Extent3d {
+        width: size as u32,
+        height: size as u32,
+        depth_or_array_layers: size as u32,
+    };
+    Ok(Image::new(
+        extent,
+        TextureDimension::D3,
+        data,
+        COLOR_GRADING_LUT_FORMAT,
+    ))
+}
@@ -0,0 +1,355 @@
+use crate::{
+    light::{GpuReservoirBuffer, LightTextures, ReservoirCache},
+    post_process::{PostProcessTextures, HDR_TEXTURE_FORMAT},
+    prepass::{DeferredBindGroup, PrepassBindGroup, PrepassPipeline, PrepassTextures},
+    view::{FrameUniform, PreviousViewUniformOffset},
+    HikariDebugView, HikariSettings, LightingPass, DEBUG_SHADER_HANDLE, WORKGROUP_SIZE,
+};
+use bevy::{
+    pbr::ViewLightsUniformOffset,
+    prelude::*,
+    render::{
+        camera::ExtractedCamera,
+        extract_component::DynamicUniformIndex,
+        render_graph::{Node, NodeRunError, RenderGraphContext, SlotInfo, SlotType},
+        render_resource::*,
+        renderer::{RenderContext, RenderDevice},
+        view::ViewUniformOffset,
+        RenderApp, RenderStage,
+    },
+};
+use serde::Serialize;
+
+pub struct DebugPlugin;
+impl Plugin for DebugPlugin {
+    fn build(&self, app: &mut App) {
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<DebugPipeline>()
+                .init_resource::<SpecializedComputePipelines<DebugPipeline>>()
+                .add_system_to_stage(RenderStage::Queue, queue_debug_pipelines)
+                .add_system_to_stage(RenderStage::Queue, queue_debug_bind_groups);
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct DebugPipeline {
+    pub view_layout: BindGroupLayout,
+    pub deferred_layout: BindGroupLayout,
+    pub input_layout: BindGroupLayout,
+    pub output_layout: BindGroupLayout,
+}
+
+impl FromWorld for DebugPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let view_layout = world.resource::<PrepassPipeline>().view_layout.clone();
+
+        let render_device = world.resource::<RenderDevice>();
+        let deferred_layout = PrepassTextures::bind_group_layout(render_device);
+
+        let input_layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                // Render
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                // Variance
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                // Temporal Reservoir
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: Some(GpuReservoirBuffer::min_size()),
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let output_layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::StorageTexture {
+                    access: StorageTextureAccess::WriteOnly,
+                    format: HDR_TEXTURE_FORMAT,
+                    view_dimension: TextureViewDimension::D2,
+                },
+                count: None,
+            }],
+        });
+
+        Self {
+            view_layout,
+            deferred_layout,
+            input_layout,
+            output_layout,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DebugEntryPoint {
+    DebugTraversal,
+    DebugInstance,
+    DebugMaterial,
+    DebugReservoirCount,
+    DebugReservoirLifetime,
+    DebugVariance,
+    DebugDepthGradient,
+    DebugVelocity,
+    DebugRadiance,
+}
+
+impl DebugEntryPoint {
+    /// Entry point and the lighting pass whose inputs are bound, if the view is enabled.
+    pub fn from_view(view: HikariDebugView) -> Option<(Self, LightingPass)> {
+        let default = LightingPass::default();
+        match view {
+            HikariDebugView::None => None,
+            HikariDebugView::Traversal(pass) => Some((Self::DebugTraversal, pass)),
+            HikariDebugView::InstanceId => Some((Self::DebugInstance, default)),
+            HikariDebugView::MaterialId => Some((Self::DebugMaterial, default)),
+            HikariDebugView::ReservoirCount(pass) => Some((Self::DebugReservoirCount, pass)),
+            HikariDebugView::ReservoirLifetime(pass) => Some((Self::DebugReservoirLifetime, pass)),
+            HikariDebugView::Variance(pass) => Some((Self::DebugVariance, pass)),
+            HikariDebugView::DepthGradient => Some((Self::DebugDepthGradient, default)),
+            HikariDebugView::Velocity => Some((Self::DebugVelocity, default)),
+            HikariDebugView::Radiance(pass) => Some((Self::DebugRadiance, pass)),
+        }
+    }
+}
+
+impl SpecializedComputePipeline for DebugPipeline {
+    type Key = DebugEntryPoint;
+
+    fn specialize(&self, key: Self::Key) -> ComputePipelineDescriptor {
+        let entry_point = serde_variant::to_variant_name(&key).unwrap().into();
+        ComputePipelineDescriptor {
+            label: None,
+            layout: Some(vec![
+                self.view_layout.clone(),
+                self.deferred_layout.clone(),
+                self.input_layout.clone(),
+                self.output_layout.clone(),
+            ]),
+            shader: DEBUG_SHADER_HANDLE.typed(),
+            shader_defs: vec![],
+            entry_point,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct CachedDebugPipeline(pub CachedComputePipelineId);
+
+fn queue_debug_pipelines(
+    mut commands: Commands,
+    pipeline: Res<DebugPipeline>,
+    mut pipelines: ResMut<SpecializedComputePipelines<DebugPipeline>>,
+    mut pipeline_cache: ResMut<PipelineCache>,
+    cameras: Query<(Entity, &HikariSettings), With<ExtractedCamera>>,
+) {
+    for (entity, settings) in &cameras {
+        if let Some((entry_point, _)) = DebugEntryPoint::from_view(settings.debug_view) {
+            let id = pipelines.specialize(&mut pipeline_cache, &pipeline, entry_point);
+            commands.entity(entity).insert(CachedDebugPipeline(id));
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct DebugBindGroup {
+    pub input: BindGroup,
+    pub output: BindGroup,
+}
+
+fn queue_debug_bind_groups(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    pipeline: Res<DebugPipeline>,
+    reservoir_cache: Res<ReservoirCache>,
+    query: Query<(
+        Entity,
+        &LightTextures,
+        &PostProcessTextures,
+        &HikariSettings,
+    )>,
+) {
+    for (entity, light, post_process, settings) in &query {
+        let pass = match DebugEntryPoint::from_view(settings.debug_view) {
+            Some((_, pass)) => pass,
+            None => continue,
+        };
+        let (id, temporal) = match pass {
+            LightingPass::Direct => (0, 0),
+            LightingPass::Emissive => (1, 2),
+            LightingPass::Indirect => (2, 6),
+        };
+
+        // The temporal reservoir written by the light pass in this frame.
+        let reservoir = match reservoir_cache
+            .get(&entity)
+            .and_then(|reservoirs| reservoirs.get(1 - light.head + temporal))
+            .and_then(|buffer| buffer.binding())
+        {
+            Some(binding) => binding,
+            None => continue,
+        };
+
+        let input = render_device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.input_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&light.render[id]),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&light.variance[id]),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: reservoir,
+                },
+            ],
+        });
+        let output = render_device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.output_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(&post_process.debug_output),
+            }],
+        });
+
+        commands
+            .entity(entity)
+            .insert(DebugBindGroup { input, output });
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub struct DebugNode {
+    query: QueryState<(
+        &'static ExtractedCamera,
+        &'static DynamicUniformIndex<FrameUniform>,
+        &'static ViewUniformOffset,
+        &'static PreviousViewUniformOffset,
+        &'static ViewLightsUniformOffset,
+        &'static DeferredBindGroup,
+        &'static DebugBindGroup,
+        &'static CachedDebugPipeline,
+        &'static HikariSettings,
+    )>,
+}
+
+impl DebugNode {
+    pub const IN_VIEW: &'static str = "view";
+
+    pub fn new(world: &mut World) -> Self {
+        Self {
+            query: world.query_filtered(),
+        }
+    }
+}
+
+impl Node for DebugNode {
+    fn input(&self) -> Vec<SlotInfo> {
+        vec![SlotInfo::new(Self::IN_VIEW, SlotType::Entity)]
+    }
+
+    fn update(&mut self, world: &mut World) {
+        self.query.update_archetypes(world);
+    }
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let entity = graph.get_input_entity(Self::IN_VIEW)?;
+        let (
+            camera,
+            frame_uniform,
+            view_uniform,
+            previous_view_uniform,
+            view_lights,
+            deferred_bind_group,
+            debug_bind_group,
+            pipeline,
+            settings,
+        ) = match self.query.get_manual(world, entity) {
+            Ok(query) => query,
+            Err(_) => return Ok(()),
+        };
+        if !settings.debug_view.is_enabled() {
+            return Ok(());
+        }
+
+        let view_bind_group = match world.get_resource::<PrepassBindGroup>() {
+            Some(bind_group) => &bind_group.view,
+            None => return Ok(()),
+        };
+        let pipeline = match world
+            .resource::<PipelineCache>()
+            .get_compute_pipeline(pipeline.0)
+        {
+            Some(pipeline) => pipeline,
+            None => return Ok(()),
+        };
+        let size = match camera.physical_target_size {
+            Some(size) => size,
+            None => return Ok(()),
+        };
+
+        let mut pass = render_context
+            .command_encoder
+            .begin_compute_pass(&ComputePassDescriptor::default());
+
+        pass.set_bind_group(
+            0,
+            view_bind_group,
+            &[
+                frame_uniform.index(),
+                view_uniform.offset,
+                previous_view_uniform.offset,
+                view_lights.offset,
+            ],
+        );
+        pass.set_bind_group(1, &deferred_bind_group.0, &[]);
+        pass.set_bind_group(2, &debug_bind_group.input, &[]);
+        pass.set_bind_group(3, &debug_bind_group.output, &[]);
+        pass.set_pipeline(pipeline);
+
+        let count = (size + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE;
+        pass.dispatch_workgroups(count.x, count.y, 1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,112 @@
+use bevy::prelude::*;
+use std::fmt::{Debug, Display, Formatter};
+
+/// Denoiser of HDR captures, set with [`HikariCapture::with_denoiser`](crate::capture::HikariCapture::with_denoiser).
+pub trait HikariDenoiser: Send + Sync + 'static {
+    /// Where the denoiser runs.
+    fn device(&self) -> DenoiserDevice;
+
+    /// Denoises the color of a read back image in place.
+    /// Only called for [`DenoiserDevice::Readback`] denoisers, on the main world before the image is written.
+    fn denoise(&self, image: &mut DenoiserImage) -> Result<(), DenoiserError> {
+        let _ = image;
+        Ok(())
+    }
+}
+
+impl Debug for dyn HikariDenoiser {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HikariDenoiser")
+            .field("device", &self.device())
+            .finish()
+    }
+}
+
+/// Where a [`HikariDenoiser`] runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DenoiserDevice {
+    /// The image is filtered by the real time denoiser of the post process pass before being read back.
+    Gpu,
+    /// The image is read back with its albedo and normal, and filtered by [`HikariDenoiser::denoise`].
+    Readback,
+}
+
+/// A captured image in linear HDR with the guides of the denoiser, all in the render resolution.
+#[derive(Debug, Clone)]
+pub struct DenoiserImage {
+    pub size: UVec2,
+    pub color: Vec<Vec4>,
+    /// Albedo of the primary surfaces, zero for missed pixels.
+    pub albedo: Vec<Vec4>,
+    /// World space normal of the primary surfaces, zero for missed pixels.
+    pub normal: Vec<Vec4>,
+}
+
+/// A denoiser failed to filter an image.
+#[derive(Debug, Clone)]
+pub struct DenoiserError(pub String);
+
+impl Display for DenoiserError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for DenoiserError {}
+
+/// The edge-avoiding à-trous wavelet filter of the post process pass, configured by
+/// [`HikariSettings::denoise_settings`](crate::HikariSettings::denoise_settings).
+///
+/// Accumulated images are filtered spatially only in the captured frame.
+/// The denoiser is bypassed if [`HikariSettings::denoise`](crate::HikariSettings::denoise) is disabled.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct GpuDenoiser;
+
+impl HikariDenoiser for GpuDenoiser {
+    fn device(&self) -> DenoiserDevice {
+        DenoiserDevice::Gpu
+    }
+}
+
+/// [Intel Open Image Denoise](https://www.openimagedenoise.org/) running on the CPU with the ray tracing filter,
+/// guided by the albedo and normal. Requires the library to be installed locally.
+#[cfg(feature = "oidn")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct OidnDenoiser;
+
+#[cfg(feature = "oidn")]
+impl HikariDenoiser for OidnDenoiser {
+    fn device(&self) -> DenoiserDevice {
+        DenoiserDevice::Readback
+    }
+
+    fn denoise(&self, image: &mut DenoiserImage) -> Result<(), DenoiserError> {
+        let rgb = |pixels: &[Vec4]| -> Vec<f32> {
+            pixels
+                .iter()
+                .flat_map(|pixel| pixel.truncate().to_array())
+                .collect()
+        };
+        let color = rgb(&image.color);
+        let albedo = rgb(&image.albedo);
+        let normal = rgb(&image.normal);
+        let mut output = vec![0.0; color.len()];
+
+        let device = oidn::Device::new();
+        oidn::RayTracing::new(&device)
+            .hdr(true)
+            .srgb(false)
+            .image_dimensions(image.size.x as usize, image.size.y as usize)
+            .albedo_normal(&albedo, &normal)
+            .filter(&color, &mut output)
+            .map_err(|err| DenoiserError(format!("{err:?}")))?;
+        device
+            .get_error()
+            .map_err(|(_, message)| DenoiserError(message))?;
+
+        for (pixel, value) in image.color.iter_mut().zip(output.chunks_exact(3)) {
+            *pixel = Vec3::from_slice(value).extend(pixel.w);
+        }
+        Ok(())
+    }
+}
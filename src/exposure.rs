@@ -0,0 +1,211 @@
+use crate::{HikariExposure, HikariSettings};
+use bevy::{
+    prelude::*,
+    render::{
+        camera::ExtractedCamera,
+        extract_component::{ComponentUniforms, UniformComponentPlugin},
+        render_resource::*,
+        renderer::{RenderDevice, RenderQueue},
+        Extract, RenderApp, RenderStage,
+    },
+    utils::HashMap,
+};
+
+pub const EXPOSURE_HISTOGRAM_BINS: usize = 64;
+
+pub struct ExposurePlugin;
+impl Plugin for ExposurePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(UniformComponentPlugin::<ExposureUniform>::default());
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<ExposurePipeline>()
+                .init_resource::<ExposureStates>()
+                .add_system_to_stage(RenderStage::Extract, extract_exposure_uniforms)
+                .add_system_to_stage(RenderStage::Prepare, prepare_exposure_states)
+                .add_system_to_stage(RenderStage::Queue, queue_exposure_bind_groups);
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, Component, ShaderType)]
+pub struct ExposureUniform {
+    /// Exposure value if not metered automatically.
+    pub ev100: f32,
+    pub auto_exposure: u32,
+    pub min_ev100: f32,
+    pub max_ev100: f32,
+    pub compensation: f32,
+    pub speed_brighten: f32,
+    pub speed_darken: f32,
+    pub delta_time: f32,
+}
+
+impl ExposureUniform {
+    fn new(exposure: HikariExposure, delta_time: f32) -> Self {
+        match exposure {
+            HikariExposure::None => Self {
+                ev100: HikariExposure::BEVY_EV100,
+                delta_time,
+                ..Default::default()
+            },
+            HikariExposure::Manual { ev100 } => Self {
+                ev100,
+                delta_time,
+                ..Default::default()
+            },
+            HikariExposure::Auto {
+                min_ev100,
+                max_ev100,
+                compensation,
+                speed_brighten,
+                speed_darken,
+            } => Self {
+                ev100: min_ev100,
+                auto_exposure: 1,
+                min_ev100,
+                max_ev100: max_ev100.max(min_ev100 + 1.0),
+                compensation,
+                speed_brighten,
+                speed_darken,
+                delta_time,
+            },
+        }
+    }
+}
+
+fn extract_exposure_uniforms(
+    mut commands: Commands,
+    time: Extract<Res<Time>>,
+    cameras: Extract<Query<(Entity, &Camera, &HikariSettings)>>,
+) {
+    for (entity, camera, settings) in cameras.iter() {
+        if camera.is_active {
+            let uniform = ExposureUniform::new(settings.exposure, time.delta_seconds());
+            commands.get_or_spawn(entity).insert(uniform);
+        }
+    }
+}
+
+/// Metered exposure of a camera, and the luminance histogram of the current frame.
+#[derive(Debug, Clone, ShaderType)]
+pub struct GpuExposureState {
+    pub ev100: f32,
+    /// Whether `ev100` has been metered, so that the first frame doesn't adapt from zero.
+    pub initialized: u32,
+    pub histogram: [u32; EXPOSURE_HISTOGRAM_BINS],
+}
+
+impl Default for GpuExposureState {
+    fn default() -> Self {
+        Self {
+            ev100: 0.0,
+            initialized: 0,
+            histogram: [0; EXPOSURE_HISTOGRAM_BINS],
+        }
+    }
+}
+
+#[derive(Default, Resource, Deref, DerefMut)]
+pub struct ExposureStates(HashMap<Entity, StorageBuffer<GpuExposureState>>);
+
+fn prepare_exposure_states(
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    mut states: ResMut<ExposureStates>,
+    cameras: Query<Entity, (With<ExtractedCamera>, With<ExposureUniform>)>,
+) {
+    // Drop the states of cameras that are no longer extracted.
+    states.retain(|entity, _| cameras.contains(*entity));
+
+    for entity in &cameras {
+        states.entry(entity).or_insert_with(|| {
+            let mut buffer = StorageBuffer::from(GpuExposureState::default());
+            buffer.write_buffer(&render_device, &render_queue);
+            buffer
+        });
+    }
+}
+
+#[derive(Resource)]
+pub struct ExposurePipeline {
+    pub layout: BindGroupLayout,
+}
+
+impl FromWorld for ExposurePipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+        let layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                // Exposure Settings
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: Some(ExposureUniform::min_size()),
+                    },
+                    count: None,
+                },
+                // Exposure State
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: Some(GpuExposureState::min_size()),
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        Self { layout }
+    }
+}
+
+#[derive(Component)]
+pub struct ExposureBindGroup(pub BindGroup);
+
+fn queue_exposure_bind_groups(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    pipeline: Res<ExposurePipeline>,
+    uniforms: Res<ComponentUniforms<ExposureUniform>>,
+    states: Res<ExposureStates>,
+    cameras: Query<Entity, With<ExposureUniform>>,
+) {
+    let uniform_binding = match uniforms.binding() {
+        Some(binding) => binding,
+        None => return,
+    };
+
+    for entity in &cameras {
+        let state_binding = match states.get(&entity).and_then(|buffer| buffer.binding()) {
+            Some(binding) => binding,
+            None => continue,
+        };
+
+        let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_binding.clone(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: state_binding,
+                },
+            ],
+        });
+        commands
+            .entity(entity)
+            .insert(ExposureBindGroup(bind_group));
+    }
+}
@@ -1,11 +1,23 @@
 use crate::{
+    accumulation::AccumulationPlugin,
+    aov::{AovNode, AovPlugin},
+    capture::{CaptureNode, CapturePlugin},
+    color_grading::CubeLutLoader,
+    debug::{DebugNode, DebugPlugin},
+    exposure::ExposurePlugin,
     light::{LightNode, LightPlugin},
-    mesh_material::MeshMaterialPlugin,
+    mesh_material::{
+        area_light::{HikariDiskLight, HikariRectLight, HikariSphereLight, HikariTubeLight},
+        MeshMaterialPlugin,
+    },
     overlay::{OverlayNode, OverlayPlugin},
     post_process::{PostProcessNode, PostProcessPlugin},
     prepass::{PrepassNode, PrepassPlugin},
+    resolution::DynamicResolutionPlugin,
+    support::{fallback_render_graph_system, probe_support, HikariSupport},
     transform::TransformPlugin,
     view::ViewPlugin,
+    warm_up::{HikariPipelinesReady, WarmUpPlugin},
 };
 use bevy::{
     asset::{load_internal_asset, load_internal_binary_asset},
@@ -26,19 +38,28 @@ use bevy::{
         RenderApp,
     },
 };
-use std::num::NonZeroU32;
 
 #[macro_use]
 extern crate num_derive;
 
+pub mod accumulation;
+pub mod aov;
+pub mod capture;
+pub mod color_grading;
+pub mod debug;
+pub mod denoiser;
+pub mod exposure;
 pub mod light;
 pub mod mesh_material;
 pub mod overlay;
 pub mod post_process;
 pub mod prelude;
 pub mod prepass;
+pub mod resolution;
+pub mod support;
 pub mod transform;
 pub mod view;
+pub mod warm_up;
 
 pub mod graph {
     pub const NAME: &str = "hikari";
@@ -46,6 +67,9 @@ pub mod graph {
         pub const PREPASS: &str = "hikari_prepass";
         pub const LIGHT: &str = "hikari_light";
         pub const POST_PROCESS: &str = "hikari_post_process";
+        pub const DEBUG: &str = "hikari_debug";
+        pub const AOV: &str = "hikari_aov";
+        pub const CAPTURE: &str = "hikari_capture";
         pub const OVERLAY: &str = "hikari_overlay";
     }
 }
@@ -89,6 +113,18 @@ pub const FSR1_RCAS_SHADER_HANDLE: HandleUntyped =
     HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 17003547378277520107);
 pub const OVERLAY_SHADER_HANDLE: HandleUntyped =
     HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 10969344919103020615);
+pub const CAPTURE_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 6350219846418370215);
+pub const AOV_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 2709152338861430542);
+pub const DEBUG_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 14537902681295547013);
+pub const DEPTH_OF_FIELD_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 9013528475630221784);
+pub const MOTION_BLUR_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 5411135366815366071);
+pub const TSR_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 15938466107227154398);
 pub const QUAD_MESH_HANDLE: HandleUntyped =
     HandleUntyped::weak_from_u64(Mesh::TYPE_UUID, 4740146776519512271);
 
@@ -173,6 +209,42 @@ impl Plugin for HikariPlugin {
             "shaders/overlay.wgsl",
             Shader::from_wgsl
         );
+        load_internal_asset!(
+            app,
+            CAPTURE_SHADER_HANDLE,
+            "shaders/capture.wgsl",
+            Shader::from_wgsl
+        );
+        load_internal_asset!(
+            app,
+            AOV_SHADER_HANDLE,
+            "shaders/aov.wgsl",
+            Shader::from_wgsl
+        );
+        load_internal_asset!(
+            app,
+            DEBUG_SHADER_HANDLE,
+            "shaders/debug.wgsl",
+            Shader::from_wgsl
+        );
+        load_internal_asset!(
+            app,
+            DEPTH_OF_FIELD_SHADER_HANDLE,
+            "shaders/depth_of_field.wgsl",
+            Shader::from_wgsl
+        );
+        load_internal_asset!(
+            app,
+            MOTION_BLUR_SHADER_HANDLE,
+            "shaders/motion_blur.wgsl",
+            Shader::from_wgsl
+        );
+        load_internal_asset!(
+            app,
+            TSR_SHADER_HANDLE,
+            "shaders/tsr.wgsl",
+            Shader::from_wgsl
+        );
         load_internal_binary_asset!(
             app,
             FSR1_EASU_SHADER_HANDLE,
@@ -205,34 +277,84 @@ impl Plugin for HikariPlugin {
                 include_bytes!("noise/LDR_RGBA_14.png"),
                 include_bytes!("noise/LDR_RGBA_15.png"),
             ];
-            let handles = Vec::from(bytes.map(|buffer| {
-                let image = Image::from_buffer(
+            let layers = bytes.map(|buffer| {
+                Image::from_buffer(
                     buffer,
                     ImageType::Extension("png"),
                     CompressedImageFormats::NONE,
                     false,
                 )
-                .unwrap();
-                images.add(image)
-            }));
-            commands.insert_resource(NoiseTextures(handles));
+                .unwrap()
+            });
+
+            // Stack all noise textures as layers of a single 2D array texture.
+            let descriptor = layers[0].texture_descriptor.clone();
+            let size = Extent3d {
+                depth_or_array_layers: NOISE_TEXTURE_COUNT as u32,
+                ..descriptor.size
+            };
+            let data = layers.into_iter().flat_map(|layer| layer.data).collect();
+            let mut image = Image::new(size, TextureDimension::D2, data, descriptor.format);
+            image.texture_view_descriptor = Some(TextureViewDescriptor {
+                dimension: Some(TextureViewDimension::D2Array),
+                ..Default::default()
+            });
+            commands.insert_resource(NoiseTextures(images.add(image)));
         };
 
         app.register_type::<HikariUniversalSettings>()
             .register_type::<HikariSettings>()
             .register_type::<Taa>()
             .register_type::<Upscale>()
+            .register_type::<UpscaleQuality>()
+            .register_type::<HikariLightUnits>()
+            .register_type::<HikariExposure>()
+            .register_type::<HikariToneMapping>()
+            .register_type::<HikariDenoiseSettings>()
+            .register_type::<DenoiseSignalSettings>()
+            .register_type::<HikariLens>()
+            .register_type::<HikariMotionBlur>()
+            .register_type::<RenderMode>()
+            .register_type::<HikariDebugView>()
+            .register_type::<LightingPass>()
+            .register_type::<HikariRectLight>()
+            .register_type::<HikariDiskLight>()
+            .register_type::<HikariSphereLight>()
+            .register_type::<HikariTubeLight>()
             .init_resource::<HikariUniversalSettings>()
-            .add_plugin(ExtractResourcePlugin::<NoiseTextures>::default())
+            .init_asset_loader::<CubeLutLoader>();
+
+        if !probe_support(app) {
+            warn!(
+                "Path tracing is not supported on this device, falling back to core 3d: {:?}",
+                app.world.resource::<HikariSupport>()
+            );
+            let ready = HikariPipelinesReady::default();
+            ready.set_empty();
+            app.insert_resource(ready)
+                .add_system_to_stage(CoreStage::PostUpdate, fallback_render_graph_system);
+            return;
+        }
+
+        app.add_plugin(ExtractResourcePlugin::<NoiseTextures>::default())
             .add_plugin(ExtractResourcePlugin::<HikariUniversalSettings>::default())
             .add_plugin(ExtractComponentPlugin::<HikariSettings>::default())
+            .add_plugin(ExtractComponentPlugin::<HikariLens>::default())
+            .add_plugin(ExtractComponentPlugin::<HikariMotionBlur>::default())
             .add_plugin(TransformPlugin)
             .add_plugin(ViewPlugin)
             .add_plugin(MeshMaterialPlugin)
             .add_plugin(PrepassPlugin)
             .add_plugin(LightPlugin)
+            .add_plugin(ExposurePlugin)
             .add_plugin(PostProcessPlugin)
             .add_plugin(OverlayPlugin)
+            .add_plugin(WarmUpPlugin)
+            .add_plugin(AccumulationPlugin)
+            .add_plugin(DynamicResolutionPlugin)
+            .add_plugin(DebugPlugin)
+            .add_plugin(AovPlugin)
+            .add_plugin(CapturePlugin)
             .add_startup_system(noise_load_system);
 
         if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
@@ -241,6 +363,9 @@ impl Plugin for HikariPlugin {
             let prepass_node = PrepassNode::new(&mut render_app.world);
             let light_node = LightNode::new(&mut render_app.world);
             let post_process_node = PostProcessNode::new(&mut render_app.world);
+            let debug_node = DebugNode::new(&mut render_app.world);
+            let aov_node = AovNode::new(&mut render_app.world);
+            let capture_node = CaptureNode::new(&mut render_app.world);
             let overlay_node = OverlayNode::new(&mut render_app.world);
             let bloom_node = BloomNode::new(&mut render_app.world);
             let tonemapping_node = TonemappingNode::new(&mut render_app.world);
@@ -258,6 +383,9 @@ impl Plugin for HikariPlugin {
             sub_graph.add_node(graph::node::PREPASS, prepass_node);
             sub_graph.add_node(graph::node::LIGHT, light_node);
             sub_graph.add_node(graph::node::POST_PROCESS, post_process_node);
+            sub_graph.add_node(graph::node::DEBUG, debug_node);
+            sub_graph.add_node(graph::node::AOV, aov_node);
+            sub_graph.add_node(graph::node::CAPTURE, capture_node);
             sub_graph.add_node(graph::node::OVERLAY, overlay_node);
             sub_graph.add_node(core_3d::graph::node::BLOOM, bloom_node);
             sub_graph.add_node(core_3d::graph::node::TONEMAPPING, tonemapping_node);
@@ -288,6 +416,30 @@ impl Plugin for HikariPlugin {
                     PostProcessNode::IN_VIEW,
                 )
                 .unwrap();
+            sub_graph
+                .add_slot_edge(
+                    sub_graph.input_node().unwrap().id,
+                    core_3d::graph::input::VIEW_ENTITY,
+                    graph::node::DEBUG,
+                    DebugNode::IN_VIEW,
+                )
+                .unwrap();
+            sub_graph
+                .add_slot_edge(
+                    sub_graph.input_node().unwrap().id,
+                    core_3d::graph::input::VIEW_ENTITY,
+                    graph::node::AOV,
+                    AovNode::IN_VIEW,
+                )
+                .unwrap();
+            sub_graph
+                .add_slot_edge(
+                    sub_graph.input_node().unwrap().id,
+                    core_3d::graph::input::VIEW_ENTITY,
+                    graph::node::CAPTURE,
+                    CaptureNode::IN_VIEW,
+                )
+                .unwrap();
             sub_graph
                 .add_slot_edge(
                     sub_graph.input_node().unwrap().id,
@@ -329,7 +481,7 @@ impl Plugin for HikariPlugin {
                 )
                 .unwrap();
 
-            // PREPASS -> LIGHT -> POST_PROCESS -> OVERLAY -> BLOOM -> TONEMAPPING -> UPSCALING
+            // PREPASS -> LIGHT -> POST_PROCESS -> DEBUG -> AOV -> CAPTURE -> OVERLAY -> BLOOM -> TONEMAPPING -> UPSCALING
             sub_graph
                 .add_node_edge(graph::node::PREPASS, graph::node::LIGHT)
                 .unwrap();
@@ -337,7 +489,16 @@ impl Plugin for HikariPlugin {
                 .add_node_edge(graph::node::LIGHT, graph::node::POST_PROCESS)
                 .unwrap();
             sub_graph
-                .add_node_edge(graph::node::POST_PROCESS, graph::node::OVERLAY)
+                .add_node_edge(graph::node::POST_PROCESS, graph::node::DEBUG)
+                .unwrap();
+            sub_graph
+                .add_node_edge(graph::node::DEBUG, graph::node::AOV)
+                .unwrap();
+            sub_graph
+                .add_node_edge(graph::node::AOV, graph::node::CAPTURE)
+                .unwrap();
+            sub_graph
+                .add_node_edge(graph::node::CAPTURE, graph::node::OVERLAY)
                 .unwrap();
             sub_graph
                 .add_node_edge(
@@ -377,6 +538,14 @@ pub struct HikariUniversalSettings {
     pub build_mesh_acceleration_structure: bool,
     /// Whether to build acceleration structure for scene instances.
     pub build_instance_acceleration_structure: bool,
+    /// Number of material texture slots to reserve up front.
+    /// Light pipelines are only recompiled when the texture count exceeds the reserved capacity.
+    pub texture_capacity: usize,
+    /// Whether to generate mip chains for material textures that lack them.
+    /// Textures hit by secondary rays are sampled at a mip level chosen by ray cones.
+    pub generate_mipmaps: bool,
+    /// How light intensities are interpreted.
+    pub light_units: HikariLightUnits,
 }
 
 impl Default for HikariUniversalSettings {
@@ -384,10 +553,33 @@ impl Default for HikariUniversalSettings {
         Self {
             build_mesh_acceleration_structure: true,
             build_instance_acceleration_structure: true,
+            texture_capacity: 0,
+            generate_mipmaps: true,
+            light_units: HikariLightUnits::default(),
         }
     }
 }
 
+/// Units of light intensities.
+///
+/// Bevy bakes the fixed exposure of a camera at f/4, 1/250s and ISO 100 ([`HikariExposure::BEVY_EV100`])
+/// into the colors of its lights, and adds emissive colors to the output as they are.
+/// In [`HikariLightUnits::Physical`], the path tracer works in the same pre-exposed units:
+/// - `DirectionalLight::illuminance` is in lux;
+/// - `AmbientLight` and `StandardMaterial::emissive` are luminances in units of `1.2 * 2^BEVY_EV100` (4800) nits;
+/// - an emissive surface of luminance `L` and area `A` emits `π * L * A` lumens.
+///
+/// [`HikariExposure`] then maps the absolute luminance to the output, so scenes lit for bevy's renderer
+/// look the same with the default exposure.
+#[derive(Debug, Default, Clone, Copy, Hash, PartialEq, Eq, Reflect)]
+pub enum HikariLightUnits {
+    /// Units consistent with bevy's standard renderer.
+    #[default]
+    Physical,
+    /// Emissive radiance of `255 * emissive.a * emissive.rgb`, as in previous versions.
+    Legacy,
+}
+
 impl ExtractResource for HikariUniversalSettings {
     type Source = Self;
 
@@ -424,12 +616,35 @@ pub struct HikariSettings {
     pub emissive_spatial_reuse: bool,
     /// Whether to do spatial sample reuse for indirect lighting in ReSTIR.
     pub indirect_spatial_reuse: bool,
+    /// Whether to reuse samples of direct lighting without bias, see [`HikariSettings::indirect_unbiased_reuse`].
+    pub direct_unbiased_reuse: bool,
+    /// Whether to reuse samples of emissive lighting without bias, see [`HikariSettings::indirect_unbiased_reuse`].
+    pub emissive_unbiased_reuse: bool,
+    /// Whether to reuse samples of indirect lighting without bias.
+    /// Reused samples are traced for visibility and weighted by the target function of the pixel,
+    /// and the reservoirs are normalized by the neighbors whose domains contain the selected sample.
+    /// This removes the darkening and light leaking of the default mode near contact shadows,
+    /// at the cost of a shadow ray per reused reservoir, and another per spatial neighbor.
+    pub indirect_unbiased_reuse: bool,
     /// Whether to do noise filtering.
     pub denoise: bool,
+    /// Parameters of the noise filter for each lighting signal.
+    pub denoise_settings: HikariDenoiseSettings,
     /// Which temporal filtering implementation to use.
     pub taa: Taa,
     /// Which upscaling implementation to use.
     pub upscale: Upscale,
+    /// Exposure applied to the radiance before tone mapping.
+    pub exposure: HikariExposure,
+    /// Which tone mapping operator to use.
+    /// If not set, HDR cameras output linear radiance to bevy's tone mapping, and others use Reinhard.
+    pub tone_mapping: Option<HikariToneMapping>,
+    /// 3D color grading LUT loaded from a `.cube` file, applied after tone mapping.
+    pub color_grading: Option<Handle<Image>>,
+    /// Whether to render in real time, or to accumulate a reference image.
+    pub render_mode: RenderMode,
+    /// Diagnostic view to show instead of the final output.
+    pub debug_view: HikariDebugView,
 }
 
 impl Default for HikariSettings {
@@ -447,13 +662,29 @@ impl Default for HikariSettings {
             temporal_reuse: true,
             emissive_spatial_reuse: false,
             indirect_spatial_reuse: true,
+            direct_unbiased_reuse: false,
+            emissive_unbiased_reuse: false,
+            indirect_unbiased_reuse: false,
             denoise: true,
+            denoise_settings: HikariDenoiseSettings::default(),
             taa: Taa::default(),
             upscale: Upscale::default(),
+            exposure: HikariExposure::default(),
+            tone_mapping: None,
+            color_grading: None,
+            render_mode: RenderMode::default(),
+            debug_view: HikariDebugView::default(),
         }
     }
 }
 
+impl HikariSettings {
+    /// Whether samples are accumulated across frames instead of being reused and denoised.
+    pub fn is_accumulating(&self) -> bool {
+        matches!(self.render_mode, RenderMode::Accumulation { .. })
+    }
+}
+
 impl ExtractComponent for HikariSettings {
     type Query = &'static Self;
     type Filter = ();
@@ -486,9 +717,268 @@ pub enum Upscale {
         /// Renders the main pass and post process on a low resolution texture.
         ratio: f32,
     },
+    /// Temporal super resolution, which accumulates jittered low resolution samples at the output resolution.
+    /// Replaces [`Taa`] if selected.
+    Tsr {
+        /// Renders the main pass and post process on a low resolution texture, from 1.0 to 3.0.
+        ratio: f32,
+    },
     None,
 }
 
+/// Presets of the render ratio for [`Upscale::Tsr`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum UpscaleQuality {
+    /// 1.5x per dimension.
+    #[default]
+    Quality,
+    /// 1.7x per dimension.
+    Balanced,
+    /// 2x per dimension.
+    Performance,
+    /// 3x per dimension.
+    UltraPerformance,
+}
+
+impl UpscaleQuality {
+    pub fn ratio(&self) -> f32 {
+        match self {
+            UpscaleQuality::Quality => 1.5,
+            UpscaleQuality::Balanced => 1.7,
+            UpscaleQuality::Performance => 2.0,
+            UpscaleQuality::UltraPerformance => 3.0,
+        }
+    }
+}
+
+/// Exposure of the radiance, in exposure values at ISO 100.
+/// An exposure value of `ev100` maps the luminance `1.2 * 2^ev100` to 1.0.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Reflect)]
+pub enum HikariExposure {
+    /// The fixed exposure of bevy's standard renderer ([`HikariExposure::BEVY_EV100`]), so radiance is not scaled.
+    #[default]
+    None,
+    /// Fixed exposure value.
+    Manual { ev100: f32 },
+    /// Exposure metered from a luminance histogram of the image, with the darkest and brightest pixels ignored.
+    Auto {
+        /// Lower bound of the metered exposure value.
+        min_ev100: f32,
+        /// Upper bound of the metered exposure value.
+        max_ev100: f32,
+        /// Exposure compensation in stops; positive values brighten the image.
+        compensation: f32,
+        /// Rate in 1/seconds at which the exposure adapts when the scene gets brighter.
+        speed_brighten: f32,
+        /// Rate in 1/seconds at which the exposure adapts when the scene gets darker.
+        speed_darken: f32,
+    },
+}
+
+impl HikariExposure {
+    /// Exposure value of bevy's standard renderer, whose light colors are pre-exposed at f/4, 1/250s and ISO 100.
+    pub const BEVY_EV100: f32 = 11.965784;
+
+    /// Auto exposure with the metering range of typical indoor and outdoor scenes.
+    pub const AUTO: Self = Self::Auto {
+        min_ev100: -8.0,
+        max_ev100: 16.0,
+        compensation: 0.0,
+        speed_brighten: 3.0,
+        speed_darken: 1.0,
+    };
+
+    /// Manual exposure from physical camera parameters:
+    /// the f-number of the aperture, the shutter time in seconds, and the ISO sensitivity.
+    pub fn physical(aperture: f32, shutter_speed: f32, sensitivity: f32) -> Self {
+        let ev100 = (aperture * aperture / shutter_speed * 100.0 / sensitivity).log2();
+        Self::Manual { ev100 }
+    }
+}
+
+/// Tone mapping operator applied to the anti-aliased radiance.
+///
+/// Anti-aliasing and upscaling run on an invertible compression of the radiance,
+/// which is undone before the operator is applied, so `None` outputs exact linear radiance.
+#[derive(Debug, Default, Clone, Copy, Hash, PartialEq, Eq, Reflect, FromReflect)]
+pub enum HikariToneMapping {
+    /// Linear radiance, without tone mapping or color grading.
+    None,
+    /// Reinhard on luminance.
+    #[default]
+    Reinhard,
+    /// [Fitted ACES](https://github.com/TheRealMJP/BakingLab/blob/master/BakingLab/ACES.hlsl) by Stephen Hill.
+    AcesFitted,
+    /// [AgX](https://github.com/sobotka/AgX), with the polynomial sigmoid fit of its default look.
+    AgX,
+    /// Analytic approximation of [Tony McMapface](https://github.com/h3r2tic/tony-mc-mapface),
+    /// which preserves hues and desaturates highlights towards white.
+    TonyMcMapface,
+}
+
+/// Parameters of the edge-avoiding à-trous wavelet filter for each lighting signal.
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+pub struct HikariDenoiseSettings {
+    pub direct: DenoiseSignalSettings,
+    pub emissive: DenoiseSignalSettings,
+    pub indirect: DenoiseSignalSettings,
+}
+
+impl Default for HikariDenoiseSettings {
+    fn default() -> Self {
+        Self {
+            direct: DenoiseSignalSettings {
+                firefly_filtering: false,
+                ..Default::default()
+            },
+            emissive: Default::default(),
+            indirect: Default::default(),
+        }
+    }
+}
+
+impl HikariDenoiseSettings {
+    /// Settings of the direct, emissive and indirect signals, in this order.
+    pub fn signals(&self) -> [DenoiseSignalSettings; 3] {
+        [self.direct, self.emissive, self.indirect]
+    }
+}
+
+/// Parameters of the noise filter of one lighting signal.
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+pub struct DenoiseSignalSettings {
+    /// Number of filter iterations, up to [`DenoiseSignalSettings::MAX_ITERATIONS`].
+    /// The step size halves with each iteration down to 1 pixel. The signal is not filtered if zero.
+    pub iterations: u32,
+    /// Radius of the B-spline kernel of each iteration: 1 for 3x3, 2 for 5x5.
+    pub kernel_radius: u32,
+    /// Whether to accumulate the signal over frames before filtering, which also estimates its variance temporally.
+    /// Pixels with a short history, e.g., newly revealed surfaces, are filtered wider.
+    pub temporal_accumulation: bool,
+    /// Exponent of the normal weight; higher values preserve geometric edges better.
+    pub normal_sigma: f32,
+    /// Scale of the depth difference tolerated along the depth gradient.
+    pub depth_sigma: f32,
+    /// Scale of the luminance difference tolerated relative to the variance.
+    pub luminance_sigma: f32,
+    /// Whether to clamp pixels brighter than 3 standard deviations above their neighborhood.
+    pub firefly_filtering: bool,
+}
+
+impl Default for DenoiseSignalSettings {
+    fn default() -> Self {
+        Self {
+            iterations: Self::MAX_ITERATIONS,
+            kernel_radius: 1,
+            temporal_accumulation: true,
+            normal_sigma: 16.0,
+            depth_sigma: 1.0,
+            luminance_sigma: 4.0,
+            firefly_filtering: true,
+        }
+    }
+}
+
+impl DenoiseSignalSettings {
+    pub const MAX_ITERATIONS: u32 = 4;
+    pub const MAX_KERNEL_RADIUS: u32 = 2;
+
+    pub fn is_enabled(&self) -> bool {
+        self.iterations > 0
+    }
+}
+
+/// Thin lens camera model for depth of field, attached on cameras alongside [`HikariSettings`].
+///
+/// The defocus is gathered from the render with the lens sampled by the temporal jitter,
+/// so it converges with temporal anti-aliasing or in accumulation mode.
+#[derive(Debug, Clone, Copy, Component, Reflect)]
+#[reflect(Component)]
+pub struct HikariLens {
+    /// Radius of the aperture in world units. Depth of field is disabled if zero.
+    pub aperture: f32,
+    /// Distance from the camera to the plane in focus.
+    pub focus_distance: f32,
+    /// Number of aperture blades, which shape the bokeh as a polygon. The bokeh is round for fewer than 3.
+    pub blades: u32,
+    /// Rotation of the aperture polygon in radians.
+    pub blade_rotation: f32,
+}
+
+impl Default for HikariLens {
+    fn default() -> Self {
+        Self {
+            aperture: 0.01,
+            focus_distance: 10.0,
+            blades: 0,
+            blade_rotation: 0.0,
+        }
+    }
+}
+
+impl HikariLens {
+    /// Lens from the f-number and the focal length in world units, e.g., 0.05 for a 50mm lens in meters.
+    pub fn physical(f_number: f32, focal_length: f32, focus_distance: f32) -> Self {
+        Self {
+            aperture: 0.5 * focal_length / f_number,
+            focus_distance,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.aperture > 0.0
+    }
+}
+
+impl ExtractComponent for HikariLens {
+    type Query = &'static Self;
+    type Filter = ();
+
+    fn extract_component(item: QueryItem<Self::Query>) -> Self {
+        *item
+    }
+}
+
+/// Motion blur from the velocity of the prepass, attached on cameras alongside [`HikariSettings`].
+///
+/// Blur is applied before anti-aliasing, and its extent is limited to [`HikariMotionBlur::MAX_RADIUS`] pixels.
+#[derive(Debug, Clone, Copy, Component, Reflect)]
+#[reflect(Component)]
+pub struct HikariMotionBlur {
+    /// Fraction of the frame time that the shutter is open, in degrees: 360 blurs over the whole frame.
+    pub shutter_angle: f32,
+    /// Number of samples along the motion of each pixel.
+    pub samples: u32,
+}
+
+impl Default for HikariMotionBlur {
+    fn default() -> Self {
+        Self {
+            shutter_angle: 180.0,
+            samples: 8,
+        }
+    }
+}
+
+impl HikariMotionBlur {
+    /// Size in pixels of the velocity tiles, which bounds the blur radius.
+    pub const MAX_RADIUS: u32 = 16;
+
+    pub fn is_enabled(&self) -> bool {
+        self.shutter_angle > 0.0 && self.samples > 0
+    }
+}
+
+impl ExtractComponent for HikariMotionBlur {
+    type Query = &'static Self;
+    type Filter = ();
+
+    fn extract_component(item: QueryItem<Self::Query>) -> Self {
+        *item
+    }
+}
+
 impl Default for Upscale {
     fn default() -> Self {
         Self::SmaaTu4x { ratio: 2.0 }
@@ -496,13 +986,46 @@ impl Default for Upscale {
 }
 
 impl Upscale {
+    /// Temporal super resolution with the ratio of a quality preset.
+    pub fn tsr(quality: UpscaleQuality) -> Self {
+        Self::Tsr {
+            ratio: quality.ratio(),
+        }
+    }
+
     pub fn ratio(&self) -> f32 {
         match self {
-            Upscale::Fsr1 { ratio, .. } | Upscale::SmaaTu4x { ratio } => ratio.clamp(1.0, 2.0),
+            Upscale::Fsr1 { ratio, .. } | Upscale::SmaaTu4x { ratio } | Upscale::Tsr { ratio } => {
+                ratio.clamp(1.0, self.max_ratio())
+            }
             Upscale::None => 1.0,
         }
     }
 
+    /// The highest ratio supported by the upscale method.
+    pub fn max_ratio(&self) -> f32 {
+        match self {
+            Upscale::Fsr1 { .. } | Upscale::SmaaTu4x { .. } => 2.0,
+            Upscale::Tsr { .. } => 3.0,
+            Upscale::None => 1.0,
+        }
+    }
+
+    /// Sets the ratio of the upscale method, if any.
+    pub fn set_ratio(&mut self, value: f32) {
+        match self {
+            Upscale::Fsr1 { ratio, .. } | Upscale::SmaaTu4x { ratio } | Upscale::Tsr { ratio } => {
+                *ratio = value
+            }
+            Upscale::None => {}
+        }
+    }
+
+    /// Whether the upscale method accumulates samples over frames, replacing [`Taa`].
+    pub fn is_temporal(&self) -> bool {
+        matches!(self, Upscale::Tsr { .. })
+    }
+
     pub fn sharpness(&self) -> f32 {
         match self {
             Upscale::Fsr1 { sharpness, .. } => *sharpness,
@@ -511,8 +1034,63 @@ impl Upscale {
     }
 }
 
+/// How frames are rendered.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum RenderMode {
+    /// Reuses samples with ReSTIR and filters the result for real-time rendering.
+    #[default]
+    Realtime,
+    /// Accumulates unbiased samples while the camera and the scene are static,
+    /// bypassing ReSTIR reuse and the denoiser. Restarts whenever anything changes.
+    Accumulation {
+        /// Number of samples per pixel to accumulate before the image is complete.
+        samples: usize,
+    },
+}
+
+/// Diagnostic views that replace the final output.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum HikariDebugView {
+    #[default]
+    None,
+    /// Heatmap of BVH nodes visited by the rays of a lighting pass, in log scale up to 4096 nodes.
+    Traversal(LightingPass),
+    /// Random colors by instance index.
+    InstanceId,
+    /// Random colors by material index.
+    MaterialId,
+    /// Sample count (M) of the temporal reservoirs of a lighting pass, relative to the max temporal reuse count.
+    ReservoirCount(LightingPass),
+    /// Age of the temporal reservoir samples of a lighting pass, relative to the max reservoir lifetime.
+    ReservoirLifetime(LightingPass),
+    /// Luminance variance of a lighting pass.
+    Variance(LightingPass),
+    /// Screen space gradient of the depth buffer.
+    DepthGradient,
+    /// Motion vectors, with zero velocity in gray.
+    Velocity,
+    /// Raw radiance of a lighting pass.
+    Radiance(LightingPass),
+}
+
+impl HikariDebugView {
+    pub fn is_enabled(&self) -> bool {
+        *self != Self::None
+    }
+}
+
+/// Lighting passes of the path tracer.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Reflect, FromReflect)]
+pub enum LightingPass {
+    #[default]
+    Direct,
+    Emissive,
+    Indirect,
+}
+
+/// Blue noise textures, stacked as layers of a 2D array texture.
 #[derive(Clone, Deref, Resource, ExtractResource)]
-pub struct NoiseTextures(pub Vec<Handle<Image>>);
+pub struct NoiseTextures(pub Handle<Image>);
 
 impl AsBindGroup for NoiseTextures {
     type Data = ();
@@ -522,20 +1100,14 @@ impl AsBindGroup for NoiseTextures {
         layout: &BindGroupLayout,
         render_device: &RenderDevice,
         images: &RenderAssets<Image>,
-        fallback_image: &FallbackImage,
+        _fallback_image: &FallbackImage,
     ) -> Result<PreparedBindGroup<Self>, AsBindGroupError> {
-        let images: Vec<_> = self
-            .iter()
-            .map(|handle| match images.get(handle) {
-                Some(image) => image,
-                None => fallback_image,
-            })
-            .collect();
-        let texture_views: Vec<_> = images.iter().map(|image| &*image.texture_view).collect();
-        let bindings = images
-            .iter()
-            .map(|image| OwnedBindingResource::TextureView(image.texture_view.clone()))
-            .collect();
+        let image = images
+            .get(&self.0)
+            .ok_or(AsBindGroupError::RetryNextUpdate)?;
+        let bindings = vec![OwnedBindingResource::TextureView(
+            image.texture_view.clone(),
+        )];
 
         let sampler = render_device.create_sampler(&SamplerDescriptor {
             label: None,
@@ -554,7 +1126,7 @@ impl AsBindGroup for NoiseTextures {
             entries: &[
                 BindGroupEntry {
                     binding: 0,
-                    resource: BindingResource::TextureViewArray(&texture_views),
+                    resource: BindingResource::TextureView(&image.texture_view),
                 },
                 BindGroupEntry {
                     binding: 1,
@@ -580,10 +1152,10 @@ impl AsBindGroup for NoiseTextures {
                     visibility: ShaderStages::COMPUTE,
                     ty: BindingType::Texture {
                         sample_type: TextureSampleType::Float { filterable: false },
-                        view_dimension: TextureViewDimension::D2,
+                        view_dimension: TextureViewDimension::D2Array,
                         multisampled: false,
                     },
-                    count: NonZeroU32::new(NOISE_TEXTURE_COUNT as u32),
+                    count: None,
                 },
                 BindGroupLayoutEntry {
                     binding: 1,
@@ -1,11 +1,15 @@
 use crate::{
+    accumulation::HikariAccumulationProgress,
     mesh_material::{
-        MeshMaterialBindGroup, MeshMaterialBindGroupLayout, MeshMaterialSystems,
-        TextureBindGroupLayout,
+        create_texture_layout, texture_capacity, MeshMaterialBindGroup,
+        MeshMaterialBindGroupLayout, MeshMaterialSystems, TextureBindGroupLayout,
+        TextureBindingMode,
     },
     prepass::{DeferredBindGroup, PrepassBindGroup, PrepassPipeline, PrepassTextures},
     view::{FrameCounter, FrameUniform, PreviousViewUniformOffset},
-    HikariSettings, NoiseTextures, LIGHT_SHADER_HANDLE, WORKGROUP_SIZE,
+    warm_up::HikariWarmUp,
+    HikariDebugView, HikariLightUnits, HikariSettings, HikariUniversalSettings, NoiseTextures,
+    LIGHT_SHADER_HANDLE, WORKGROUP_SIZE,
 };
 use bevy::{
     pbr::ViewLightsUniformOffset,
@@ -29,6 +33,8 @@ use serde::Serialize;
 pub const ALBEDO_TEXTURE_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
 pub const VARIANCE_TEXTURE_FORMAT: TextureFormat = TextureFormat::R32Float;
 pub const RENDER_TEXTURE_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+pub const ACCUMULATION_TEXTURE_FORMAT: TextureFormat = TextureFormat::Rgba32Float;
+pub const AREA_LIGHT_TEXTURE_FORMAT: TextureFormat = TextureFormat::R32Uint;
 
 pub struct LightPlugin;
 impl Plugin for LightPlugin {
@@ -68,12 +74,13 @@ pub struct GpuReservoirBuffer {
 #[derive(Default, Resource, Deref, DerefMut)]
 pub struct ReservoirCache(HashMap<Entity, Vec<StorageBuffer<GpuReservoirBuffer>>>);
 
-#[derive(Resource)]
+#[derive(Resource, Clone)]
 pub struct LightPipeline {
     pub view_layout: BindGroupLayout,
     pub deferred_layout: BindGroupLayout,
     pub mesh_material_layout: BindGroupLayout,
 
+    pub texture_binding: TextureBindingMode,
     pub texture_count: u32,
     pub texture_layout: BindGroupLayout,
 
@@ -100,6 +107,9 @@ bitflags::bitflags! {
         const EMISSIVE_LIT_BIT      = 1 << LightPipelineKey::EMISSIVE_LIT_SHIFT_BITS;
         const RENDER_EMISSIVE_BIT   = 1 << LightPipelineKey::RENDER_EMISSIVE_SHIFT_BITS;
         const MULTIPLE_BOUNCES_BIT  = 1 << LightPipelineKey::MULTIPLE_BOUNCES_SHIFT_BITS;
+        const TEXTURE_ATLAS_BIT     = 1 << LightPipelineKey::TEXTURE_ATLAS_SHIFT_BITS;
+        const DEBUG_TRAVERSAL_BIT   = 1 << LightPipelineKey::DEBUG_TRAVERSAL_SHIFT_BITS;
+        const LEGACY_LIGHT_UNITS_BIT = 1 << LightPipelineKey::LEGACY_LIGHT_UNITS_SHIFT_BITS;
         const TEXTURE_COUNT_BITS    = LightPipelineKey::TEXTURE_COUNT_MASK_BITS << LightPipelineKey::TEXTURE_COUNT_SHIFT_BITS;
     }
 }
@@ -109,6 +119,9 @@ impl LightPipelineKey {
     const EMISSIVE_LIT_SHIFT_BITS: u32 = 4;
     const RENDER_EMISSIVE_SHIFT_BITS: u32 = 5;
     const MULTIPLE_BOUNCES_SHIFT_BITS: u32 = 6;
+    const TEXTURE_ATLAS_SHIFT_BITS: u32 = 7;
+    const DEBUG_TRAVERSAL_SHIFT_BITS: u32 = 8;
+    const LEGACY_LIGHT_UNITS_SHIFT_BITS: u32 = 9;
     const TEXTURE_COUNT_MASK_BITS: u32 = 0xFFFF;
     const TEXTURE_COUNT_SHIFT_BITS: u32 = 32 - 16;
 
@@ -138,7 +151,9 @@ impl SpecializedComputePipeline for LightPipeline {
 
     fn specialize(&self, key: Self::Key) -> ComputePipelineDescriptor {
         let mut shader_defs = vec![];
-        if key.texture_count() == 0 {
+        if key.contains(LightPipelineKey::TEXTURE_ATLAS_BIT) {
+            shader_defs.push("TEXTURE_ATLAS".into());
+        } else if key.texture_count() == 0 {
             shader_defs.push("NO_TEXTURE".into());
         }
         if key.contains(LightPipelineKey::EMISSIVE_LIT_BIT) {
@@ -150,6 +165,12 @@ impl SpecializedComputePipeline for LightPipeline {
         if key.contains(LightPipelineKey::MULTIPLE_BOUNCES_BIT) {
             shader_defs.push("MULTIPLE_BOUNCES".into());
         }
+        if key.contains(LightPipelineKey::DEBUG_TRAVERSAL_BIT) {
+            shader_defs.push("DEBUG_TRAVERSAL".into());
+        }
+        if key.contains(LightPipelineKey::LEGACY_LIGHT_UNITS_BIT) {
+            shader_defs.push("LEGACY_LIGHT_UNITS".into());
+        }
 
         let entry_point = serde_variant::to_variant_name(&key.entry_point())
             .unwrap()
@@ -177,6 +198,7 @@ fn prepare_light_pipeline(
     mut commands: Commands,
     render_device: Res<RenderDevice>,
     mesh_material_layout: Res<MeshMaterialBindGroupLayout>,
+    texture_binding: Res<TextureBindingMode>,
     texture_layout: Res<TextureBindGroupLayout>,
     prepass_pipeline: Res<PrepassPipeline>,
 ) {
@@ -229,6 +251,50 @@ fn prepare_light_pipeline(
                 },
                 count: None,
             },
+            // Accumulation Texture
+            BindGroupLayoutEntry {
+                binding: 3,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::StorageTexture {
+                    access: StorageTextureAccess::ReadWrite,
+                    format: ACCUMULATION_TEXTURE_FORMAT,
+                    view_dimension: TextureViewDimension::D2,
+                },
+                count: None,
+            },
+            // Specular Albedo Texture
+            BindGroupLayoutEntry {
+                binding: 4,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::StorageTexture {
+                    access: StorageTextureAccess::ReadWrite,
+                    format: ALBEDO_TEXTURE_FORMAT,
+                    view_dimension: TextureViewDimension::D2,
+                },
+                count: None,
+            },
+            // Specular Texture
+            BindGroupLayoutEntry {
+                binding: 5,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::StorageTexture {
+                    access: StorageTextureAccess::ReadWrite,
+                    format: RENDER_TEXTURE_FORMAT,
+                    view_dimension: TextureViewDimension::D2,
+                },
+                count: None,
+            },
+            // Area Light Texture
+            BindGroupLayoutEntry {
+                binding: 6,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::StorageTexture {
+                    access: StorageTextureAccess::ReadWrite,
+                    format: AREA_LIGHT_TEXTURE_FORMAT,
+                    view_dimension: TextureViewDimension::D2,
+                },
+                count: None,
+            },
         ],
     });
 
@@ -286,6 +352,7 @@ fn prepare_light_pipeline(
         view_layout,
         deferred_layout,
         mesh_material_layout,
+        texture_binding: *texture_binding,
         texture_count,
         texture_layout,
         noise_layout,
@@ -299,8 +366,16 @@ pub struct LightTextures {
     /// Index of the current frame's output denoised texture.
     pub head: usize,
     pub albedo: TextureView,
+    /// Specular part of the albedo, with the roughness in alpha.
+    pub specular_albedo: TextureView,
     pub variance: [TextureView; 3],
     pub render: [TextureView; 3],
+    /// Specular part of the renders, which is denoised separately from the diffuse part.
+    pub specular: [TextureView; 3],
+    /// Sums of accumulated samples, only allocated in full size in accumulation mode.
+    pub accumulation: [TextureView; 3],
+    /// Index of the area light seen by the camera at each deferred pixel, written by the albedo pass.
+    pub area_light: TextureView,
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -370,13 +445,27 @@ fn prepare_light_textures(
 
             let variance = create_texture_array![VARIANCE_TEXTURE_FORMAT, scaled_size; 3];
             let render = create_texture_array![RENDER_TEXTURE_FORMAT, scaled_size; 3];
+            let specular = create_texture_array![RENDER_TEXTURE_FORMAT, scaled_size; 3];
             let albedo = create_texture(ALBEDO_TEXTURE_FORMAT, size);
+            let specular_albedo = create_texture(ALBEDO_TEXTURE_FORMAT, size);
+            let area_light = create_texture(AREA_LIGHT_TEXTURE_FORMAT, size);
+
+            let accumulation_size = match settings.is_accumulating() {
+                true => scaled_size,
+                false => UVec2::ONE,
+            };
+            let accumulation =
+                create_texture_array![ACCUMULATION_TEXTURE_FORMAT, accumulation_size; 3];
 
             commands.entity(entity).insert(LightTextures {
                 head: counter.0 % 2,
                 albedo,
+                specular_albedo,
                 variance,
                 render,
+                specular,
+                accumulation,
+                area_light,
             });
         }
     }
@@ -391,57 +480,93 @@ pub struct CachedLightPipelines {
     indirect_multiple_bounces: CachedComputePipelineId,
     emissive_spatial_reuse: CachedComputePipelineId,
     indirect_spatial_reuse: CachedComputePipelineId,
+    /// Pipelines compiled ahead of time for larger texture capacities.
+    warm_up: Vec<CachedLightPipelines>,
+    /// Pipelines rendering the BVH traversal cost, only queued if any camera shows it.
+    debug_traversal: Option<Box<CachedLightPipelines>>,
 }
 
-fn queue_light_pipelines(
-    mut commands: Commands,
-    pipeline: Res<LightPipeline>,
-    mut pipelines: ResMut<SpecializedComputePipelines<LightPipeline>>,
-    mut pipeline_cache: ResMut<PipelineCache>,
-) {
-    let key = LightPipelineKey::from_texture_count(pipeline.texture_count);
+impl CachedLightPipelines {
+    /// Ids of all queued pipelines, including the warm-up ones.
+    pub fn ids(&self) -> Vec<CachedComputePipelineId> {
+        let mut ids = vec![
+            self.full_screen_albedo,
+            self.direct_lit,
+            self.direct_emissive,
+            self.indirect,
+            self.indirect_multiple_bounces,
+            self.emissive_spatial_reuse,
+            self.indirect_spatial_reuse,
+        ];
+        ids.extend(self.warm_up.iter().flat_map(|pipelines| pipelines.ids()));
+        ids.extend(
+            self.debug_traversal
+                .iter()
+                .flat_map(|pipelines| pipelines.ids()),
+        );
+        ids
+    }
+}
+
+fn specialize_light_pipelines(
+    pipelines: &mut SpecializedComputePipelines<LightPipeline>,
+    pipeline_cache: &mut PipelineCache,
+    pipeline: &LightPipeline,
+    light_units: HikariLightUnits,
+    debug_traversal: bool,
+) -> CachedLightPipelines {
+    let mut key = LightPipelineKey::from_texture_count(pipeline.texture_count);
+    if pipeline.texture_binding == TextureBindingMode::Atlas {
+        key |= LightPipelineKey::TEXTURE_ATLAS_BIT;
+    }
+    if light_units == HikariLightUnits::Legacy {
+        key |= LightPipelineKey::LEGACY_LIGHT_UNITS_BIT;
+    }
+    if debug_traversal {
+        key |= LightPipelineKey::DEBUG_TRAVERSAL_BIT;
+    }
 
     let full_screen_albedo = {
         let key = key | LightPipelineKey::from_entry_point(LightEntryPoint::FullScreenAlbedo);
-        pipelines.specialize(&mut pipeline_cache, &pipeline, key)
+        pipelines.specialize(pipeline_cache, pipeline, key)
     };
 
     let direct_lit = {
         let key = key
             | LightPipelineKey::from_entry_point(LightEntryPoint::DirectLit)
             | LightPipelineKey::RENDER_EMISSIVE_BIT;
-        pipelines.specialize(&mut pipeline_cache, &pipeline, key)
+        pipelines.specialize(pipeline_cache, pipeline, key)
     };
     let direct_emissive = {
         let key = key
             | LightPipelineKey::from_entry_point(LightEntryPoint::DirectLit)
             | LightPipelineKey::EMISSIVE_LIT_BIT;
-        pipelines.specialize(&mut pipeline_cache, &pipeline, key)
+        pipelines.specialize(pipeline_cache, pipeline, key)
     };
 
     let indirect = {
         let key = key | LightPipelineKey::from_entry_point(LightEntryPoint::IndirectLitAmbient);
-        pipelines.specialize(&mut pipeline_cache, &pipeline, key)
+        pipelines.specialize(pipeline_cache, pipeline, key)
     };
     let indirect_multiple_bounces = {
         let key = key
             | LightPipelineKey::from_entry_point(LightEntryPoint::IndirectLitAmbient)
             | LightPipelineKey::MULTIPLE_BOUNCES_BIT;
-        pipelines.specialize(&mut pipeline_cache, &pipeline, key)
+        pipelines.specialize(pipeline_cache, pipeline, key)
     };
 
     let emissive_spatial_reuse = {
         let key = key
             | LightPipelineKey::from_entry_point(LightEntryPoint::SpatialReuse)
             | LightPipelineKey::EMISSIVE_LIT_BIT;
-        pipelines.specialize(&mut pipeline_cache, &pipeline, key)
+        pipelines.specialize(pipeline_cache, pipeline, key)
     };
     let indirect_spatial_reuse = {
         let key = key | LightPipelineKey::from_entry_point(LightEntryPoint::SpatialReuse);
-        pipelines.specialize(&mut pipeline_cache, &pipeline, key)
+        pipelines.specialize(pipeline_cache, pipeline, key)
     };
 
-    commands.insert_resource(CachedLightPipelines {
+    CachedLightPipelines {
         full_screen_albedo,
         direct_lit,
         direct_emissive,
@@ -449,7 +574,76 @@ fn queue_light_pipelines(
         indirect_multiple_bounces,
         emissive_spatial_reuse,
         indirect_spatial_reuse,
-    })
+        warm_up: vec![],
+        debug_traversal: None,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn queue_light_pipelines(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    pipeline: Res<LightPipeline>,
+    universal_settings: Res<HikariUniversalSettings>,
+    warm_up: Res<HikariWarmUp>,
+    mut warm_up_pipelines: Local<HashMap<u32, LightPipeline>>,
+    mut pipelines: ResMut<SpecializedComputePipelines<LightPipeline>>,
+    mut pipeline_cache: ResMut<PipelineCache>,
+    cameras: Query<&HikariSettings>,
+) {
+    let light_units = universal_settings.light_units;
+    let mut cached = specialize_light_pipelines(
+        &mut pipelines,
+        &mut pipeline_cache,
+        &pipeline,
+        light_units,
+        false,
+    );
+
+    if cameras
+        .iter()
+        .any(|settings| matches!(settings.debug_view, HikariDebugView::Traversal(_)))
+    {
+        cached.debug_traversal = Some(Box::new(specialize_light_pipelines(
+            &mut pipelines,
+            &mut pipeline_cache,
+            &pipeline,
+            light_units,
+            true,
+        )));
+    }
+
+    // Specialize pipelines for every larger capacity bucket up to the warm-up texture count,
+    // so that crossing a bucket boundary later doesn't cause a hitch.
+    if pipeline.is_changed() {
+        warm_up_pipelines.clear();
+    }
+    if pipeline.texture_binding == TextureBindingMode::Array {
+        // Larger layouts would fail to create on this device.
+        let max_capacity = texture_capacity(warm_up.max_texture_count, 0)
+            .min(render_device.limits().max_sampled_textures_per_shader_stage);
+        let mut capacity = (2 * pipeline.texture_count).max(1);
+        while capacity <= max_capacity {
+            let warm_up_pipeline =
+                warm_up_pipelines
+                    .entry(capacity)
+                    .or_insert_with(|| LightPipeline {
+                        texture_count: capacity,
+                        texture_layout: create_texture_layout(&render_device, capacity),
+                        ..pipeline.clone()
+                    });
+            cached.warm_up.push(specialize_light_pipelines(
+                &mut pipelines,
+                &mut pipeline_cache,
+                warm_up_pipeline,
+                light_units,
+                false,
+            ));
+            capacity *= 2;
+        }
+    }
+
+    commands.insert_resource(cached);
 }
 
 #[derive(Component, Clone)]
@@ -494,6 +688,8 @@ fn queue_light_bind_groups(
             let render = [0, 1, 2].map(|id| {
                 let variance = &light.variance[id];
                 let render = &light.render[id];
+                let accumulation = &light.accumulation[id];
+                let specular = &light.specular[id];
 
                 render_device.create_bind_group(&BindGroupDescriptor {
                     label: None,
@@ -511,6 +707,22 @@ fn queue_light_bind_groups(
                             binding: 2,
                             resource: BindingResource::TextureView(render),
                         },
+                        BindGroupEntry {
+                            binding: 3,
+                            resource: BindingResource::TextureView(accumulation),
+                        },
+                        BindGroupEntry {
+                            binding: 4,
+                            resource: BindingResource::TextureView(&light.specular_albedo),
+                        },
+                        BindGroupEntry {
+                            binding: 5,
+                            resource: BindingResource::TextureView(specular),
+                        },
+                        BindGroupEntry {
+                            binding: 6,
+                            resource: BindingResource::TextureView(&light.area_light),
+                        },
                     ],
                 })
             });
@@ -565,6 +777,7 @@ pub struct LightNode {
         &'static DeferredBindGroup,
         &'static LightBindGroup,
         &'static HikariSettings,
+        Option<&'static HikariAccumulationProgress>,
     )>,
 }
 
@@ -603,6 +816,7 @@ impl Node for LightNode {
             deferred_bind_group,
             light_bind_group,
             settings,
+            progress,
         ) = match self.query.get_manual(world, entity) {
             Ok(query) => query,
             Err(_) => return Ok(()),
@@ -616,10 +830,27 @@ impl Node for LightNode {
             None => return Ok(()),
         };
 
+        // Once the target sample count is reached, the accumulated image is kept as is.
+        let accumulating = settings.is_accumulating();
+        if accumulating && !matches!(progress, Some(progress) if progress.accumulating) {
+            return Ok(());
+        }
+
         let pipelines = world.resource::<CachedLightPipelines>();
         let pipeline_cache = world.resource::<PipelineCache>();
 
-        let size = camera.physical_target_size.unwrap();
+        // The traversal cost is written by the temporal passes, so spatial reuse is skipped.
+        let debug_traversal = matches!(settings.debug_view, HikariDebugView::Traversal(_));
+        let pipelines = match &pipelines.debug_traversal {
+            Some(debug_pipelines) if debug_traversal => debug_pipelines,
+            _ => pipelines,
+        };
+        let spatial_reuse = !accumulating && !debug_traversal;
+
+        let size = match camera.physical_target_size {
+            Some(size) => size,
+            None => return Ok(()),
+        };
         let scale = settings.upscale.ratio().recip();
         let scaled_size = (scale * size.as_vec2()).ceil().as_uvec2();
 
@@ -672,8 +903,8 @@ impl Node for LightNode {
                 ],
                 [
                     false,
-                    settings.emissive_spatial_reuse,
-                    settings.indirect_spatial_reuse,
+                    settings.emissive_spatial_reuse && spatial_reuse,
+                    settings.indirect_spatial_reuse && spatial_reuse,
                 ],
             ))
         {
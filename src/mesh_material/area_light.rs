@@ -0,0 +1,309 @@
+use super::{light_tree::LightBounds, GpuAreaLight};
+use bevy::{
+    prelude::*,
+    render::{Extract, RenderApp, RenderStage},
+};
+use std::f32::consts::{FRAC_PI_2, PI};
+
+pub struct AreaLightPlugin;
+impl Plugin for AreaLightPlugin {
+    fn build(&self, app: &mut App) {
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<ExtractedAreaLights>()
+                .add_system_to_stage(RenderStage::Extract, extract_area_lights);
+        }
+    }
+}
+
+/// Rectangular area light in the local XY plane, emitting towards the forward direction (local -Z).
+///
+/// `intensity` is the luminous power in lumens, as for bevy's `PointLight`.
+#[derive(Debug, Clone, Copy, Component, Reflect)]
+#[reflect(Component)]
+pub struct HikariRectLight {
+    pub color: Color,
+    pub intensity: f32,
+    /// Width and height of the rectangle along local X and Y.
+    pub size: Vec2,
+    /// Whether the light is seen by camera rays.
+    pub visible_to_camera: bool,
+}
+
+impl Default for HikariRectLight {
+    fn default() -> Self {
+        Self {
+            color: Color::WHITE,
+            intensity: 800.0,
+            size: Vec2::ONE,
+            visible_to_camera: true,
+        }
+    }
+}
+
+/// Disk area light in the local XY plane, emitting towards the forward direction (local -Z).
+///
+/// `intensity` is the luminous power in lumens, as for bevy's `PointLight`.
+#[derive(Debug, Clone, Copy, Component, Reflect)]
+#[reflect(Component)]
+pub struct HikariDiskLight {
+    pub color: Color,
+    pub intensity: f32,
+    pub radius: f32,
+    /// Whether the light is seen by camera rays.
+    pub visible_to_camera: bool,
+}
+
+impl Default for HikariDiskLight {
+    fn default() -> Self {
+        Self {
+            color: Color::WHITE,
+            intensity: 800.0,
+            radius: 0.5,
+            visible_to_camera: true,
+        }
+    }
+}
+
+/// Spherical area light emitting in all directions.
+///
+/// `intensity` is the luminous power in lumens, as for bevy's `PointLight`.
+#[derive(Debug, Clone, Copy, Component, Reflect)]
+#[reflect(Component)]
+pub struct HikariSphereLight {
+    pub color: Color,
+    pub intensity: f32,
+    pub radius: f32,
+    /// Whether the light is seen by camera rays.
+    pub visible_to_camera: bool,
+}
+
+impl Default for HikariSphereLight {
+    fn default() -> Self {
+        Self {
+            color: Color::WHITE,
+            intensity: 800.0,
+            radius: 0.1,
+            visible_to_camera: true,
+        }
+    }
+}
+
+/// Tube (capsule) area light along local X, emitting in all directions.
+///
+/// `intensity` is the luminous power in lumens, as for bevy's `PointLight`.
+#[derive(Debug, Clone, Copy, Component, Reflect)]
+#[reflect(Component)]
+pub struct HikariTubeLight {
+    pub color: Color,
+    pub intensity: f32,
+    pub radius: f32,
+    /// Length of the tube between the centers of its caps.
+    pub length: f32,
+    /// Whether the light is seen by camera rays.
+    pub visible_to_camera: bool,
+}
+
+impl Default for HikariTubeLight {
+    fn default() -> Self {
+        Self {
+            color: Color::WHITE,
+            intensity: 800.0,
+            radius: 0.05,
+            length: 1.0,
+            visible_to_camera: true,
+        }
+    }
+}
+
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AreaLightKind {
+    Rect = 0,
+    Disk = 1,
+    Sphere = 2,
+    Tube = 3,
+}
+
+impl GpuAreaLight {
+    fn new(
+        kind: AreaLightKind,
+        transform: &GlobalTransform,
+        color: Color,
+        intensity: f32,
+        extent: Vec2,
+        visible_to_camera: bool,
+    ) -> Self {
+        let (_, rotation, position) = transform.to_scale_rotation_translation();
+        let mut light = Self {
+            position,
+            kind: kind as u32,
+            radiance: Vec3::ZERO,
+            visible: visible_to_camera.into(),
+            tangent: rotation * Vec3::X,
+            width: extent.x,
+            bitangent: rotation * Vec3::Y,
+            height: extent.y,
+        };
+
+        // A Lambertian emitter of luminance `L` and area `A` emits `π * L * A` lumens.
+        let area = light.area();
+        let luminance = match area > 0.0 {
+            true => intensity / (PI * area),
+            false => 0.0,
+        };
+        light.radiance = Vec4::from(color.as_linear_rgba_f32()).truncate() * luminance;
+        light
+    }
+
+    fn kind(&self) -> AreaLightKind {
+        match self.kind {
+            0 => AreaLightKind::Rect,
+            1 => AreaLightKind::Disk,
+            2 => AreaLightKind::Sphere,
+            _ => AreaLightKind::Tube,
+        }
+    }
+
+    /// Area of the emitting surface.
+    pub fn area(&self) -> f32 {
+        let (width, height) = (self.width, self.height);
+        match self.kind() {
+            AreaLightKind::Rect => 4.0 * width * height,
+            AreaLightKind::Disk => PI * width * width,
+            AreaLightKind::Sphere => 4.0 * PI * width * width,
+            AreaLightKind::Tube => 4.0 * PI * width * (height + width),
+        }
+    }
+
+    /// Bounds of the light in the light tree.
+    pub fn bounds(&self) -> LightBounds {
+        let (width, height) = (self.width, self.height);
+        // Rect and disk lights only emit forward, while the normals of spheres and tubes span all directions.
+        let (extent, theta_o, two_sided) = match self.kind() {
+            AreaLightKind::Rect => (
+                (self.tangent * width).abs() + (self.bitangent * height).abs(),
+                0.0,
+                false,
+            ),
+            AreaLightKind::Disk => (
+                (self.tangent * width).abs() + (self.bitangent * width).abs(),
+                0.0,
+                false,
+            ),
+            AreaLightKind::Sphere => (Vec3::splat(width), FRAC_PI_2, true),
+            AreaLightKind::Tube => ((self.tangent * height).abs() + width, FRAC_PI_2, true),
+        };
+
+        // Emissive instances emit `luminance * area` into each hemisphere of their two-sided surfaces,
+        // while every point of an area light only emits into the hemisphere outside of it, i.e., half as much.
+        let luminance = self.radiance.dot(Vec3::new(0.2126, 0.7152, 0.0722));
+
+        LightBounds {
+            min: self.position - extent,
+            max: self.position + extent,
+            power: 0.5 * luminance * self.area(),
+            axis: self.bitangent.cross(self.tangent),
+            theta_o,
+            two_sided,
+        }
+    }
+}
+
+/// Area lights of the current frame, only changed if any light is changed.
+#[derive(Default, Resource, Deref)]
+pub struct ExtractedAreaLights(Vec<GpuAreaLight>);
+
+#[allow(clippy::type_complexity)]
+fn extract_area_lights(
+    rect_lights: Extract<
+        Query<(
+            &HikariRectLight,
+            &GlobalTransform,
+            Option<&ComputedVisibility>,
+        )>,
+    >,
+    disk_lights: Extract<
+        Query<(
+            &HikariDiskLight,
+            &GlobalTransform,
+            Option<&ComputedVisibility>,
+        )>,
+    >,
+    sphere_lights: Extract<
+        Query<(
+            &HikariSphereLight,
+            &GlobalTransform,
+            Option<&ComputedVisibility>,
+        )>,
+    >,
+    tube_lights: Extract<
+        Query<(
+            &HikariTubeLight,
+            &GlobalTransform,
+            Option<&ComputedVisibility>,
+        )>,
+    >,
+    mut extracted: ResMut<ExtractedAreaLights>,
+) {
+    let is_visible = |visibility: Option<&ComputedVisibility>| {
+        visibility
+            .into_iter()
+            .all(ComputedVisibility::is_visible_in_hierarchy)
+    };
+
+    let mut lights = vec![];
+    for (light, transform, visibility) in &rect_lights {
+        if is_visible(visibility) {
+            lights.push(GpuAreaLight::new(
+                AreaLightKind::Rect,
+                transform,
+                light.color,
+                light.intensity,
+                0.5 * light.size,
+                light.visible_to_camera,
+            ));
+        }
+    }
+    for (light, transform, visibility) in &disk_lights {
+        if is_visible(visibility) {
+            lights.push(GpuAreaLight::new(
+                AreaLightKind::Disk,
+                transform,
+                light.color,
+                light.intensity,
+                Vec2::new(light.radius, 0.0),
+                light.visible_to_camera,
+            ));
+        }
+    }
+    for (light, transform, visibility) in &sphere_lights {
+        if is_visible(visibility) {
+            lights.push(GpuAreaLight::new(
+                AreaLightKind::Sphere,
+                transform,
+                light.color,
+                light.intensity,
+                Vec2::new(light.radius, 0.0),
+                light.visible_to_camera,
+            ));
+        }
+    }
+    for (light, transform, visibility) in &tube_lights {
+        if is_visible(visibility) {
+            lights.push(GpuAreaLight::new(
+                AreaLightKind::Tube,
+                transform,
+                light.color,
+                light.intensity,
+                Vec2::new(light.radius, 0.5 * light.length),
+                light.visible_to_camera,
+            ));
+        }
+    }
+
+    // Only touch the resource if lights are changed, so that light trees are not rebuilt every frame.
+    if lights != extracted.0 {
+        extracted.0 = lights;
+    }
+}
@@ -0,0 +1,357 @@
+use super::{material::MaterialTextures, MeshMaterialSystems, TextureBindingMode};
+use bevy::{
+    prelude::*,
+    render::{
+        render_resource::*,
+        renderer::{RenderDevice, RenderQueue},
+        Extract, RenderApp, RenderStage,
+    },
+    utils::HashMap,
+};
+
+/// Maximal width or height of the texture atlas.
+pub const MAX_ATLAS_SIZE: u32 = 8192;
+
+/// Size of the white block in the atlas that stands in for textures not loaded yet.
+const WHITE_BLOCK_SIZE: u32 = 4;
+
+pub struct AtlasPlugin;
+impl Plugin for AtlasPlugin {
+    fn build(&self, app: &mut App) {
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<ExtractedAtlasImages>()
+                .init_resource::<TextureAtlas>()
+                .add_system_to_stage(RenderStage::Extract, extract_atlas_images)
+                .add_system_to_stage(
+                    RenderStage::Prepare,
+                    prepare_texture_atlas
+                        .after(MeshMaterialSystems::PrepareTextures)
+                        .before(MeshMaterialSystems::PrepareAssets),
+                );
+        }
+    }
+}
+
+/// A material texture converted into RGBA8 texels.
+#[derive(Debug, Clone)]
+pub struct AtlasImage {
+    pub size: UVec2,
+    pub data: Vec<u8>,
+    pub srgb: bool,
+}
+
+impl AtlasImage {
+    /// Converts the first mip level of an image, or returns `None` if the format is not supported.
+    pub fn from_image(image: &Image) -> Option<Self> {
+        let format = image.texture_descriptor.format;
+        let info = format.describe();
+        let size = UVec2::new(
+            image.texture_descriptor.size.width,
+            image.texture_descriptor.size.height,
+        );
+        let len = (size.x * size.y) as usize * info.block_size as usize;
+        let data = image.data.get(..len)?;
+
+        let data = match format {
+            TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => data.to_vec(),
+            TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => data
+                .chunks_exact(4)
+                .flat_map(|texel| [texel[2], texel[1], texel[0], texel[3]])
+                .collect(),
+            TextureFormat::Rg8Unorm => data
+                .chunks_exact(2)
+                .flat_map(|texel| [texel[0], texel[1], 0, 255])
+                .collect(),
+            TextureFormat::R8Unorm => data.iter().flat_map(|&r| [r, r, r, 255]).collect(),
+            _ => return None,
+        };
+
+        Some(Self {
+            size,
+            data,
+            srgb: info.srgb,
+        })
+    }
+
+    fn white() -> Self {
+        let size = UVec2::splat(WHITE_BLOCK_SIZE);
+        let data = vec![255; (size.x * size.y) as usize * 4];
+        Self {
+            size,
+            data,
+            srgb: false,
+        }
+    }
+
+    /// Halves the resolution with a box filter.
+    fn downsample(&self) -> Self {
+        let size = (self.size / 2).max(UVec2::ONE);
+        let texel = |x: u32, y: u32, channel: u32| {
+            let x = x.min(self.size.x - 1);
+            let y = y.min(self.size.y - 1);
+            self.data[((y * self.size.x + x) * 4 + channel) as usize] as u32
+        };
+
+        let mut data = Vec::with_capacity((size.x * size.y) as usize * 4);
+        for y in 0..size.y {
+            for x in 0..size.x {
+                for channel in 0..4 {
+                    let sum = texel(2 * x, 2 * y, channel)
+                        + texel(2 * x + 1, 2 * y, channel)
+                        + texel(2 * x, 2 * y + 1, channel)
+                        + texel(2 * x + 1, 2 * y + 1, channel);
+                    data.push(((sum + 2) / 4) as u8);
+                }
+            }
+        }
+
+        Self {
+            size,
+            data,
+            srgb: self.srgb,
+        }
+    }
+}
+
+/// CPU copies of material textures, only extracted if the atlas is used.
+#[derive(Default, Resource)]
+pub struct ExtractedAtlasImages {
+    images: HashMap<Handle<Image>, Option<AtlasImage>>,
+    changed: bool,
+}
+
+#[derive(Resource)]
+pub struct TextureAtlas {
+    pub texture_view: Option<TextureView>,
+    pub sampler: Sampler,
+    /// Offsets and scales of material textures in the atlas, indexed by texture id.
+    pub rects: Vec<Vec4>,
+    /// Whether material textures are sRGB encoded, indexed by texture id.
+    pub srgb: Vec<bool>,
+}
+
+impl FromWorld for TextureAtlas {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+        let sampler = render_device.create_sampler(&SamplerDescriptor {
+            label: None,
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            texture_view: None,
+            sampler,
+            rects: vec![],
+            srgb: vec![],
+        }
+    }
+}
+
+impl TextureAtlas {
+    pub fn rect(&self, id: u32) -> Vec4 {
+        self.rects
+            .get(id as usize)
+            .copied()
+            .unwrap_or(Vec4::new(0.0, 0.0, 1.0, 1.0))
+    }
+
+    pub fn srgb(&self, id: u32) -> bool {
+        self.srgb.get(id as usize).copied().unwrap_or_default()
+    }
+}
+
+fn extract_atlas_images(
+    mut events: Extract<EventReader<AssetEvent<Image>>>,
+    assets: Extract<Res<Assets<Image>>>,
+    texture_binding: Res<TextureBindingMode>,
+    textures: Res<MaterialTextures>,
+    mut extracted: ResMut<ExtractedAtlasImages>,
+) {
+    if *texture_binding != TextureBindingMode::Atlas {
+        return;
+    }
+
+    for event in events.iter() {
+        if let AssetEvent::Modified { handle } | AssetEvent::Removed { handle } = event {
+            if extracted.images.remove(handle).is_some() {
+                extracted.changed = true;
+            }
+        }
+    }
+
+    for handle in &textures.data {
+        if extracted.images.contains_key(handle) {
+            continue;
+        }
+        if let Some(image) = assets.get(handle) {
+            let atlas_image = AtlasImage::from_image(image);
+            if atlas_image.is_none() {
+                warn!(
+                    "Texture format {:?} is not supported by the texture atlas",
+                    image.texture_descriptor.format
+                );
+            }
+            extracted.images.insert(handle.clone_weak(), atlas_image);
+            extracted.changed = true;
+        }
+    }
+}
+
+/// Packs rectangles into rows of a fixed width.
+/// Returns the offsets of the rectangles and the total height.
+fn pack_shelves(sizes: &[UVec2], width: u32) -> (Vec<UVec2>, u32) {
+    let mut order: Vec<_> = (0..sizes.len()).collect();
+    order.sort_by_key(|&index| std::cmp::Reverse(sizes[index].y));
+
+    let mut offsets = vec![UVec2::ZERO; sizes.len()];
+    let mut cursor = UVec2::ZERO;
+    let mut row_height = 0;
+    for index in order {
+        let size = sizes[index];
+        if cursor.x + size.x > width {
+            cursor = UVec2::new(0, cursor.y + row_height);
+            row_height = 0;
+        }
+        offsets[index] = cursor;
+        cursor.x += size.x;
+        row_height = row_height.max(size.y);
+    }
+
+    (offsets, cursor.y + row_height)
+}
+
+fn prepare_texture_atlas(
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    texture_binding: Res<TextureBindingMode>,
+    textures: Res<MaterialTextures>,
+    mut extracted: ResMut<ExtractedAtlasImages>,
+    mut atlas: ResMut<TextureAtlas>,
+) {
+    if *texture_binding != TextureBindingMode::Atlas
+        || (!extracted.changed && atlas.rects.len() == textures.data.len())
+    {
+        return;
+    }
+    extracted.changed = false;
+
+    // The first entry is the white block used by missing textures.
+    let white = AtlasImage::white();
+    let mut handles: Vec<Option<&Handle<Image>>> = vec![None];
+    let mut images = vec![&white];
+    for (handle, image) in extracted.images.iter() {
+        if let Some(image) = image {
+            handles.push(Some(handle));
+            images.push(image);
+        }
+    }
+
+    let max_size = render_device
+        .limits()
+        .max_texture_dimension_2d
+        .min(MAX_ATLAS_SIZE);
+    let area: u32 = images.iter().map(|image| image.size.x * image.size.y).sum();
+    let widest = images.iter().map(|image| image.size.x).max().unwrap_or(1);
+    let width = ((area as f32).sqrt().ceil() as u32)
+        .next_power_of_two()
+        .max(widest)
+        .min(max_size);
+
+    // Find the finest mip level such that all textures fit into the atlas.
+    // Past the coarsest mip of the largest texture, nothing shrinks any further.
+    let largest = images
+        .iter()
+        .skip(1)
+        .map(|image| image.size.max_element())
+        .max();
+    let max_level = 32 - largest.unwrap_or(1).leading_zeros();
+    let mut level = 0u32;
+    let (offsets, height, sizes) = loop {
+        let sizes: Vec<_> = images
+            .iter()
+            .enumerate()
+            .map(|(index, image)| match index {
+                0 => image.size,
+                _ => (image.size >> level).max(UVec2::ONE),
+            })
+            .collect();
+        let (offsets, height) = pack_shelves(&sizes, width);
+        if height <= max_size && sizes.iter().all(|size| size.x <= width) {
+            break (offsets, height, sizes);
+        }
+        if level >= max_level {
+            warn!(
+                "{} textures do not fit into a {}x{} texture atlas",
+                images.len() - 1,
+                width,
+                max_size
+            );
+            return;
+        }
+        level += 1;
+    };
+
+    let mut data = vec![0u8; (width * height) as usize * 4];
+    for (index, (image, offset)) in images.iter().zip(offsets.iter()).enumerate() {
+        let mut image = (*image).clone();
+        if index > 0 {
+            for _ in 0..level {
+                image = image.downsample();
+            }
+        }
+
+        let row_len = image.size.x as usize * 4;
+        for (y, row) in image.data.chunks_exact(row_len).enumerate() {
+            let start = ((offset.y as usize + y) * width as usize + offset.x as usize) * 4;
+            data[start..start + row_len].copy_from_slice(row);
+        }
+    }
+
+    let texture = render_device.create_texture_with_data(
+        &render_queue,
+        &TextureDescriptor {
+            label: None,
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8Unorm,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+        },
+        &data,
+    );
+    atlas.texture_view = Some(texture.create_view(&TextureViewDescriptor::default()));
+
+    let extent = Vec2::new(width as f32, height as f32);
+    let entries: HashMap<_, _> = handles
+        .iter()
+        .zip(images.iter())
+        .zip(offsets.iter().zip(sizes.iter()))
+        .map(|((handle, image), (offset, size))| {
+            let offset = offset.as_vec2() / extent;
+            let scale = size.as_vec2() / extent;
+            let rect = Vec4::new(offset.x, offset.y, scale.x, scale.y);
+            (*handle, (rect, image.srgb))
+        })
+        .collect();
+
+    let (rects, srgb) = textures
+        .data
+        .iter()
+        .map(|handle| entries.get(&Some(handle)).unwrap_or(&entries[&None]))
+        .copied()
+        .unzip();
+    atlas.rects = rects;
+    atlas.srgb = srgb;
+}
@@ -0,0 +1,181 @@
+use super::{atlas::AtlasImage, material::MaterialTextures, mipmap::srgb_to_linear, GpuMesh};
+use bevy::{
+    prelude::*,
+    render::{Extract, RenderApp, RenderStage},
+    utils::HashMap,
+};
+
+/// Maximal width or height of the luminance of emissive textures.
+const MAX_LUMINANCE_SIZE: u32 = 256;
+
+/// Number of subdivisions of each triangle edge when averaging its luminance.
+const TRIANGLE_SUBDIVISIONS: u32 = 4;
+
+/// Primitive luminances are clamped to this fraction of their mean,
+/// so that no primitive of an emissive mesh has a zero sampling probability.
+const MIN_RELATIVE_LUMINANCE: f32 = 1.0 / 256.0;
+
+pub struct EmissivePlugin;
+impl Plugin for EmissivePlugin {
+    fn build(&self, app: &mut App) {
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<EmissiveTextures>()
+                .add_system_to_stage(RenderStage::Extract, extract_emissive_textures);
+        }
+    }
+}
+
+/// Luminance of an emissive texture, with a box filtered mip chain.
+#[derive(Debug, Clone)]
+pub struct EmissiveLuminance {
+    levels: Vec<(UVec2, Vec<f32>)>,
+}
+
+impl EmissiveLuminance {
+    pub fn from_image(image: &AtlasImage) -> Self {
+        let decode = |value: u8| {
+            let value = value as f32 / 255.0;
+            match image.srgb {
+                true => srgb_to_linear(value),
+                false => value,
+            }
+        };
+        let data = image
+            .data
+            .chunks_exact(4)
+            .map(|texel| {
+                let [r, g, b] = [texel[0], texel[1], texel[2]].map(decode);
+                0.2126 * r + 0.7152 * g + 0.0722 * b
+            })
+            .collect();
+
+        let mut level = (image.size, data);
+        while level.0.max_element() > MAX_LUMINANCE_SIZE {
+            level = Self::downsample(&level);
+        }
+
+        let mut levels = vec![level];
+        while let Some(level) = levels.last().filter(|level| level.0.max_element() > 1) {
+            let next = Self::downsample(level);
+            levels.push(next);
+        }
+        Self { levels }
+    }
+
+    /// Halves the resolution with a box filter.
+    fn downsample((size, data): &(UVec2, Vec<f32>)) -> (UVec2, Vec<f32>) {
+        let next_size = (*size / 2).max(UVec2::ONE);
+        let texel = |x: u32, y: u32| {
+            let x = x.min(size.x - 1);
+            let y = y.min(size.y - 1);
+            data[(y * size.x + x) as usize]
+        };
+
+        let mut next = Vec::with_capacity((next_size.x * next_size.y) as usize);
+        for y in 0..next_size.y {
+            for x in 0..next_size.x {
+                let sum = texel(2 * x, 2 * y)
+                    + texel(2 * x + 1, 2 * y)
+                    + texel(2 * x, 2 * y + 1)
+                    + texel(2 * x + 1, 2 * y + 1);
+                next.push(0.25 * sum);
+            }
+        }
+        (next_size, next)
+    }
+
+    /// Fetches the nearest texel of a mip level, with repeated texture coordinates.
+    fn fetch(&self, level: usize, uv: Vec2) -> f32 {
+        let (size, data) = &self.levels[level];
+        let uv = uv - uv.floor();
+        let coords = (uv * size.as_vec2()).as_uvec2().min(*size - 1);
+        data[(coords.y * size.x + coords.x) as usize]
+    }
+
+    /// Averages the luminance over a triangle in texture space,
+    /// with stratified samples at the mip level where each sample covers about a texel.
+    pub fn triangle_average(&self, uvs: [Vec2; 3]) -> f32 {
+        let size = self.levels[0].0.as_vec2();
+        let [uv0, uv1, uv2] = uvs;
+        let texel_area = 0.5 * (uv1 - uv0).perp_dot(uv2 - uv0).abs() * size.x * size.y;
+
+        let n = TRIANGLE_SUBDIVISIONS;
+        let sample_count = (n * n) as f32;
+        let level = (0.5 * (texel_area / sample_count).log2()).ceil().max(0.0) as usize;
+        let level = level.min(self.levels.len() - 1);
+
+        // Take the centroids of the sub-triangles of a regular subdivision.
+        let mut sum = 0.0;
+        for i in 0..n {
+            for j in 0..n - i {
+                let mut barycentrics = vec![Vec2::new(i as f32 + 1.0 / 3.0, j as f32 + 1.0 / 3.0)];
+                if i + j + 1 < n {
+                    barycentrics.push(Vec2::new(i as f32 + 2.0 / 3.0, j as f32 + 2.0 / 3.0));
+                }
+                for b in barycentrics.into_iter().map(|b| b / n as f32) {
+                    let uv = uv0 + b.x * (uv1 - uv0) + b.y * (uv2 - uv0);
+                    sum += self.fetch(level, uv);
+                }
+            }
+        }
+        sum / sample_count
+    }
+
+    /// Average luminance of each primitive of the mesh.
+    /// Returns all zeros if the mesh doesn't emit at all.
+    pub fn primitive_luminances(&self, mesh: &GpuMesh) -> Vec<f32> {
+        let mut luminances: Vec<_> = mesh
+            .primitives
+            .iter()
+            .map(|primitive| {
+                let uvs = primitive.indices.map(|id| mesh.vertices[id as usize].uv);
+                self.triangle_average(uvs)
+            })
+            .collect();
+
+        let mean = luminances.iter().sum::<f32>() / luminances.len().max(1) as f32;
+        let min_luminance = MIN_RELATIVE_LUMINANCE * mean;
+        for luminance in luminances.iter_mut() {
+            *luminance = luminance.max(min_luminance);
+        }
+        luminances
+    }
+}
+
+/// Luminance of the emissive textures of materials,
+/// which is `None` if the texture format is not supported.
+#[derive(Default, Resource, Deref, DerefMut)]
+pub struct EmissiveTextures(HashMap<Handle<Image>, Option<EmissiveLuminance>>);
+
+fn extract_emissive_textures(
+    mut events: Extract<EventReader<AssetEvent<Image>>>,
+    assets: Extract<Res<Assets<Image>>>,
+    textures: Res<MaterialTextures>,
+    mut emissive_textures: ResMut<EmissiveTextures>,
+) {
+    for event in events.iter() {
+        if let AssetEvent::Modified { handle } | AssetEvent::Removed { handle } = event {
+            if emissive_textures.contains_key(handle) {
+                emissive_textures.remove(handle);
+            }
+        }
+    }
+
+    for handle in &textures.emissive {
+        if emissive_textures.contains_key(handle) {
+            continue;
+        }
+        if let Some(image) = assets.get(handle) {
+            let luminance =
+                AtlasImage::from_image(image).map(|image| EmissiveLuminance::from_image(&image));
+            if luminance.is_none() {
+                warn!(
+                    "Texture format {:?} is not supported for emissive light sampling",
+                    image.texture_descriptor.format
+                );
+            }
+            emissive_textures.insert(handle.clone_weak(), luminance);
+        }
+    }
+}
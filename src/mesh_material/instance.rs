@@ -1,11 +1,18 @@
 use super::{
-    material::GpuStandardMaterials, mesh::GpuMeshes, GpuAliasEntry, GpuAliasTableBuffer,
-    GpuEmissive, GpuEmissiveBuffer, GpuMesh, GpuStandardMaterial, MeshMaterialSystems,
+    area_light::ExtractedAreaLights,
+    emissive::EmissiveTextures,
+    light_tree::{build_light_tree, LightBounds},
+    material::{GpuStandardMaterials, MaterialTextures},
+    mesh::GpuMeshes,
+    GpuAliasEntry, GpuAliasTableBuffer, GpuAreaLight, GpuAreaLightBuffer, GpuEmissive,
+    GpuEmissiveBuffer, GpuLightNode, GpuLightNodeBuffer, GpuMesh, GpuStandardMaterial,
+    MeshMaterialSystems,
 };
 use crate::{
     mesh_material::{GpuInstance, GpuInstanceBuffer, GpuNode, GpuNodeBuffer},
+    support::probe_support,
     transform::GlobalTransformQueue,
-    HikariUniversalSettings,
+    HikariLightUnits, HikariUniversalSettings,
 };
 use bevy::{
     asset::Asset,
@@ -62,6 +69,10 @@ where
                 .after(VisibilitySystems::CalculateBounds),
         );
 
+        if !probe_support(app) {
+            return;
+        }
+
         if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
             render_app.add_system_to_stage(RenderStage::Extract, extract_instances::<M>);
         }
@@ -73,8 +84,9 @@ pub struct InstanceRenderAssets {
     pub instance_buffer: StorageBuffer<GpuInstanceBuffer>,
     pub instance_node_buffer: StorageBuffer<GpuNodeBuffer>,
     pub emissive_buffer: StorageBuffer<GpuEmissiveBuffer>,
-    pub emissive_node_buffer: StorageBuffer<GpuNodeBuffer>,
+    pub light_node_buffer: StorageBuffer<GpuLightNodeBuffer>,
     pub alias_table_buffer: StorageBuffer<GpuAliasTableBuffer>,
+    pub area_light_buffer: StorageBuffer<GpuAreaLightBuffer>,
     pub instance_indices: DynamicUniformBuffer<InstanceIndex>,
 }
 
@@ -84,8 +96,9 @@ impl InstanceRenderAssets {
         instances: Vec<GpuInstance>,
         instance_nodes: Vec<GpuNode>,
         emissives: Vec<GpuEmissive>,
-        emissive_nodes: Vec<GpuNode>,
+        light_nodes: Vec<GpuLightNode>,
         alias_table: Vec<GpuAliasEntry>,
+        area_lights: Vec<GpuAreaLight>,
     ) {
         self.instance_buffer.get_mut().data = instances;
         self.emissive_buffer.get_mut().data = emissives;
@@ -94,17 +107,21 @@ impl InstanceRenderAssets {
         self.instance_node_buffer.get_mut().count = instance_nodes.len() as u32;
         self.instance_node_buffer.get_mut().data = instance_nodes;
 
-        self.emissive_node_buffer.get_mut().count = emissive_nodes.len() as u32;
-        self.emissive_node_buffer.get_mut().data = emissive_nodes;
+        self.light_node_buffer.get_mut().count = light_nodes.len() as u32;
+        self.light_node_buffer.get_mut().data = light_nodes;
+
+        self.area_light_buffer.get_mut().count = area_lights.len() as u32;
+        self.area_light_buffer.get_mut().data = area_lights;
     }
 
     pub fn write_buffer(&mut self, device: &RenderDevice, queue: &RenderQueue) {
         self.instance_buffer.write_buffer(device, queue);
         self.instance_node_buffer.write_buffer(device, queue);
         self.emissive_buffer.write_buffer(device, queue);
-        self.emissive_node_buffer.write_buffer(device, queue);
+        self.light_node_buffer.write_buffer(device, queue);
         self.instance_indices.write_buffer(device, queue);
         self.alias_table_buffer.write_buffer(device, queue);
+        self.area_light_buffer.write_buffer(device, queue);
     }
 }
 
@@ -238,7 +255,10 @@ type Instances = BTreeMap<
     ),
 >;
 
-type AlisaTableCache = BTreeMap<Entity, (Vec3, Vec<GpuAliasEntry>)>;
+/// Alias tables of emissive instances, with the scales they are built for,
+/// the surface areas weighted by the emissive texture luminance,
+/// and the normal cones of the scaled but not yet rotated meshes.
+type AlisaTableCache = BTreeMap<Entity, (Vec3, Vec<GpuAliasEntry>, f32, (Vec3, f32))>;
 
 /// Note: this system must run AFTER [`prepare_mesh_assets`].
 #[allow(clippy::too_many_arguments)]
@@ -252,6 +272,9 @@ fn prepare_instances(
     mut alias_table_cache: Local<AlisaTableCache>,
     meshes: Res<GpuMeshes>,
     materials: Res<GpuStandardMaterials>,
+    textures: Res<MaterialTextures>,
+    emissive_textures: Res<EmissiveTextures>,
+    area_lights: Res<ExtractedAreaLights>,
     universal_settings: Res<HikariUniversalSettings>,
 ) {
     if !universal_settings.build_instance_acceleration_structure {
@@ -349,9 +372,21 @@ fn prepare_instances(
         commands.insert_or_spawn_batch(command_batch);
     };
 
-    if instance_changed || meshes.is_changed() || materials.is_changed() {
+    // Alias tables depend on the mesh and the luminance of the emissive texture.
+    if meshes.is_changed() || materials.is_changed() || emissive_textures.is_changed() {
+        alias_table_cache.clear();
+    }
+
+    if instance_changed
+        || meshes.is_changed()
+        || materials.is_changed()
+        || emissive_textures.is_changed()
+        || area_lights.is_changed()
+        || universal_settings.is_changed()
+    {
         // Important: update mesh and material info for every instance
         let mut emissives = vec![];
+        let mut light_bounds = vec![];
         let mut alias_table = vec![];
 
         collection.retain(|_, (_, _, _, visibility)| visibility.is_visible_in_hierarchy());
@@ -379,34 +414,70 @@ fn prepare_instances(
 
         for (id, (entity, (instance, mesh, material, _))) in collection.iter().enumerate() {
             let emissive = material.emissive;
-            let intensity = 255.0 * emissive.w * emissive.xyz().length();
-            if intensity > 0.0 {
+            let luminance = match universal_settings.light_units {
+                HikariLightUnits::Physical => emissive.xyz().max_element(),
+                HikariLightUnits::Legacy => 255.0 * emissive.w * emissive.xyz().length(),
+            };
+            if luminance > 0.0 {
                 // Compute alias table for light sampling
-                let instance_scale = instance.transform.to_scale_rotation_translation().0;
-                let alias_table = {
-                    let cached_table = alias_table_cache.get(entity).and_then(|(scale, table)| {
-                        scale.abs_diff_eq(instance_scale, 0.01).then_some(table)
-                    });
-                    let cache_hit = cached_table.is_some();
-                    let mut instance_table = cached_table
-                        .map_or_else(|| mesh.build_alias_table(instance.transform), Clone::clone);
-                    if !cache_hit {
-                        alias_table_cache.insert(*entity, (instance_scale, instance_table.clone()));
+                let (instance_scale, instance_rotation, _) =
+                    instance.transform.to_scale_rotation_translation();
+                let cached_table = alias_table_cache
+                    .get(entity)
+                    .filter(|(scale, _, _, _)| scale.abs_diff_eq(instance_scale, 0.01));
+                let (mut instance_table, emissive_area, normal_bounds) = match cached_table {
+                    Some((_, table, emissive_area, normal_bounds)) => {
+                        (table.clone(), *emissive_area, *normal_bounds)
+                    }
+                    None => {
+                        // Weight primitives by the luminance of the emissive texture, once it is loaded.
+                        let luminances = textures
+                            .data
+                            .get(material.emissive_texture as usize)
+                            .and_then(|handle| emissive_textures.get(handle))
+                            .and_then(Option::as_ref)
+                            .map(|texture| texture.primitive_luminances(mesh));
+                        let table =
+                            mesh.build_alias_table(instance.transform, luminances.as_deref());
+
+                        let areas = mesh.transformed_primitive_areas(instance.transform);
+                        let emissive_area = match &luminances {
+                            Some(luminances) => {
+                                areas.iter().zip(luminances).map(|(a, l)| a * l).sum()
+                            }
+                            None => areas.iter().sum(),
+                        };
+
+                        // Rotations only turn the normal cone, so it is cached per scale like the alias table.
+                        let normal_bounds =
+                            mesh.transformed_normal_bounds(Mat4::from_scale(instance_scale));
+                        alias_table_cache.insert(
+                            *entity,
+                            (instance_scale, table.clone(), emissive_area, normal_bounds),
+                        );
+                        (table, emissive_area, normal_bounds)
                     }
+                };
 
+                // The emissive texture is black.
+                if instance_table.is_empty() {
+                    continue;
+                }
+
+                let alias_table = {
                     let index = UVec2::new(alias_table.len() as u32, instance_table.len() as u32);
                     alias_table.append(&mut instance_table);
                     index
                 };
 
-                let surface_area = mesh
+                let surface_area: f32 = mesh
                     .transformed_primitive_areas(instance.transform)
                     .iter()
                     .sum();
 
                 // Add to emissive list.
                 let position = 0.5 * (instance.max + instance.min);
-                let radius = 0.5 * (instance.max - instance.min).length() + intensity.sqrt();
+                let radius = 0.5 * (instance.max - instance.min).length();
                 emissives.push(GpuEmissive {
                     emissive,
                     position,
@@ -416,23 +487,30 @@ fn prepare_instances(
                     surface_area,
                     node_index: 0,
                 });
+
+                let (axis, theta_o) = normal_bounds;
+                light_bounds.push(LightBounds {
+                    min: instance.min,
+                    max: instance.max,
+                    power: luminance * emissive_area,
+                    axis: instance_rotation * axis,
+                    theta_o,
+                    two_sided: true,
+                });
             }
         }
 
-        let emissive_nodes = match emissives.is_empty() {
-            true => vec![],
-            false => {
-                let bvh = BVH::build(&mut emissives);
-                bvh.flatten_custom(&GpuNode::pack)
-            }
-        };
+        // Area lights are sampled from the same light tree, after the emissives.
+        light_bounds.extend(area_lights.iter().map(GpuAreaLight::bounds));
+        let light_nodes = build_light_tree(&mut emissives, &light_bounds);
 
         render_assets.set(
             instances,
             instance_nodes,
             emissives,
-            emissive_nodes,
+            light_nodes,
             alias_table,
+            area_lights.to_vec(),
         );
         render_assets.write_buffer(&render_device, &render_queue);
     } else {
@@ -0,0 +1,143 @@
+use super::{GpuEmissive, GpuLightNode};
+use bevy::prelude::*;
+use std::f32::consts::PI;
+
+/// Flag of light tree leaves, whose `child` is an index into the emissive buffer.
+const LIGHT_LEAF_FLAG: u32 = 0x80000000;
+
+/// Flag of leaves whose `child` is an index into the area light buffer instead.
+const AREA_LIGHT_FLAG: u32 = 0x40000000;
+
+/// Spatial, power and orientation bounds of a set of emitters.
+#[derive(Debug, Clone, Copy)]
+pub struct LightBounds {
+    pub min: Vec3,
+    pub max: Vec3,
+    /// Emitted power, in arbitrary but consistent units.
+    pub power: f32,
+    /// Axis of the cone bounding the emitter normals, up to their sign if two-sided.
+    pub axis: Vec3,
+    /// Half apex angle of the normal cone.
+    pub theta_o: f32,
+    /// Whether any of the emitters emits from both sides of its surface.
+    pub two_sided: bool,
+}
+
+impl LightBounds {
+    pub fn centroid(&self) -> Vec3 {
+        0.5 * (self.min + self.max)
+    }
+
+    /// Bounds of both sets of emitters.
+    /// Normal cones are merged as in "Importance Sampling of Many Lights with Adaptive Tree Splitting".
+    pub fn union(&self, other: &Self) -> Self {
+        let (a, b) = match self.theta_o >= other.theta_o {
+            true => (self, other),
+            false => (other, self),
+        };
+
+        // Two-sided normals are bounded up to their sign, so turn the narrower cone towards the wider one.
+        // One-sided cones must be merged as they are, or back-facing emitters would be bounded.
+        let two_sided = a.two_sided || b.two_sided;
+        let b_axis = match two_sided && a.axis.dot(b.axis) < 0.0 {
+            true => -b.axis,
+            false => b.axis,
+        };
+        let theta_d = a.axis.dot(b_axis).clamp(-1.0, 1.0).acos();
+
+        let (axis, theta_o) = if (theta_d + b.theta_o).min(PI) <= a.theta_o {
+            (a.axis, a.theta_o)
+        } else {
+            let theta_o = 0.5 * (a.theta_o + theta_d + b.theta_o);
+            let rotation_axis = a.axis.cross(b_axis);
+            if theta_o >= PI || rotation_axis.length_squared() < 1.0e-12 {
+                (a.axis, theta_o.min(PI))
+            } else {
+                let rotation =
+                    Quat::from_axis_angle(rotation_axis.normalize(), theta_o - a.theta_o);
+                (rotation * a.axis, theta_o)
+            }
+        };
+
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+            power: self.power + other.power,
+            axis,
+            theta_o,
+            two_sided,
+        }
+    }
+}
+
+/// Builds a binary light tree over the emissives, whose bounds are given in the same order,
+/// followed by the bounds of the area lights.
+/// Also assigns the leaf index of each emissive.
+pub fn build_light_tree(
+    emissives: &mut [GpuEmissive],
+    bounds: &[LightBounds],
+) -> Vec<GpuLightNode> {
+    let mut nodes = vec![];
+    let mut indices: Vec<_> = (0..bounds.len()).collect();
+    if !indices.is_empty() {
+        build_node(&mut indices, emissives, bounds, &mut nodes);
+    }
+    nodes
+}
+
+fn build_node(
+    indices: &mut [usize],
+    emissives: &mut [GpuEmissive],
+    bounds: &[LightBounds],
+    nodes: &mut Vec<GpuLightNode>,
+) -> LightBounds {
+    let node_index = nodes.len();
+    nodes.push(GpuLightNode::default());
+
+    let (node_bounds, child) = if let [index] = indices {
+        let child = match emissives.get_mut(*index) {
+            Some(emissive) => {
+                emissive.node_index = node_index as u32;
+                *index as u32
+            }
+            None => (*index - emissives.len()) as u32 | AREA_LIGHT_FLAG,
+        };
+        (bounds[*index], child | LIGHT_LEAF_FLAG)
+    } else {
+        // Split at the median centroid along the longest axis of the centroid bounds.
+        let (min, max) = indices.iter().map(|&index| bounds[index].centroid()).fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), centroid| (min.min(centroid), max.max(centroid)),
+        );
+        let extent = max - min;
+        let axis = if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        };
+
+        let mid = indices.len() / 2;
+        indices.select_nth_unstable_by(mid, |&x, &y| {
+            bounds[x].centroid()[axis].total_cmp(&bounds[y].centroid()[axis])
+        });
+        let (left, right) = indices.split_at_mut(mid);
+
+        let left = build_node(left, emissives, bounds, nodes);
+        let child = nodes.len() as u32;
+        let right = build_node(right, emissives, bounds, nodes);
+        (left.union(&right), child)
+    };
+
+    nodes[node_index] = GpuLightNode {
+        min: node_bounds.min,
+        power: node_bounds.power,
+        max: node_bounds.max,
+        child,
+        axis: node_bounds.axis,
+        cos_theta_o: node_bounds.theta_o.cos(),
+        two_sided: node_bounds.two_sided.into(),
+    };
+    node_bounds
+}
@@ -1,4 +1,12 @@
-use super::{GpuStandardMaterial, GpuStandardMaterialBuffer, MeshMaterialSystems};
+use crate::{
+    accumulation::{asset_change_system, AccumulationReset, AccumulationSystems},
+    support::probe_support,
+};
+
+use super::{
+    atlas::TextureAtlas, mipmap::queue_material_mipmaps, GpuStandardMaterial,
+    GpuStandardMaterialBuffer, MeshMaterialSystems,
+};
 use bevy::{
     asset::{Asset, HandleId},
     prelude::*,
@@ -42,6 +50,17 @@ where
     M: Into<StandardMaterial> + Clone + Asset,
 {
     fn build(&self, app: &mut App) {
+        if !probe_support(app) {
+            return;
+        }
+
+        app.init_resource::<AccumulationReset>()
+            .add_system_to_stage(CoreStage::PostUpdate, queue_material_mipmaps::<M>)
+            .add_system_to_stage(
+                CoreStage::Last,
+                asset_change_system::<M>.label(AccumulationSystems::DetectChanges),
+            );
+
         if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
             render_app.add_system_to_stage(RenderStage::Extract, extract_material_assets::<M>);
         }
@@ -55,6 +74,8 @@ pub struct MaterialRenderAssets(pub StorageBuffer<GpuStandardMaterialBuffer>);
 pub struct MaterialTextures {
     pub data: Vec<Handle<Image>>,
     pub index: HashMap<Handle<Image>, usize>,
+    /// Textures used as emissive textures, whose luminance weights light sampling.
+    pub emissive: HashSet<Handle<Image>>,
 }
 
 impl MaterialTextures {
@@ -73,6 +94,10 @@ impl MaterialTextures {
         add_texture!(metallic_roughness_texture);
         add_texture!(normal_map_texture);
         add_texture!(occlusion_texture);
+
+        if let Some(texture) = &material.emissive_texture {
+            self.emissive.insert(texture.clone_weak());
+        }
     }
 
     pub fn id(&self, maybe_handle: &Option<Handle<Image>>) -> u32 {
@@ -136,16 +161,21 @@ fn prepare_material_textures(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn prepare_material_assets(
     mut extracted_assets: ResMut<ExtractedMaterials>,
     mut assets: Local<BTreeMap<HandleId, StandardMaterial>>,
     mut materials: ResMut<GpuStandardMaterials>,
     mut render_assets: ResMut<MaterialRenderAssets>,
     textures: Res<MaterialTextures>,
+    atlas: Res<TextureAtlas>,
     render_device: Res<RenderDevice>,
     render_queue: Res<RenderQueue>,
 ) {
-    if extracted_assets.removed.is_empty() && extracted_assets.extracted.is_empty() {
+    if extracted_assets.removed.is_empty()
+        && extracted_assets.extracted.is_empty()
+        && !atlas.is_changed()
+    {
         return;
     }
 
@@ -175,6 +205,23 @@ fn prepare_material_assets(
             let normal_map_texture = textures.id(&material.normal_map_texture);
             let occlusion_texture = textures.id(&material.occlusion_texture);
 
+            let base_color_texture_rect = atlas.rect(base_color_texture);
+            let emissive_texture_rect = atlas.rect(emissive_texture);
+            let metallic_roughness_texture_rect = atlas.rect(metallic_roughness_texture);
+            let normal_map_texture_rect = atlas.rect(normal_map_texture);
+            let occlusion_texture_rect = atlas.rect(occlusion_texture);
+            let srgb_texture_mask = [
+                base_color_texture,
+                emissive_texture,
+                metallic_roughness_texture,
+                normal_map_texture,
+                occlusion_texture,
+            ]
+            .iter()
+            .enumerate()
+            .filter(|(_, id)| atlas.srgb(**id))
+            .fold(0, |mask, (slot, _)| mask | (1 << slot));
+
             let (perceptual_roughness, metallic, reflectance) = (
                 material.perceptual_roughness,
                 material.metallic,
@@ -192,6 +239,12 @@ fn prepare_material_assets(
                 reflectance,
                 normal_map_texture,
                 occlusion_texture,
+                base_color_texture_rect,
+                emissive_texture_rect,
+                metallic_roughness_texture_rect,
+                normal_map_texture_rect,
+                occlusion_texture_rect,
+                srgb_texture_mask,
             };
             materials.insert(handle, (material.clone(), offset as u32));
             material
@@ -0,0 +1,172 @@
+use crate::HikariUniversalSettings;
+use bevy::{
+    asset::Asset,
+    prelude::*,
+    render::render_resource::{TextureDimension, TextureFormat},
+    utils::HashSet,
+};
+
+pub struct MipmapPlugin;
+impl Plugin for MipmapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MipmapQueue>()
+            .add_system_to_stage(CoreStage::Last, generate_mipmaps_system);
+    }
+}
+
+/// Material textures waiting for their mip chains to be generated.
+#[derive(Default, Resource, Deref, DerefMut)]
+pub struct MipmapQueue(HashSet<Handle<Image>>);
+
+/// Generates the full mip chain of an image on the CPU with a box filter.
+/// Returns `false` if the image already has mips, or its format or dimension is not supported.
+pub fn generate_mipmaps(image: &mut Image) -> bool {
+    let descriptor = &image.texture_descriptor;
+    if descriptor.mip_level_count > 1
+        || descriptor.dimension != TextureDimension::D2
+        || descriptor.size.depth_or_array_layers > 1
+    {
+        return false;
+    }
+
+    let (channels, srgb) = match descriptor.format {
+        TextureFormat::Rgba8Unorm | TextureFormat::Bgra8Unorm => (4, false),
+        TextureFormat::Rgba8UnormSrgb | TextureFormat::Bgra8UnormSrgb => (4, true),
+        TextureFormat::Rg8Unorm => (2, false),
+        TextureFormat::R8Unorm => (1, false),
+        _ => return false,
+    };
+
+    let mut size = UVec2::new(descriptor.size.width, descriptor.size.height);
+    let len = (size.x * size.y * channels) as usize;
+    if image.data.len() < len {
+        return false;
+    }
+
+    // Texels are averaged in linear space, except for the alpha channel.
+    let decode = |value: u8, channel: u32| -> f32 {
+        let value = value as f32 / 255.0;
+        match srgb && channel < 3 {
+            true => srgb_to_linear(value),
+            false => value,
+        }
+    };
+    let encode = |value: f32, channel: u32| -> u8 {
+        let value = match srgb && channel < 3 {
+            true => linear_to_srgb(value),
+            false => value,
+        };
+        (value.clamp(0.0, 1.0) * 255.0).round() as u8
+    };
+
+    let mut data = image.data[..len].to_vec();
+    let mut level = 0..len;
+    let mut mip_level_count = 1;
+    while size.max_element() > 1 {
+        let next_size = (size / 2).max(UVec2::ONE);
+        let start = data.len();
+        for y in 0..next_size.y {
+            for x in 0..next_size.x {
+                for channel in 0..channels {
+                    let texel = |x: u32, y: u32| {
+                        let x = x.min(size.x - 1);
+                        let y = y.min(size.y - 1);
+                        let index = level.start + ((y * size.x + x) * channels + channel) as usize;
+                        decode(data[index], channel)
+                    };
+                    let sum = texel(2 * x, 2 * y)
+                        + texel(2 * x + 1, 2 * y)
+                        + texel(2 * x, 2 * y + 1)
+                        + texel(2 * x + 1, 2 * y + 1);
+                    data.push(encode(0.25 * sum, channel));
+                }
+            }
+        }
+
+        level = start..data.len();
+        size = next_size;
+        mip_level_count += 1;
+    }
+
+    image.data = data;
+    image.texture_descriptor.mip_level_count = mip_level_count;
+    true
+}
+
+pub(super) fn srgb_to_linear(value: f32) -> f32 {
+    match value <= 0.04045 {
+        true => value / 12.92,
+        false => ((value + 0.055) / 1.055).powf(2.4),
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    match value <= 0.0031308 {
+        true => value * 12.92,
+        false => 1.055 * value.powf(1.0 / 2.4) - 0.055,
+    }
+}
+
+/// Queues the textures of created or modified materials for mip generation.
+pub(super) fn queue_material_mipmaps<M: Into<StandardMaterial> + Clone + Asset>(
+    settings: Res<HikariUniversalSettings>,
+    mut events: EventReader<AssetEvent<M>>,
+    assets: Res<Assets<M>>,
+    mut queue: ResMut<MipmapQueue>,
+) {
+    for event in events.iter() {
+        let handle = match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle,
+            AssetEvent::Removed { .. } => continue,
+        };
+        if !settings.generate_mipmaps {
+            continue;
+        }
+
+        if let Some(material) = assets.get(handle) {
+            let material: StandardMaterial = material.clone().into();
+            queue.extend(
+                [
+                    material.base_color_texture,
+                    material.emissive_texture,
+                    material.metallic_roughness_texture,
+                    material.normal_map_texture,
+                    material.occlusion_texture,
+                ]
+                .into_iter()
+                .flatten()
+                .map(|handle| handle.clone_weak()),
+            );
+        }
+    }
+}
+
+fn generate_mipmaps_system(
+    mut events: EventReader<AssetEvent<Image>>,
+    mut images: ResMut<Assets<Image>>,
+    mut queue: ResMut<MipmapQueue>,
+) {
+    for event in events.iter() {
+        if let AssetEvent::Removed { handle } = event {
+            queue.remove(handle);
+        }
+    }
+
+    // Textures that are still loading stay in the queue.
+    let loaded: Vec<_> = queue
+        .iter()
+        .filter(|handle| images.contains(handle))
+        .cloned()
+        .collect();
+    for handle in loaded {
+        queue.remove(&handle);
+        if let Some(mut image) = images.get(&handle).cloned() {
+            if generate_mipmaps(&mut image) {
+                // Mutable access marks the image as modified, so it gets uploaded again.
+                if let Some(target) = images.get_mut(&handle) {
+                    *target = image;
+                }
+            }
+        }
+    }
+}
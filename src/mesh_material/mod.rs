@@ -1,7 +1,13 @@
+use crate::{support::HikariSupport, HikariUniversalSettings};
+
 use self::{
+    area_light::AreaLightPlugin,
+    atlas::{AtlasPlugin, TextureAtlas},
+    emissive::EmissivePlugin,
     instance::InstancePlugin,
     material::{MaterialPlugin, MaterialTextures},
     mesh::MeshPlugin,
+    mipmap::MipmapPlugin,
 };
 use bevy::{
     ecs::system::{lifetimeless::SRes, SystemParamItem},
@@ -13,6 +19,7 @@ use bevy::{
         render_phase::{EntityRenderCommand, RenderCommandResult, TrackedRenderPass},
         render_resource::*,
         renderer::RenderDevice,
+        texture::FallbackImage,
         RenderApp, RenderStage,
     },
 };
@@ -24,9 +31,14 @@ use bvh::{
 use itertools::Itertools;
 use std::num::NonZeroU32;
 
+pub mod area_light;
+pub mod atlas;
+pub mod emissive;
 pub mod instance;
+pub mod light_tree;
 pub mod material;
 pub mod mesh;
+pub mod mipmap;
 
 pub use instance::{
     DynamicInstanceIndex, GenericInstancePlugin, InstanceIndex, InstanceRenderAssets,
@@ -41,11 +53,16 @@ impl Plugin for MeshMaterialPlugin {
         app.add_plugin(MeshPlugin)
             .add_plugin(MaterialPlugin)
             .add_plugin(InstancePlugin)
+            .add_plugin(AtlasPlugin)
+            .add_plugin(MipmapPlugin)
+            .add_plugin(EmissivePlugin)
+            .add_plugin(AreaLightPlugin)
             .add_plugin(GenericMaterialPlugin::<StandardMaterial>::default())
             .add_plugin(GenericInstancePlugin::<StandardMaterial>::default());
 
         if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
             render_app
+                .init_resource::<TextureBindingMode>()
                 .init_resource::<MeshMaterialBindGroupLayout>()
                 .init_resource::<TextureBindGroupLayout>()
                 .add_system_to_stage(
@@ -200,6 +217,24 @@ impl GpuNode {
     }
 }
 
+/// Node of the light tree, laid out in depth-first order.
+#[derive(Debug, Default, Clone, Copy, ShaderType)]
+pub struct GpuLightNode {
+    pub min: Vec3,
+    /// Total emitted power of the emissives below this node.
+    pub power: f32,
+    pub max: Vec3,
+    /// Index of the right child (the left child directly follows the node),
+    /// or the emissive or area light index with the leaf flag set.
+    pub child: u32,
+    /// Axis of the cone bounding the emitter normals, up to their sign if two-sided.
+    pub axis: Vec3,
+    /// Cosine of the half apex angle of the normal cone.
+    pub cos_theta_o: f32,
+    /// Whether any emitter below this node emits from both sides.
+    pub two_sided: u32,
+}
+
 #[derive(Debug, Default, Clone, ShaderType)]
 pub struct GpuStandardMaterial {
     pub base_color: Vec4,
@@ -215,6 +250,15 @@ pub struct GpuStandardMaterial {
 
     pub normal_map_texture: u32,
     pub occlusion_texture: u32,
+
+    /// Offsets and scales of textures in the atlas, if the atlas is used.
+    pub base_color_texture_rect: Vec4,
+    pub emissive_texture_rect: Vec4,
+    pub metallic_roughness_texture_rect: Vec4,
+    pub normal_map_texture_rect: Vec4,
+    pub occlusion_texture_rect: Vec4,
+    /// Bit mask of textures that are stored sRGB encoded in the atlas.
+    pub srgb_texture_mask: u32,
 }
 
 #[derive(Debug, Default, Clone, Copy, ShaderType)]
@@ -223,6 +267,8 @@ pub struct GpuAliasEntry {
     pub prob: f32,
     /// The index of the other one in the bucket.
     pub index: u32,
+    /// The probability density, per unit area, of sampling a point on this primitive.
+    pub pdf: f32,
 }
 
 #[derive(Debug, Default, Clone, ShaderType)]
@@ -233,26 +279,26 @@ pub struct GpuEmissive {
     pub instance: u32,
     pub alias_table: UVec2,
     pub surface_area: f32,
+    /// Index of the leaf in the light tree.
     node_index: u32,
 }
 
-impl Bounded for GpuEmissive {
-    fn aabb(&self) -> AABB {
-        AABB {
-            min: self.position - self.radius,
-            max: self.position + self.radius,
-        }
-    }
-}
-
-impl BHShape for GpuEmissive {
-    fn set_bh_node_index(&mut self, index: usize) {
-        self.node_index = index as u32;
-    }
-
-    fn bh_node_index(&self) -> usize {
-        self.node_index as usize
-    }
+/// Analytic area light, which is a rectangle, a disk, a sphere or a tube.
+#[derive(Debug, Default, Clone, Copy, PartialEq, ShaderType)]
+pub struct GpuAreaLight {
+    pub position: Vec3,
+    /// The [`AreaLightKind`](area_light::AreaLightKind) of the light.
+    pub kind: u32,
+    /// Emitted radiance, in the same units as material emissive.
+    pub radiance: Vec3,
+    /// Whether the light is visible to camera rays.
+    pub visible: u32,
+    pub tangent: Vec3,
+    /// Half width of the rectangle, or radius of the other kinds.
+    pub width: f32,
+    pub bitangent: Vec3,
+    /// Half height of the rectangle, or half length of the tube.
+    pub height: f32,
 }
 
 #[derive(Default, ShaderType)]
@@ -274,6 +320,13 @@ pub struct GpuNodeBuffer {
     pub data: Vec<GpuNode>,
 }
 
+#[derive(Default, ShaderType)]
+pub struct GpuLightNodeBuffer {
+    pub count: u32,
+    #[size(runtime)]
+    pub data: Vec<GpuLightNode>,
+}
+
 #[derive(Default, ShaderType)]
 pub struct GpuInstanceBuffer {
     #[size(runtime)]
@@ -298,6 +351,13 @@ pub struct GpuEmissiveBuffer {
     pub data: Vec<GpuEmissive>,
 }
 
+#[derive(Default, ShaderType)]
+pub struct GpuAreaLightBuffer {
+    pub count: u32,
+    #[size(runtime)]
+    pub data: Vec<GpuAreaLight>,
+}
+
 #[derive(Debug)]
 pub enum PrepareMeshError {
     MissingAttributePosition,
@@ -327,19 +387,73 @@ impl GpuMesh {
             .collect()
     }
 
-    pub fn build_alias_table(&self, transform: Mat4) -> Vec<GpuAliasEntry> {
+    /// Bounds the geometric normals of the transformed primitives with a cone,
+    /// returning its axis and half apex angle.
+    /// Normals are bounded up to their sign, since emissive surfaces emit from both sides.
+    pub fn transformed_normal_bounds(&self, transform: Mat4) -> (Vec3, f32) {
+        let normals: Vec<_> = self
+            .primitives
+            .iter()
+            .map(|primitive| {
+                let [v0, v1, v2] = [0, 1, 2]
+                    .map(|id| self.vertices[primitive.indices[id] as usize])
+                    .map(|v| transform.transform_point3(v.position));
+                (v1 - v0).cross(v2 - v0)
+            })
+            .filter(|normal| normal.length_squared() > 0.0)
+            .collect();
+
+        // Flip the normals into the hemisphere of the largest primitive, and average them by area.
+        let reference = normals
+            .iter()
+            .copied()
+            .max_by(|x, y| x.length_squared().total_cmp(&y.length_squared()))
+            .unwrap_or(Vec3::Y);
+        let axis = normals
+            .iter()
+            .fold(Vec3::ZERO, |sum, &normal| {
+                match normal.dot(reference) < 0.0 {
+                    true => sum - normal,
+                    false => sum + normal,
+                }
+            })
+            .normalize_or_zero();
+        if axis == Vec3::ZERO {
+            return (Vec3::Y, std::f32::consts::PI);
+        }
+
+        let theta_o = normals
+            .iter()
+            .map(|normal| axis.dot(normal.normalize()).abs().min(1.0).acos())
+            .fold(0.0, f32::max);
+        (axis, theta_o)
+    }
+
+    /// Builds the alias table for sampling points on the transformed mesh.
+    /// Primitives are weighted by their areas, and by their emissive luminances if given.
+    pub fn build_alias_table(
+        &self,
+        transform: Mat4,
+        luminances: Option<&[f32]>,
+    ) -> Vec<GpuAliasEntry> {
         let primitive_count = self.primitives.len();
         let areas = self.transformed_primitive_areas(transform);
-        let surface_area: f32 = areas.iter().sum();
+        let luminances = luminances.map_or_else(|| vec![1.0; primitive_count], <[f32]>::to_vec);
+        let weights: Vec<_> = areas
+            .iter()
+            .zip(luminances.iter())
+            .map(|(area, luminance)| area * luminance)
+            .collect();
+        let total_weight: f32 = weights.iter().sum();
 
-        if primitive_count == 0 {
+        if primitive_count == 0 || total_weight <= 0.0 {
             vec![]
         } else {
-            let mean_area = surface_area / (primitive_count as f32);
-            let probabilities = areas
+            let mean_weight = total_weight / (primitive_count as f32);
+            let probabilities = weights
                 .iter()
                 .enumerate()
-                .map(|(id, area)| (id, area / mean_area));
+                .map(|(id, weight)| (id, weight / mean_weight));
             let mut over: Vec<_> = probabilities.clone().filter(|prob| prob.1 > 1.0).collect();
             let mut under: Vec<_> = probabilities.filter(|prob| prob.1 < 1.0).collect();
 
@@ -347,6 +461,7 @@ impl GpuMesh {
                 .map(|id| GpuAliasEntry {
                     prob: 0.0,
                     index: id as u32,
+                    pdf: luminances[id] / total_weight,
                 })
                 .collect();
 
@@ -368,6 +483,7 @@ impl GpuMesh {
                 alias_table[under_bucket.0] = GpuAliasEntry {
                     prob: delta,
                     index: over_bucket.0 as u32,
+                    ..alias_table[under_bucket.0]
                 };
             }
 
@@ -568,14 +684,14 @@ impl FromWorld for MeshMaterialBindGroupLayout {
                     },
                     count: None,
                 },
-                // Emissive nodes
+                // Light tree nodes
                 BindGroupLayoutEntry {
                     binding: 7,
                     visibility: ShaderStages::all(),
                     ty: BindingType::Buffer {
                         ty: BufferBindingType::Storage { read_only: true },
                         has_dynamic_offset: false,
-                        min_binding_size: Some(GpuNodeBuffer::min_size()),
+                        min_binding_size: Some(GpuLightNodeBuffer::min_size()),
                     },
                     count: None,
                 },
@@ -590,6 +706,17 @@ impl FromWorld for MeshMaterialBindGroupLayout {
                     },
                     count: None,
                 },
+                // Area lights
+                BindGroupLayoutEntry {
+                    binding: 9,
+                    visibility: ShaderStages::all(),
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: Some(GpuAreaLightBuffer::min_size()),
+                    },
+                    count: None,
+                },
             ],
         });
 
@@ -597,39 +724,36 @@ impl FromWorld for MeshMaterialBindGroupLayout {
     }
 }
 
+/// How material textures are bound to the light pipelines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Resource)]
+pub enum TextureBindingMode {
+    /// Bind material textures as a binding array, which requires non-uniform indexing.
+    Array,
+    /// Pack material textures into a single atlas texture.
+    Atlas,
+}
+
+impl FromWorld for TextureBindingMode {
+    fn from_world(world: &mut World) -> Self {
+        if world.resource::<HikariSupport>().texture_binding_array {
+            Self::Array
+        } else {
+            Self::Atlas
+        }
+    }
+}
+
 #[derive(Resource)]
 pub struct TextureBindGroupLayout {
     pub layout: BindGroupLayout,
+    /// Capacity of the texture binding array, which is always a power of two (or zero).
     pub texture_count: u32,
 }
 
 impl FromWorld for TextureBindGroupLayout {
     fn from_world(world: &mut World) -> Self {
         let render_device = world.resource::<RenderDevice>();
-
-        let layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-            label: None,
-            entries: &[
-                // Textures
-                BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: ShaderStages::all(),
-                    ty: BindingType::Texture {
-                        sample_type: TextureSampleType::Float { filterable: true },
-                        view_dimension: TextureViewDimension::D2,
-                        multisampled: false,
-                    },
-                    count: None,
-                },
-                // Samplers
-                BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: ShaderStages::all(),
-                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
-                    count: None,
-                },
-            ],
-        });
+        let layout = create_texture_layout(render_device, 0);
 
         Self {
             layout,
@@ -638,13 +762,9 @@ impl FromWorld for TextureBindGroupLayout {
     }
 }
 
-fn prepare_texture_bind_group_layout(
-    render_device: Res<RenderDevice>,
-    textures: Res<MaterialTextures>,
-    mut texture_layout: ResMut<TextureBindGroupLayout>,
-) {
-    let texture_count = textures.data.len() as u32;
-    let layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+/// Creates the layout of material textures, which are bound as arrays if `texture_count` is not zero.
+pub fn create_texture_layout(render_device: &RenderDevice, texture_count: u32) -> BindGroupLayout {
+    render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
         label: None,
         entries: &[
             // Textures
@@ -666,8 +786,36 @@ fn prepare_texture_bind_group_layout(
                 count: NonZeroU32::new(texture_count),
             },
         ],
-    });
+    })
+}
+
+/// Rounds the texture count up to the next power of two, so that pipelines are only
+/// re-specialized when a bucket boundary is crossed.
+pub fn texture_capacity(texture_count: usize, reserved: usize) -> u32 {
+    match texture_count.max(reserved) {
+        0 => 0,
+        count => count.next_power_of_two() as u32,
+    }
+}
 
+fn prepare_texture_bind_group_layout(
+    render_device: Res<RenderDevice>,
+    universal_settings: Res<HikariUniversalSettings>,
+    texture_binding: Res<TextureBindingMode>,
+    textures: Res<MaterialTextures>,
+    mut texture_layout: ResMut<TextureBindGroupLayout>,
+) {
+    // The atlas is always bound as a single texture.
+    if *texture_binding == TextureBindingMode::Atlas {
+        return;
+    }
+
+    let texture_count = texture_capacity(textures.data.len(), universal_settings.texture_capacity);
+    if texture_count == texture_layout.texture_count {
+        return;
+    }
+
+    let layout = create_texture_layout(&render_device, texture_count);
     *texture_layout = TextureBindGroupLayout {
         layout,
         texture_count,
@@ -685,8 +833,10 @@ fn queue_mesh_material_bind_group(
     mut commands: Commands,
     render_device: Res<RenderDevice>,
     mesh_pipeline: Res<MeshPipeline>,
+    fallback_image: Res<FallbackImage>,
     meshes: Res<MeshRenderAssets>,
     textures: Res<MaterialTextures>,
+    atlas: Res<TextureAtlas>,
     materials: Res<MaterialRenderAssets>,
     instances: Res<InstanceRenderAssets>,
     images: Res<RenderAssets<Image>>,
@@ -701,8 +851,9 @@ fn queue_mesh_material_bind_group(
         Some(instance_node_binding),
         Some(material_binding),
         Some(emissive_binding),
-        Some(emissive_node_binding),
+        Some(light_node_binding),
         Some(alias_table_binding),
+        Some(area_light_binding),
     ) = (
         meshes.vertex_buffer.binding(),
         meshes.primitive_buffer.binding(),
@@ -711,8 +862,9 @@ fn queue_mesh_material_bind_group(
         instances.instance_node_buffer.binding(),
         materials.binding(),
         instances.emissive_buffer.binding(),
-        instances.emissive_node_buffer.binding(),
+        instances.light_node_buffer.binding(),
         instances.alias_table_buffer.binding(),
+        instances.area_light_buffer.binding(),
     ) {
         let mesh_material = render_device.create_bind_group(&BindGroupDescriptor {
             label: None,
@@ -748,12 +900,16 @@ fn queue_mesh_material_bind_group(
                 },
                 BindGroupEntry {
                     binding: 7,
-                    resource: emissive_node_binding,
+                    resource: light_node_binding,
                 },
                 BindGroupEntry {
                     binding: 8,
                     resource: emissive_binding,
                 },
+                BindGroupEntry {
+                    binding: 9,
+                    resource: area_light_binding,
+                },
             ],
         });
 
@@ -762,8 +918,14 @@ fn queue_mesh_material_bind_group(
                 .get(handle)
                 .unwrap_or(&mesh_pipeline.dummy_white_gpu_image)
         });
-        let textures: Vec<_> = images.clone().map(|image| &*image.texture_view).collect();
-        let samplers: Vec<_> = images.map(|image| &*image.sampler).collect();
+        let mut textures: Vec<_> = images.clone().map(|image| &*image.texture_view).collect();
+        let mut samplers: Vec<_> = images.map(|image| &*image.sampler).collect();
+
+        // Pad the arrays with fallback images to fill up the reserved capacity.
+        // In atlas mode the capacity is zero, and the atlas is bound as a single texture instead.
+        let capacity = texture_layout.texture_count as usize;
+        textures.resize(capacity, &fallback_image.texture_view);
+        samplers.resize(capacity, &fallback_image.sampler);
 
         let texture = if !textures.is_empty() {
             render_device.create_bind_group(&BindGroupDescriptor {
@@ -782,17 +944,24 @@ fn queue_mesh_material_bind_group(
             })
         } else {
             let dummy_white_gpu_image = &mesh_pipeline.dummy_white_gpu_image;
+            let (texture_view, sampler) = match &atlas.texture_view {
+                Some(texture_view) => (texture_view, &atlas.sampler),
+                None => (
+                    &dummy_white_gpu_image.texture_view,
+                    &dummy_white_gpu_image.sampler,
+                ),
+            };
             render_device.create_bind_group(&BindGroupDescriptor {
                 label: None,
                 layout: &texture_layout.layout,
                 entries: &[
                     BindGroupEntry {
                         binding: 0,
-                        resource: BindingResource::TextureView(&dummy_white_gpu_image.texture_view),
+                        resource: BindingResource::TextureView(texture_view),
                     },
                     BindGroupEntry {
                         binding: 1,
-                        resource: BindingResource::Sampler(&dummy_white_gpu_image.sampler),
+                        resource: BindingResource::Sampler(sampler),
                     },
                 ],
             })
@@ -1,6 +1,6 @@
 use crate::{
     light::LightTextures, post_process::PostProcessTextures, prepass::PrepassBindGroup,
-    HikariSettings, Taa, Upscale, OVERLAY_SHADER_HANDLE, QUAD_MESH_HANDLE,
+    HikariSettings, OVERLAY_SHADER_HANDLE, QUAD_MESH_HANDLE,
 };
 use bevy::{
     core_pipeline::clear_color::ClearColorConfig,
@@ -108,12 +108,10 @@ impl SpecializedMeshPipeline for OverlayPipeline {
         let vertex_buffer_layout = layout.get_layout(&vertex_attributes)?;
         let bind_group_layout = vec![self.input_layout.clone()];
 
-        let mut shader_defs = vec![];
-        let mut format = TextureFormat::bevy_default();
-        if key.contains(MeshPipelineKey::HDR) {
-            shader_defs.push("HDR".into());
-            format = ViewTarget::TEXTURE_FORMAT_HDR;
-        }
+        let format = match key.contains(MeshPipelineKey::HDR) {
+            true => ViewTarget::TEXTURE_FORMAT_HDR,
+            false => TextureFormat::bevy_default(),
+        };
 
         Ok(RenderPipelineDescriptor {
             label: None,
@@ -126,7 +124,7 @@ impl SpecializedMeshPipeline for OverlayPipeline {
             },
             fragment: Some(FragmentState {
                 shader: OVERLAY_SHADER_HANDLE.typed::<Shader>(),
-                shader_defs,
+                shader_defs: vec![],
                 entry_point: "fragment".into(),
                 targets: vec![Some(ColorTargetState {
                     format,
@@ -222,15 +220,7 @@ fn queue_overlay_bind_groups(
     >,
 ) {
     for (entity, light, post_process, settings) in &query {
-        let current = post_process.head;
-
-        let input_texture = match (settings.upscale, settings.taa) {
-            (Upscale::Fsr1 { .. }, _) => &post_process.upscale_output[1],
-            (Upscale::SmaaTu4x { .. }, Taa::None) => &post_process.upscale_output[0],
-            (Upscale::SmaaTu4x { .. }, Taa::Jasmine) => &post_process.taa_output[current],
-            (Upscale::None, Taa::Jasmine) => &post_process.taa_output[current],
-            (Upscale::None, Taa::None) => &post_process.tone_mapping_output[current],
-        };
+        let input_texture = post_process.output(settings);
 
         let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
             label: None,
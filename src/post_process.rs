@@ -1,10 +1,14 @@
 use crate::{
+    capture::f32_to_f16,
+    color_grading::COLOR_GRADING_LUT_FORMAT,
+    exposure::{ExposureBindGroup, ExposurePipeline, ExposureUniform},
     light::{LightTextures, VARIANCE_TEXTURE_FORMAT},
     prepass::{DeferredBindGroup, PrepassBindGroup, PrepassPipeline, PrepassTextures},
     view::{FrameCounter, FrameUniform, PreviousViewUniformOffset},
-    HikariSettings, Taa, Upscale, DENOISE_SHADER_HANDLE, FSR1_EASU_SHADER_HANDLE,
-    FSR1_RCAS_SHADER_HANDLE, SMAA_SHADER_HANDLE, TAA_SHADER_HANDLE, TONE_MAPPING_SHADER_HANDLE,
-    WORKGROUP_SIZE,
+    DenoiseSignalSettings, HikariExposure, HikariLens, HikariMotionBlur, HikariSettings, Taa,
+    Upscale, DENOISE_SHADER_HANDLE, DEPTH_OF_FIELD_SHADER_HANDLE, FSR1_EASU_SHADER_HANDLE,
+    FSR1_RCAS_SHADER_HANDLE, MOTION_BLUR_SHADER_HANDLE, SMAA_SHADER_HANDLE, TAA_SHADER_HANDLE,
+    TONE_MAPPING_SHADER_HANDLE, TSR_SHADER_HANDLE, WORKGROUP_SIZE,
 };
 use bevy::{
     ecs::query::QueryItem,
@@ -16,9 +20,10 @@ use bevy::{
             ComponentUniforms, DynamicUniformIndex, ExtractComponent, ExtractComponentPlugin,
             UniformComponentPlugin,
         },
+        render_asset::RenderAssets,
         render_graph::{Node, NodeRunError, RenderGraphContext, SlotInfo, SlotType},
         render_resource::*,
-        renderer::{RenderContext, RenderDevice},
+        renderer::{RenderContext, RenderDevice, RenderQueue},
         texture::TextureCache,
         view::ViewUniformOffset,
         RenderApp, RenderStage,
@@ -38,6 +43,8 @@ impl Plugin for PostProcessPlugin {
             render_app
                 .init_resource::<PostProcessPipeline>()
                 .init_resource::<SpecializedComputePipelines<PostProcessPipeline>>()
+                .init_resource::<DenoiseUniforms>()
+                .add_system_to_stage(RenderStage::Prepare, prepare_denoise_uniforms)
                 .add_system_to_stage(RenderStage::Prepare, prepare_post_process_textures)
                 .add_system_to_stage(RenderStage::Queue, queue_post_process_pipelines)
                 .add_system_to_stage(RenderStage::Queue, queue_post_process_bind_groups);
@@ -52,16 +59,25 @@ pub struct PostProcessPipeline {
     pub sampler_layout: BindGroupLayout,
     pub denoise_internal_layout: BindGroupLayout,
     pub denoise_render_layout: BindGroupLayout,
+    pub denoise_history_layout: BindGroupLayout,
     pub tone_mapping_layout: BindGroupLayout,
+    pub depth_of_field_layout: BindGroupLayout,
+    pub motion_blur_layout: BindGroupLayout,
     pub smaa_layout: BindGroupLayout,
     pub taa_layout: BindGroupLayout,
+    pub tsr_layout: BindGroupLayout,
     pub upscale_layout: BindGroupLayout,
+    pub color_grading_layout: BindGroupLayout,
     pub output_layout: BindGroupLayout,
+    pub exposure_layout: BindGroupLayout,
+    /// Bound when a camera has no color grading LUT.
+    pub identity_lut: TextureView,
 }
 
 impl FromWorld for PostProcessPipeline {
     fn from_world(world: &mut World) -> Self {
         let view_layout = world.resource::<PrepassPipeline>().view_layout.clone();
+        let exposure_layout = world.resource::<ExposurePipeline>().layout.clone();
 
         let render_device = world.resource::<RenderDevice>();
         let deferred_layout = PrepassTextures::bind_group_layout(render_device);
@@ -193,6 +209,90 @@ impl FromWorld for PostProcessPipeline {
                         },
                         count: None,
                     },
+                    // Denoise Settings
+                    BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Uniform,
+                            has_dynamic_offset: true,
+                            min_binding_size: Some(DenoiseUniform::min_size()),
+                        },
+                        count: None,
+                    },
+                    // Specular Albedo
+                    BindGroupLayoutEntry {
+                        binding: 5,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Float { filterable: true },
+                            view_dimension: TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    // Specular Render
+                    BindGroupLayoutEntry {
+                        binding: 6,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Float { filterable: true },
+                            view_dimension: TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let denoise_history_layout =
+            render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: None,
+                entries: &[
+                    // Previous History
+                    BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Float { filterable: true },
+                            view_dimension: TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    // Previous Moments
+                    BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Float { filterable: true },
+                            view_dimension: TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    // History
+                    BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::StorageTexture {
+                            access: StorageTextureAccess::ReadWrite,
+                            format: HDR_TEXTURE_FORMAT,
+                            view_dimension: TextureViewDimension::D2,
+                        },
+                        count: None,
+                    },
+                    // Moments
+                    BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::StorageTexture {
+                            access: StorageTextureAccess::ReadWrite,
+                            format: HDR_TEXTURE_FORMAT,
+                            view_dimension: TextureViewDimension::D2,
+                        },
+                        count: None,
+                    },
                 ],
             });
 
@@ -236,6 +336,50 @@ impl FromWorld for PostProcessPipeline {
                 ],
             });
 
+        let depth_of_field_layout =
+            render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: None,
+                entries: &[BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                }],
+            });
+
+        let motion_blur_layout =
+            render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: None,
+                entries: &[
+                    // Input
+                    BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Float { filterable: true },
+                            view_dimension: TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    // Velocity Tiles
+                    BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Float { filterable: true },
+                            view_dimension: TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
         let smaa_layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
             label: None,
             entries: &[
@@ -292,6 +436,67 @@ impl FromWorld for PostProcessPipeline {
             ],
         });
 
+        let tsr_layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                // Previous Render
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                // Current Render
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                // Albedo and Reactivity
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                // Previous History Weight
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                // History Weight
+                BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::StorageTexture {
+                        access: StorageTextureAccess::ReadWrite,
+                        format: HDR_TEXTURE_FORMAT,
+                        view_dimension: TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
         let upscale_layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
             label: None,
             entries: &[
@@ -318,6 +523,60 @@ impl FromWorld for PostProcessPipeline {
             ],
         });
 
+        let color_grading_layout =
+            render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: None,
+                entries: &[
+                    // Input
+                    BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Float { filterable: true },
+                            view_dimension: TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    // LUT
+                    BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Float { filterable: true },
+                            view_dimension: TextureViewDimension::D3,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        // A 2x2x2 LUT with its corners at the corners of the unit cube.
+        let identity_lut_data: Vec<u8> = (0..8)
+            .flat_map(|index| [index & 1, (index >> 1) & 1, (index >> 2) & 1, 1])
+            .flat_map(|value| f32_to_f16(value as f32).to_le_bytes())
+            .collect();
+        let identity_lut = render_device
+            .create_texture_with_data(
+                world.resource::<RenderQueue>(),
+                &TextureDescriptor {
+                    label: None,
+                    size: Extent3d {
+                        width: 2,
+                        height: 2,
+                        depth_or_array_layers: 2,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D3,
+                    format: COLOR_GRADING_LUT_FORMAT,
+                    usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+                },
+                &identity_lut_data,
+            )
+            .create_view(&TextureViewDescriptor::default());
+
         let output_layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
             label: None,
             entries: &[BindGroupLayoutEntry {
@@ -338,11 +597,18 @@ impl FromWorld for PostProcessPipeline {
             sampler_layout,
             denoise_internal_layout,
             denoise_render_layout,
+            denoise_history_layout,
             tone_mapping_layout,
+            depth_of_field_layout,
+            motion_blur_layout,
             smaa_layout,
             taa_layout,
+            tsr_layout,
             upscale_layout,
+            color_grading_layout,
             output_layout,
+            exposure_layout,
+            identity_lut,
         }
     }
 }
@@ -360,6 +626,15 @@ pub enum PostProcessEntryPoint {
     SmaaTu4xExtrapolate = 5,
     Upscale = 6,
     UpscaleSharpen = 7,
+    ColorGrading = 8,
+    LuminanceHistogram = 9,
+    ExposureAdaptation = 10,
+    DepthOfField = 11,
+    MotionBlurTileMax = 12,
+    MotionBlurNeighborMax = 13,
+    MotionBlur = 14,
+    TemporalUpscale = 15,
+    TemporalAccumulation = 16,
 }
 
 bitflags::bitflags! {
@@ -372,7 +647,7 @@ bitflags::bitflags! {
 }
 
 impl PostProcessPipelineKey {
-    const ENTRY_POINT_MASK_BITS: u32 = 0xF;
+    const ENTRY_POINT_MASK_BITS: u32 = 0x1F;
     const DENOISE_LEVEL_MASK_BITS: u32 = 0b11;
     const DENOISE_LEVEL_SHIFT_BITS: u32 = 32 - 2;
     const FIREFLY_FILTERING_SHIFT_BITS: u32 = 8;
@@ -412,25 +687,31 @@ impl SpecializedComputePipeline for PostProcessPipeline {
         }
 
         let (layout, shader) = match key.entry_point() {
-            PostProcessEntryPoint::Demodulation | PostProcessEntryPoint::Denoise => {
+            PostProcessEntryPoint::Demodulation
+            | PostProcessEntryPoint::TemporalAccumulation
+            | PostProcessEntryPoint::Denoise => {
                 let layout = vec![
                     self.view_layout.clone(),
                     self.deferred_layout.clone(),
                     self.sampler_layout.clone(),
                     self.denoise_internal_layout.clone(),
                     self.denoise_render_layout.clone(),
+                    self.denoise_history_layout.clone(),
                 ];
                 shader_defs.push(format!("DENOISE_LEVEL_{}", key.denoise_level()));
                 let shader = DENOISE_SHADER_HANDLE.typed();
                 (layout, shader)
             }
-            PostProcessEntryPoint::ToneMapping => {
+            PostProcessEntryPoint::ToneMapping
+            | PostProcessEntryPoint::LuminanceHistogram
+            | PostProcessEntryPoint::ExposureAdaptation => {
                 let layout = vec![
                     self.view_layout.clone(),
                     self.deferred_layout.clone(),
                     self.sampler_layout.clone(),
                     self.tone_mapping_layout.clone(),
                     self.output_layout.clone(),
+                    self.exposure_layout.clone(),
                 ];
                 let shader = TONE_MAPPING_SHADER_HANDLE.typed();
                 (layout, shader)
@@ -468,6 +749,17 @@ impl SpecializedComputePipeline for PostProcessPipeline {
                 let shader = SMAA_SHADER_HANDLE.typed();
                 (layout, shader)
             }
+            PostProcessEntryPoint::TemporalUpscale => {
+                let layout = vec![
+                    self.view_layout.clone(),
+                    self.deferred_layout.clone(),
+                    self.sampler_layout.clone(),
+                    self.tsr_layout.clone(),
+                    self.output_layout.clone(),
+                ];
+                let shader = TSR_SHADER_HANDLE.typed();
+                (layout, shader)
+            }
             PostProcessEntryPoint::Upscale => {
                 let layout = vec![
                     self.sampler_layout.clone(),
@@ -488,6 +780,42 @@ impl SpecializedComputePipeline for PostProcessPipeline {
                 let shader = FSR1_RCAS_SHADER_HANDLE.typed();
                 (layout, shader)
             }
+            PostProcessEntryPoint::DepthOfField => {
+                let layout = vec![
+                    self.view_layout.clone(),
+                    self.deferred_layout.clone(),
+                    self.sampler_layout.clone(),
+                    self.depth_of_field_layout.clone(),
+                    self.output_layout.clone(),
+                ];
+                let shader = DEPTH_OF_FIELD_SHADER_HANDLE.typed();
+                (layout, shader)
+            }
+            PostProcessEntryPoint::MotionBlurTileMax
+            | PostProcessEntryPoint::MotionBlurNeighborMax
+            | PostProcessEntryPoint::MotionBlur => {
+                let layout = vec![
+                    self.view_layout.clone(),
+                    self.deferred_layout.clone(),
+                    self.sampler_layout.clone(),
+                    self.motion_blur_layout.clone(),
+                    self.output_layout.clone(),
+                ];
+                let shader = MOTION_BLUR_SHADER_HANDLE.typed();
+                (layout, shader)
+            }
+            PostProcessEntryPoint::ColorGrading => {
+                let layout = vec![
+                    self.view_layout.clone(),
+                    self.deferred_layout.clone(),
+                    self.sampler_layout.clone(),
+                    self.color_grading_layout.clone(),
+                    self.output_layout.clone(),
+                ];
+                shader_defs.push("COLOR_GRADING".into());
+                let shader = TONE_MAPPING_SHADER_HANDLE.typed();
+                (layout, shader)
+            }
         };
 
         ComputePipelineDescriptor {
@@ -515,6 +843,87 @@ pub struct FsrConstantsUniform {
     pub hdr: u32,
 }
 
+/// Runs the denoiser on the accumulated renders of a camera in accumulation mode in the current frame.
+/// Inserted on cameras whose HDR capture is denoised on the GPU.
+#[derive(Component)]
+pub struct DenoiseAccumulation;
+
+/// Whether the renders of a camera are denoised in the current frame.
+/// Accumulated images are converged, so the denoiser is bypassed unless requested by [`DenoiseAccumulation`].
+pub fn is_denoising(
+    settings: &HikariSettings,
+    denoise_accumulation: Option<&DenoiseAccumulation>,
+) -> bool {
+    settings.denoise && (!settings.is_accumulating() || denoise_accumulation.is_some())
+}
+
+/// Filter parameters of the diffuse or specular part of one lighting signal, see [`DenoiseSignalSettings`].
+#[derive(Debug, Default, Clone, Copy, ShaderType)]
+pub struct DenoiseUniform {
+    pub iterations: u32,
+    pub kernel_radius: u32,
+    pub temporal_accumulation: u32,
+    pub specular: u32,
+    pub normal_sigma: f32,
+    pub depth_sigma: f32,
+    pub luminance_sigma: f32,
+}
+
+impl DenoiseUniform {
+    pub fn new(settings: DenoiseSignalSettings, specular: bool) -> Self {
+        Self {
+            iterations: settings
+                .iterations
+                .min(DenoiseSignalSettings::MAX_ITERATIONS),
+            kernel_radius: settings
+                .kernel_radius
+                .clamp(1, DenoiseSignalSettings::MAX_KERNEL_RADIUS),
+            temporal_accumulation: settings.temporal_accumulation.into(),
+            specular: specular.into(),
+            normal_sigma: settings.normal_sigma.max(0.0),
+            depth_sigma: settings.depth_sigma.max(0.0),
+            luminance_sigma: settings.luminance_sigma.max(0.0),
+        }
+    }
+}
+
+#[derive(Default, Resource)]
+pub struct DenoiseUniforms {
+    pub uniforms: DynamicUniformBuffer<DenoiseUniform>,
+}
+
+/// Offsets of the diffuse and specular filter parameters of the direct, emissive and indirect signals
+/// in [`DenoiseUniforms`].
+#[derive(Component)]
+pub struct DenoiseUniformOffset {
+    pub offsets: [[u32; 2]; 3],
+}
+
+fn prepare_denoise_uniforms(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    mut denoise_uniforms: ResMut<DenoiseUniforms>,
+    query: Query<(Entity, &HikariSettings), With<ExtractedCamera>>,
+) {
+    denoise_uniforms.uniforms.clear();
+    for (entity, settings) in &query {
+        let offsets = settings.denoise_settings.signals().map(|signal| {
+            [false, true].map(|specular| {
+                let uniform = DenoiseUniform::new(signal, specular);
+                denoise_uniforms.uniforms.push(uniform)
+            })
+        });
+        commands
+            .entity(entity)
+            .insert(DenoiseUniformOffset { offsets });
+    }
+
+    denoise_uniforms
+        .uniforms
+        .write_buffer(&render_device, &render_queue);
+}
+
 impl ExtractComponent for FsrConstantsUniform {
     type Query = (&'static Camera, &'static HikariSettings);
     type Filter = ();
@@ -627,16 +1036,86 @@ pub struct PostProcessTextures {
     pub denoise_internal: [TextureView; 4],
     pub denoise_internal_variance: TextureView,
     pub denoise_render: [TextureView; 3],
+    /// Temporally accumulated demodulated irradiance, with the history length in alpha.
+    /// Ordered as the diffuse and specular parts of the direct, emissive and indirect signals.
+    pub denoise_history: [[TextureView; 2]; 6],
+    /// First and second moments of the luminance, for the temporal variance estimate; ordered as `denoise_history`.
+    pub denoise_moments: [[TextureView; 2]; 6],
     pub tone_mapping_output: [TextureView; 2],
+    /// Tone mapping output that is defocused into `tone_mapping_output` if depth of field is enabled.
+    pub depth_of_field_input: TextureView,
+    /// Input of the motion blur pass, which writes into `tone_mapping_output` if motion blur is enabled.
+    pub motion_blur_input: TextureView,
+    /// Max velocities of tiles, and of their neighborhoods.
+    pub motion_blur_tiles: [TextureView; 2],
     pub taa_output: [TextureView; 2],
+    /// Outputs of the upscale passes; the current and previous history with temporal upscaling.
     pub upscale_output: [TextureView; 2],
+    /// Accumulated sample weights of the temporal upscale history.
+    pub tsr_weight: [TextureView; 2],
+    /// Tone mapped and color graded output in full size.
+    pub color_grading_output: TextureView,
+    /// Written by the debug pass if a debug view is enabled.
+    pub debug_output: TextureView,
 }
 
+impl PostProcessTextures {
+    /// Direct, emissive and indirect renders that are summed up by the tone mapping pass.
+    pub fn tone_mapping_inputs<'a>(
+        &'a self,
+        light: &'a LightTextures,
+        settings: &HikariSettings,
+        denoise: bool,
+    ) -> [&'a TextureView; 3] {
+        let signals = settings.denoise_settings.signals();
+        let [direct_render, emissive_render, indirect_render] =
+            [0, 1, 2].map(|id| match denoise && signals[id].is_enabled() {
+                true => &self.denoise_render[id],
+                false => &light.render[id],
+            });
+
+        // Use fallback texture when there is no indirect rendering pass.
+        match settings.indirect_bounces {
+            0 => [direct_render, emissive_render, &self.fallback],
+            _ => [direct_render, emissive_render, indirect_render],
+        }
+    }
+
+    /// The last texture written by the post process chain, which is presented by the overlay pass.
+    pub fn output(&self, settings: &HikariSettings) -> &TextureView {
+        match settings.debug_view.is_enabled() {
+            true => &self.debug_output,
+            false => &self.color_grading_output,
+        }
+    }
+
+    /// The anti-aliased and upscaled texture, which holds reversibly tone mapped radiance.
+    pub fn anti_aliasing_output(&self, settings: &HikariSettings) -> &TextureView {
+        let current = self.head;
+        match (settings.upscale, settings.taa) {
+            (Upscale::Fsr1 { .. }, _) => &self.upscale_output[1],
+            (Upscale::Tsr { .. }, _) => &self.upscale_output[current],
+            (Upscale::SmaaTu4x { .. }, Taa::None) => &self.upscale_output[0],
+            (Upscale::SmaaTu4x { .. }, Taa::Jasmine) => &self.taa_output[current],
+            (Upscale::None, Taa::Jasmine) => &self.taa_output[current],
+            (Upscale::None, Taa::None) => &self.tone_mapping_output[current],
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
 fn prepare_post_process_textures(
     mut commands: Commands,
     render_device: Res<RenderDevice>,
     mut texture_cache: ResMut<TextureCache>,
-    cameras: Query<(Entity, &ExtractedCamera, &FrameCounter, &HikariSettings)>,
+    cameras: Query<(
+        Entity,
+        &ExtractedCamera,
+        &FrameCounter,
+        &HikariSettings,
+        Option<&HikariLens>,
+        Option<&HikariMotionBlur>,
+    )>,
 ) {
     let texture_usage = TextureUsages::TEXTURE_BINDING | TextureUsages::STORAGE_BINDING;
     let fallback = texture_cache
@@ -658,7 +1137,7 @@ fn prepare_post_process_textures(
         )
         .default_view;
 
-    for (entity, camera, counter, settings) in &cameras {
+    for (entity, camera, counter, settings, lens, motion_blur) in &cameras {
         if let Some(size) = camera.physical_target_size {
             let mut create_texture = |texture_format, scale: f32| {
                 let extent = Extent3d {
@@ -713,22 +1192,63 @@ fn prepare_post_process_textures(
             let denoise_internal = create_texture_array![HDR_TEXTURE_FORMAT, scale; 4];
             let denoise_render = create_texture_array![HDR_TEXTURE_FORMAT, scale; 3];
 
+            // Only signals accumulated over frames keep a history.
+            let signals = settings.denoise_settings.signals();
+            let temporal_accumulation = [0, 1, 2, 3, 4, 5]
+                .map(|id| settings.denoise && signals[id / 2].temporal_accumulation);
+            let denoise_history = temporal_accumulation.map(|enabled| match enabled {
+                true => create_texture_array![HDR_TEXTURE_FORMAT, scale; 2],
+                false => create_texture_array![fallback; 2],
+            });
+            let denoise_moments = temporal_accumulation.map(|enabled| match enabled {
+                true => create_texture_array![HDR_TEXTURE_FORMAT, scale; 2],
+                false => create_texture_array![fallback; 2],
+            });
+
             let tone_mapping_output = create_texture_array![HDR_TEXTURE_FORMAT, scale; 2];
+            let depth_of_field_input = match matches!(lens, Some(lens) if lens.is_enabled()) {
+                true => create_texture(HDR_TEXTURE_FORMAT, scale),
+                false => fallback.clone(),
+            };
+            let (motion_blur_input, motion_blur_tiles) = match matches!(motion_blur, Some(motion_blur) if motion_blur.is_enabled())
+            {
+                true => {
+                    let tile_scale = scale / HikariMotionBlur::MAX_RADIUS as f32;
+                    (
+                        create_texture(HDR_TEXTURE_FORMAT, scale),
+                        create_texture_array![HDR_TEXTURE_FORMAT, tile_scale; 2],
+                    )
+                }
+                false => (fallback.clone(), create_texture_array![fallback; 2]),
+            };
 
             let upscale_output = match settings.upscale {
                 Upscale::SmaaTu4x { .. } => {
                     scale *= 2.0;
                     create_texture_array![HDR_TEXTURE_FORMAT, scale; 2]
                 }
-                Upscale::Fsr1 { .. } => create_texture_array![HDR_TEXTURE_FORMAT, 1.0; 2],
+                Upscale::Fsr1 { .. } | Upscale::Tsr { .. } => {
+                    create_texture_array![HDR_TEXTURE_FORMAT, 1.0; 2]
+                }
                 Upscale::None => create_texture_array![fallback; 2],
             };
+            let tsr_weight = match settings.upscale.is_temporal() {
+                true => create_texture_array![HDR_TEXTURE_FORMAT, 1.0; 2],
+                false => create_texture_array![fallback; 2],
+            };
 
             let taa_output = match settings.taa {
-                Taa::Jasmine => {
+                Taa::Jasmine if !settings.upscale.is_temporal() => {
                     create_texture_array![HDR_TEXTURE_FORMAT, scale; 2]
                 }
-                Taa::None => create_texture_array![fallback; 2],
+                _ => create_texture_array![fallback; 2],
+            };
+
+            let color_grading_output = create_texture(HDR_TEXTURE_FORMAT, 1.0);
+
+            let debug_output = match settings.debug_view.is_enabled() {
+                true => create_texture(HDR_TEXTURE_FORMAT, 1.0),
+                false => fallback.clone(),
             };
 
             commands.entity(entity).insert(PostProcessTextures {
@@ -739,9 +1259,17 @@ fn prepare_post_process_textures(
                 denoise_internal,
                 denoise_internal_variance,
                 denoise_render,
+                denoise_history,
+                denoise_moments,
                 tone_mapping_output,
+                depth_of_field_input,
+                motion_blur_input,
+                motion_blur_tiles,
                 taa_output,
                 upscale_output,
+                tsr_weight,
+                color_grading_output,
+                debug_output,
             });
         }
     }
@@ -751,14 +1279,49 @@ fn prepare_post_process_textures(
 #[derive(Resource)]
 pub struct CachedPostProcessPipelines {
     demodulation: CachedComputePipelineId,
-    denoise_direct: [CachedComputePipelineId; 4],
+    temporal_accumulation: CachedComputePipelineId,
     denoise: [CachedComputePipelineId; 4],
+    denoise_firefly_filtering: [CachedComputePipelineId; 4],
     tone_mapping: CachedComputePipelineId,
+    luminance_histogram: CachedComputePipelineId,
+    exposure_adaptation: CachedComputePipelineId,
+    depth_of_field: CachedComputePipelineId,
+    motion_blur_tile_max: CachedComputePipelineId,
+    motion_blur_neighbor_max: CachedComputePipelineId,
+    motion_blur: CachedComputePipelineId,
     taa_jasmine: CachedComputePipelineId,
     smaa_tu4x: CachedComputePipelineId,
     smaa_tu4x_extrapolate: CachedComputePipelineId,
+    temporal_upscale: CachedComputePipelineId,
     upscale: CachedComputePipelineId,
     upscale_sharpen: CachedComputePipelineId,
+    color_grading: CachedComputePipelineId,
+}
+
+impl CachedPostProcessPipelines {
+    /// Ids of all queued pipelines.
+    pub fn ids(&self) -> Vec<CachedComputePipelineId> {
+        let mut ids = vec![self.demodulation, self.temporal_accumulation];
+        ids.extend(self.denoise);
+        ids.extend(self.denoise_firefly_filtering);
+        ids.extend([
+            self.tone_mapping,
+            self.luminance_histogram,
+            self.exposure_adaptation,
+            self.depth_of_field,
+            self.motion_blur_tile_max,
+            self.motion_blur_neighbor_max,
+            self.motion_blur,
+            self.taa_jasmine,
+            self.smaa_tu4x,
+            self.smaa_tu4x_extrapolate,
+            self.temporal_upscale,
+            self.upscale,
+            self.upscale_sharpen,
+            self.color_grading,
+        ]);
+        ids
+    }
 }
 
 fn queue_post_process_pipelines(
@@ -771,12 +1334,17 @@ fn queue_post_process_pipelines(
         let key = PostProcessPipelineKey::from_entry_point(PostProcessEntryPoint::Demodulation);
         pipelines.specialize(&mut pipeline_cache, &pipeline, key)
     };
-    let denoise_direct = [0, 1, 2, 3].map(|level| {
+    let temporal_accumulation = {
+        let key =
+            PostProcessPipelineKey::from_entry_point(PostProcessEntryPoint::TemporalAccumulation);
+        pipelines.specialize(&mut pipeline_cache, &pipeline, key)
+    };
+    let denoise = [0, 1, 2, 3].map(|level| {
         let mut key = PostProcessPipelineKey::from_entry_point(PostProcessEntryPoint::Denoise);
         key |= PostProcessPipelineKey::from_denoise_level(level);
         pipelines.specialize(&mut pipeline_cache, &pipeline, key)
     });
-    let denoise = [0, 1, 2, 3].map(|level| {
+    let denoise_firefly_filtering = [0, 1, 2, 3].map(|level| {
         let mut key = PostProcessPipelineKey::from_entry_point(PostProcessEntryPoint::Denoise);
         key |= PostProcessPipelineKey::from_denoise_level(level);
         key |= PostProcessPipelineKey::FIREFLY_FILTERING_BITS;
@@ -787,6 +1355,36 @@ fn queue_post_process_pipelines(
         let key = PostProcessPipelineKey::from_entry_point(PostProcessEntryPoint::ToneMapping);
         pipelines.specialize(&mut pipeline_cache, &pipeline, key)
     };
+    let luminance_histogram = {
+        let key =
+            PostProcessPipelineKey::from_entry_point(PostProcessEntryPoint::LuminanceHistogram);
+        pipelines.specialize(&mut pipeline_cache, &pipeline, key)
+    };
+    let exposure_adaptation = {
+        let key =
+            PostProcessPipelineKey::from_entry_point(PostProcessEntryPoint::ExposureAdaptation);
+        pipelines.specialize(&mut pipeline_cache, &pipeline, key)
+    };
+
+    let depth_of_field = {
+        let key = PostProcessPipelineKey::from_entry_point(PostProcessEntryPoint::DepthOfField);
+        pipelines.specialize(&mut pipeline_cache, &pipeline, key)
+    };
+
+    let motion_blur_tile_max = {
+        let key =
+            PostProcessPipelineKey::from_entry_point(PostProcessEntryPoint::MotionBlurTileMax);
+        pipelines.specialize(&mut pipeline_cache, &pipeline, key)
+    };
+    let motion_blur_neighbor_max = {
+        let key =
+            PostProcessPipelineKey::from_entry_point(PostProcessEntryPoint::MotionBlurNeighborMax);
+        pipelines.specialize(&mut pipeline_cache, &pipeline, key)
+    };
+    let motion_blur = {
+        let key = PostProcessPipelineKey::from_entry_point(PostProcessEntryPoint::MotionBlur);
+        pipelines.specialize(&mut pipeline_cache, &pipeline, key)
+    };
 
     let taa_jasmine = {
         let key = PostProcessPipelineKey::from_entry_point(PostProcessEntryPoint::TaaJasmine);
@@ -803,6 +1401,11 @@ fn queue_post_process_pipelines(
         pipelines.specialize(&mut pipeline_cache, &pipeline, key)
     };
 
+    let temporal_upscale = {
+        let key = PostProcessPipelineKey::from_entry_point(PostProcessEntryPoint::TemporalUpscale);
+        pipelines.specialize(&mut pipeline_cache, &pipeline, key)
+    };
+
     let upscale = {
         let key = PostProcessPipelineKey::from_entry_point(PostProcessEntryPoint::Upscale);
         pipelines.specialize(&mut pipeline_cache, &pipeline, key)
@@ -812,16 +1415,30 @@ fn queue_post_process_pipelines(
         pipelines.specialize(&mut pipeline_cache, &pipeline, key)
     };
 
+    let color_grading = {
+        let key = PostProcessPipelineKey::from_entry_point(PostProcessEntryPoint::ColorGrading);
+        pipelines.specialize(&mut pipeline_cache, &pipeline, key)
+    };
+
     commands.insert_resource(CachedPostProcessPipelines {
         demodulation,
-        denoise_direct,
+        temporal_accumulation,
         denoise,
+        denoise_firefly_filtering,
         tone_mapping,
+        luminance_histogram,
+        exposure_adaptation,
+        depth_of_field,
+        motion_blur_tile_max,
+        motion_blur_neighbor_max,
+        motion_blur,
         taa_jasmine,
         smaa_tu4x,
         smaa_tu4x_extrapolate,
+        temporal_upscale,
         upscale,
         upscale_sharpen,
+        color_grading,
     })
 }
 
@@ -830,16 +1447,28 @@ pub struct PostProcessBindGroup {
     pub sampler: BindGroup,
     pub denoise_internal: BindGroup,
     pub denoise_render: Vec<BindGroup>,
+    pub denoise_history: [[BindGroup; 2]; 3],
     pub tone_mapping: BindGroup,
     pub tone_mapping_output: BindGroup,
+    pub depth_of_field: BindGroup,
+    pub depth_of_field_output: BindGroup,
+    pub motion_blur: BindGroup,
+    pub motion_blur_tile_max_output: BindGroup,
+    pub motion_blur_neighbor_max: BindGroup,
+    pub motion_blur_neighbor_max_output: BindGroup,
+    pub motion_blur_output: BindGroup,
     pub smaa: BindGroup,
     pub smaa_output: BindGroup,
     pub taa: BindGroup,
     pub taa_output: BindGroup,
+    pub tsr: BindGroup,
+    pub tsr_output: BindGroup,
     pub upscale: BindGroup,
     pub upscale_output: BindGroup,
     pub upscale_sharpen: BindGroup,
     pub upscale_sharpen_output: BindGroup,
+    pub color_grading: BindGroup,
+    pub color_grading_output: BindGroup,
 }
 
 #[allow(clippy::type_complexity)]
@@ -847,13 +1476,18 @@ fn queue_post_process_bind_groups(
     mut commands: Commands,
     render_device: Res<RenderDevice>,
     pipeline: Res<PostProcessPipeline>,
+    images: Res<RenderAssets<Image>>,
     fsr_constants_uniforms: Res<ComponentUniforms<FsrConstantsUniform>>,
+    denoise_uniforms: Res<DenoiseUniforms>,
     query: Query<
         (
             Entity,
             &LightTextures,
             &PostProcessTextures,
             &HikariSettings,
+            Option<&HikariLens>,
+            Option<&HikariMotionBlur>,
+            Option<&DenoiseAccumulation>,
         ),
         With<ExtractedCamera>,
     >,
@@ -862,8 +1496,12 @@ fn queue_post_process_bind_groups(
         Some(binding) => binding,
         None => return,
     };
+    let denoise_binding = match denoise_uniforms.uniforms.binding() {
+        Some(binding) => binding,
+        None => return,
+    };
 
-    for (entity, light, post_process, settings) in &query {
+    for (entity, light, post_process, settings, lens, motion_blur, denoise_accumulation) in &query {
         let current = post_process.head;
         let previous = 1 - current;
 
@@ -933,27 +1571,69 @@ fn queue_post_process_bind_groups(
                                 &post_process.denoise_render[id],
                             ),
                         },
+                        BindGroupEntry {
+                            binding: 4,
+                            resource: denoise_binding.clone(),
+                        },
+                        BindGroupEntry {
+                            binding: 5,
+                            resource: BindingResource::TextureView(&light.specular_albedo),
+                        },
+                        BindGroupEntry {
+                            binding: 6,
+                            resource: BindingResource::TextureView(&light.specular[id]),
+                        },
                     ],
                 })
             })
             .to_vec();
 
-        let (direct_render, emissive_render, mut indirect_render) = match settings.denoise {
-            false => (&light.render[0], &light.render[1], &light.render[2]),
-            true => (
-                &post_process.denoise_render[0],
-                &post_process.denoise_render[1],
-                &post_process.denoise_render[2],
-            ),
-        };
-
         if settings.indirect_bounces == 0 {
             // Do not denoise when there is no indirect rendering pass.
             denoise_render.pop();
-            // Use fallback texture when there is no indirect denoise pass.
-            indirect_render = &post_process.fallback;
         }
 
+        let denoise_history = [0, 1, 2].map(|signal| {
+            [0, 1].map(|part| {
+                let id = 2 * signal + part;
+                render_device.create_bind_group(&BindGroupDescriptor {
+                    label: None,
+                    layout: &pipeline.denoise_history_layout,
+                    entries: &[
+                        BindGroupEntry {
+                            binding: 0,
+                            resource: BindingResource::TextureView(
+                                &post_process.denoise_history[id][previous],
+                            ),
+                        },
+                        BindGroupEntry {
+                            binding: 1,
+                            resource: BindingResource::TextureView(
+                                &post_process.denoise_moments[id][previous],
+                            ),
+                        },
+                        BindGroupEntry {
+                            binding: 2,
+                            resource: BindingResource::TextureView(
+                                &post_process.denoise_history[id][current],
+                            ),
+                        },
+                        BindGroupEntry {
+                            binding: 3,
+                            resource: BindingResource::TextureView(
+                                &post_process.denoise_moments[id][current],
+                            ),
+                        },
+                    ],
+                })
+            })
+        });
+        let [direct_render, emissive_render, indirect_render] = post_process.tone_mapping_inputs(
+            light,
+            settings,
+            is_denoising(settings, denoise_accumulation),
+        );
+
         let tone_mapping = render_device.create_bind_group(&BindGroupDescriptor {
             label: None,
             layout: &pipeline.tone_mapping_layout,
@@ -972,7 +1652,89 @@ fn queue_post_process_bind_groups(
                 },
             ],
         });
+        // Optional stages after tone mapping are chained by writing into the input of the next one;
+        // the last stage writes into `tone_mapping_output`.
+        let motion_blur_input = match matches!(motion_blur, Some(motion_blur) if motion_blur.is_enabled())
+        {
+            true => &post_process.motion_blur_input,
+            false => &post_process.tone_mapping_output[current],
+        };
+        let tone_mapping_output = match matches!(lens, Some(lens) if lens.is_enabled()) {
+            true => &post_process.depth_of_field_input,
+            false => motion_blur_input,
+        };
         let tone_mapping_output = render_device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.output_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(tone_mapping_output),
+            }],
+        });
+
+        let depth_of_field = render_device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.depth_of_field_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(&post_process.depth_of_field_input),
+            }],
+        });
+        let depth_of_field_output = render_device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.output_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(motion_blur_input),
+            }],
+        });
+
+        let motion_blur = render_device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.motion_blur_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&post_process.motion_blur_input),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&post_process.motion_blur_tiles[1]),
+                },
+            ],
+        });
+        let motion_blur_tile_max_output = render_device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.output_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(&post_process.motion_blur_tiles[0]),
+            }],
+        });
+        let motion_blur_neighbor_max = render_device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.motion_blur_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&post_process.motion_blur_input),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&post_process.motion_blur_tiles[0]),
+                },
+            ],
+        });
+        let motion_blur_neighbor_max_output =
+            render_device.create_bind_group(&BindGroupDescriptor {
+                label: None,
+                layout: &pipeline.output_layout,
+                entries: &[BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&post_process.motion_blur_tiles[1]),
+                }],
+            });
+        let motion_blur_output = render_device.create_bind_group(&BindGroupDescriptor {
             label: None,
             layout: &pipeline.output_layout,
             entries: &[BindGroupEntry {
@@ -1035,6 +1797,43 @@ fn queue_post_process_bind_groups(
             }],
         });
 
+        let tsr = render_device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.tsr_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&post_process.upscale_output[previous]),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(
+                        &post_process.tone_mapping_output[current],
+                    ),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::TextureView(&light.albedo),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: BindingResource::TextureView(&post_process.tsr_weight[previous]),
+                },
+                BindGroupEntry {
+                    binding: 4,
+                    resource: BindingResource::TextureView(&post_process.tsr_weight[current]),
+                },
+            ],
+        });
+        let tsr_output = render_device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.output_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(&post_process.upscale_output[current]),
+            }],
+        });
+
         let upscale_input_texture = match settings.taa {
             Taa::Jasmine => &post_process.taa_output[current],
             Taa::None => &post_process.tone_mapping_output[current],
@@ -1086,20 +1885,63 @@ fn queue_post_process_bind_groups(
             }],
         });
 
+        let lut = settings
+            .color_grading
+            .as_ref()
+            .and_then(|handle| images.get(handle))
+            .filter(|image| image.texture_format == COLOR_GRADING_LUT_FORMAT)
+            .map_or(&pipeline.identity_lut, |image| &image.texture_view);
+        let color_grading = render_device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.color_grading_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(
+                        post_process.anti_aliasing_output(settings),
+                    ),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(lut),
+                },
+            ],
+        });
+        let color_grading_output = render_device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.output_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(&post_process.color_grading_output),
+            }],
+        });
+
         commands.entity(entity).insert(PostProcessBindGroup {
             sampler,
             denoise_internal,
             denoise_render,
+            denoise_history,
             tone_mapping,
             tone_mapping_output,
+            depth_of_field,
+            depth_of_field_output,
+            motion_blur,
+            motion_blur_tile_max_output,
+            motion_blur_neighbor_max,
+            motion_blur_neighbor_max_output,
+            motion_blur_output,
             smaa,
             smaa_output,
             taa,
             taa_output,
+            tsr,
+            tsr_output,
             upscale,
             upscale_output,
             upscale_sharpen,
             upscale_sharpen_output,
+            color_grading,
+            color_grading_output,
         });
     }
 }
@@ -1115,7 +1957,13 @@ pub struct PostProcessNode {
         &'static DeferredBindGroup,
         &'static PostProcessBindGroup,
         &'static DynamicUniformIndex<FsrConstantsUniform>,
+        &'static DenoiseUniformOffset,
+        &'static DynamicUniformIndex<ExposureUniform>,
+        &'static ExposureBindGroup,
         &'static HikariSettings,
+        Option<&'static HikariLens>,
+        Option<&'static HikariMotionBlur>,
+        Option<&'static DenoiseAccumulation>,
     )>,
 }
 
@@ -1154,7 +2002,13 @@ impl Node for PostProcessNode {
             deferred_bind_group,
             post_process_bind_group,
             fsr_constants_uniform,
+            denoise_uniform,
+            exposure_uniform,
+            exposure_bind_group,
             settings,
+            lens,
+            motion_blur,
+            denoise_accumulation,
         ) = match self.query.get_manual(world, entity) {
             Ok(query) => query,
             Err(_) => return Ok(()),
@@ -1167,7 +2021,10 @@ impl Node for PostProcessNode {
         let pipelines = world.resource::<CachedPostProcessPipelines>();
         let pipeline_cache = world.resource::<PipelineCache>();
 
-        let size = camera.physical_target_size.unwrap();
+        let size = match camera.physical_target_size {
+            Some(size) => size,
+            None => return Ok(()),
+        };
         let scale = settings.upscale.ratio().recip();
         let mut scaled_size = (scale * size.as_vec2()).ceil().as_uvec2();
 
@@ -1188,44 +2045,90 @@ impl Node for PostProcessNode {
         pass.set_bind_group(1, &deferred_bind_group.0, &[]);
         pass.set_bind_group(2, &post_process_bind_group.sampler, &[]);
 
-        if settings.denoise {
+        if is_denoising(settings, denoise_accumulation) {
             pass.set_bind_group(3, &post_process_bind_group.denoise_internal, &[]);
 
-            let denoise_pipelines = [
-                pipelines.denoise_direct,
-                pipelines.denoise,
-                pipelines.denoise,
-            ];
-
-            for (render_bind_group, denoise) in post_process_bind_group
-                .denoise_render
-                .iter()
-                .zip(denoise_pipelines.iter())
+            for (((render_bind_group, history_bind_groups), signal), offsets) in
+                post_process_bind_group
+                    .denoise_render
+                    .iter()
+                    .zip(post_process_bind_group.denoise_history.iter())
+                    .zip(settings.denoise_settings.signals())
+                    .zip(denoise_uniform.offsets)
             {
-                pass.set_bind_group(4, render_bind_group, &[]);
-
-                if let Some(pipeline) = pipeline_cache.get_compute_pipeline(pipelines.demodulation)
-                {
-                    pass.set_pipeline(pipeline);
-
-                    let count = (scaled_size + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE;
-                    pass.dispatch_workgroups(count.x, count.y, 1);
+                if !signal.is_enabled() {
+                    continue;
                 }
 
-                for pipeline in denoise
-                    .iter()
-                    .filter_map(|pipeline| pipeline_cache.get_compute_pipeline(*pipeline))
-                {
-                    pass.set_pipeline(pipeline);
-
-                    let count = (scaled_size + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE;
-                    pass.dispatch_workgroups(count.x, count.y, 1);
+                // Skip the coarsest levels, so that the last iteration always writes the output.
+                let iterations =
+                    signal.iterations.min(DenoiseSignalSettings::MAX_ITERATIONS) as usize;
+                let denoise = match signal.firefly_filtering {
+                    true => &pipelines.denoise_firefly_filtering,
+                    false => &pipelines.denoise,
+                };
+                let denoise = &denoise[denoise.len() - iterations..];
+
+                // The diffuse part writes the output, to which the specular part is added.
+                for (history_bind_group, offset) in history_bind_groups.iter().zip(offsets) {
+                    pass.set_bind_group(4, render_bind_group, &[offset]);
+                    pass.set_bind_group(5, history_bind_group, &[]);
+
+                    if let Some(pipeline) =
+                        pipeline_cache.get_compute_pipeline(pipelines.demodulation)
+                    {
+                        pass.set_pipeline(pipeline);
+
+                        let count = (scaled_size + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE;
+                        pass.dispatch_workgroups(count.x, count.y, 1);
+                    }
+
+                    // The history of accumulated images is stale, and they are converged anyway.
+                    if signal.temporal_accumulation && !settings.is_accumulating() {
+                        if let Some(pipeline) =
+                            pipeline_cache.get_compute_pipeline(pipelines.temporal_accumulation)
+                        {
+                            pass.set_pipeline(pipeline);
+
+                            let count = (scaled_size + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE;
+                            pass.dispatch_workgroups(count.x, count.y, 1);
+                        }
+                    }
+
+                    for pipeline in denoise
+                        .iter()
+                        .filter_map(|pipeline| pipeline_cache.get_compute_pipeline(*pipeline))
+                    {
+                        pass.set_pipeline(pipeline);
+
+                        let count = (scaled_size + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE;
+                        pass.dispatch_workgroups(count.x, count.y, 1);
+                    }
                 }
             }
         }
 
         pass.set_bind_group(3, &post_process_bind_group.tone_mapping, &[]);
         pass.set_bind_group(4, &post_process_bind_group.tone_mapping_output, &[]);
+        pass.set_bind_group(5, &exposure_bind_group.0, &[exposure_uniform.index()]);
+
+        if matches!(settings.exposure, HikariExposure::Auto { .. }) {
+            if let Some(pipeline) =
+                pipeline_cache.get_compute_pipeline(pipelines.luminance_histogram)
+            {
+                pass.set_pipeline(pipeline);
+
+                let count = (scaled_size + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE;
+                pass.dispatch_workgroups(count.x, count.y, 1);
+            }
+
+            if let Some(pipeline) =
+                pipeline_cache.get_compute_pipeline(pipelines.exposure_adaptation)
+            {
+                pass.set_pipeline(pipeline);
+                pass.dispatch_workgroups(1, 1, 1);
+            }
+        }
 
         if let Some(pipeline) = pipeline_cache.get_compute_pipeline(pipelines.tone_mapping) {
             pass.set_pipeline(pipeline);
@@ -1234,6 +2137,61 @@ impl Node for PostProcessNode {
             pass.dispatch_workgroups(count.x, count.y, 1);
         }
 
+        if matches!(lens, Some(lens) if lens.is_enabled()) {
+            pass.set_bind_group(3, &post_process_bind_group.depth_of_field, &[]);
+            pass.set_bind_group(4, &post_process_bind_group.depth_of_field_output, &[]);
+
+            if let Some(pipeline) = pipeline_cache.get_compute_pipeline(pipelines.depth_of_field) {
+                pass.set_pipeline(pipeline);
+
+                let count = (scaled_size + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE;
+                pass.dispatch_workgroups(count.x, count.y, 1);
+            }
+        }
+
+        if matches!(motion_blur, Some(motion_blur) if motion_blur.is_enabled()) {
+            let tile_size = UVec2::splat(HikariMotionBlur::MAX_RADIUS);
+            let tile_count = (scaled_size + tile_size - 1) / tile_size;
+
+            pass.set_bind_group(3, &post_process_bind_group.motion_blur, &[]);
+            pass.set_bind_group(4, &post_process_bind_group.motion_blur_tile_max_output, &[]);
+
+            if let Some(pipeline) =
+                pipeline_cache.get_compute_pipeline(pipelines.motion_blur_tile_max)
+            {
+                pass.set_pipeline(pipeline);
+
+                let count = (tile_count + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE;
+                pass.dispatch_workgroups(count.x, count.y, 1);
+            }
+
+            pass.set_bind_group(3, &post_process_bind_group.motion_blur_neighbor_max, &[]);
+            pass.set_bind_group(
+                4,
+                &post_process_bind_group.motion_blur_neighbor_max_output,
+                &[],
+            );
+
+            if let Some(pipeline) =
+                pipeline_cache.get_compute_pipeline(pipelines.motion_blur_neighbor_max)
+            {
+                pass.set_pipeline(pipeline);
+
+                let count = (tile_count + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE;
+                pass.dispatch_workgroups(count.x, count.y, 1);
+            }
+
+            pass.set_bind_group(3, &post_process_bind_group.motion_blur, &[]);
+            pass.set_bind_group(4, &post_process_bind_group.motion_blur_output, &[]);
+
+            if let Some(pipeline) = pipeline_cache.get_compute_pipeline(pipelines.motion_blur) {
+                pass.set_pipeline(pipeline);
+
+                let count = (scaled_size + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE;
+                pass.dispatch_workgroups(count.x, count.y, 1);
+            }
+        }
+
         if matches!(settings.upscale, Upscale::SmaaTu4x { .. }) {
             pass.set_bind_group(3, &post_process_bind_group.smaa, &[]);
             pass.set_bind_group(4, &post_process_bind_group.smaa_output, &[]);
@@ -1258,7 +2216,18 @@ impl Node for PostProcessNode {
             scaled_size *= 2;
         }
 
-        if matches!(settings.taa, Taa::Jasmine) {
+        if settings.upscale.is_temporal() {
+            pass.set_bind_group(3, &post_process_bind_group.tsr, &[]);
+            pass.set_bind_group(4, &post_process_bind_group.tsr_output, &[]);
+
+            if let Some(pipeline) = pipeline_cache.get_compute_pipeline(pipelines.temporal_upscale)
+            {
+                pass.set_pipeline(pipeline);
+
+                let count = (size + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE;
+                pass.dispatch_workgroups(count.x, count.y, 1);
+            }
+        } else if matches!(settings.taa, Taa::Jasmine) {
             pass.set_bind_group(3, &post_process_bind_group.taa, &[]);
             pass.set_bind_group(4, &post_process_bind_group.taa_output, &[]);
 
@@ -1308,6 +2277,30 @@ impl Node for PostProcessNode {
             }
         }
 
+        if !settings.debug_view.is_enabled() {
+            pass.set_bind_group(
+                0,
+                view_bind_group,
+                &[
+                    frame_uniform.index(),
+                    view_uniform.offset,
+                    previous_view_uniform.offset,
+                    view_lights.offset,
+                ],
+            );
+            pass.set_bind_group(1, &deferred_bind_group.0, &[]);
+            pass.set_bind_group(2, &post_process_bind_group.sampler, &[]);
+            pass.set_bind_group(3, &post_process_bind_group.color_grading, &[]);
+            pass.set_bind_group(4, &post_process_bind_group.color_grading_output, &[]);
+
+            if let Some(pipeline) = pipeline_cache.get_compute_pipeline(pipelines.color_grading) {
+                pass.set_pipeline(pipeline);
+
+                let count = (size + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE;
+                pass.dispatch_workgroups(count.x, count.y, 1);
+            }
+        }
+
         Ok(())
     }
 }
@@ -1,4 +1,16 @@
 pub use crate::{
-    mesh_material::{GenericInstancePlugin, GenericMaterialPlugin},
-    HikariPlugin, HikariSettings, HikariUniversalSettings, Taa, Upscale,
+    accumulation::HikariAccumulationProgress,
+    aov::{Aov, HikariAovs},
+    capture::{CaptureFormat, CaptureSource, HikariCapture, HikariCaptured},
+    denoiser::{DenoiserDevice, DenoiserImage, GpuDenoiser, HikariDenoiser},
+    mesh_material::{
+        area_light::{HikariDiskLight, HikariRectLight, HikariSphereLight, HikariTubeLight},
+        GenericInstancePlugin, GenericMaterialPlugin,
+    },
+    resolution::HikariDynamicResolution,
+    support::HikariSupport,
+    warm_up::{HikariPipelinesReady, HikariWarmUp},
+    DenoiseSignalSettings, HikariDebugView, HikariDenoiseSettings, HikariExposure, HikariLens,
+    HikariLightUnits, HikariMotionBlur, HikariPlugin, HikariSettings, HikariToneMapping,
+    HikariUniversalSettings, LightingPass, RenderMode, Taa, Upscale, UpscaleQuality,
 };
@@ -360,7 +360,11 @@ fn prepass_textures_system(
     )>,
 ) {
     for (entity, camera, _settings) in &queries.p0() {
-        if let Some(size) = camera.physical_target_size() {
+        // Skip minimized windows, whose textures can't be created.
+        if let Some(size) = camera
+            .physical_target_size()
+            .filter(|size| size.x > 0 && size.y > 0)
+        {
             // let scale = settings.upscale.ratio().recip();
             let size = size.as_vec2().ceil().as_uvec2();
             let size = Extent3d {
@@ -486,7 +490,8 @@ fn queue_prepass_meshes(
                 let key = MeshPipelineKey::from_primitive_topology(mesh.primitive_topology);
                 let key = PrepassPipelineKey {
                     mesh_pipeline_key: key,
-                    temporal_anti_aliasing: matches!(settings.taa, Taa::Jasmine),
+                    temporal_anti_aliasing: matches!(settings.taa, Taa::Jasmine)
+                        || settings.upscale.is_temporal(),
                     smaa_tu4x: matches!(settings.upscale, Upscale::SmaaTu4x { .. }),
                 };
                 let pipeline_id =
@@ -0,0 +1,130 @@
+use crate::{HikariSettings, Upscale};
+use bevy::{
+    ecs::query::QueryItem,
+    prelude::*,
+    render::extract_component::{ExtractComponent, ExtractComponentPlugin},
+};
+
+pub struct DynamicResolutionPlugin;
+impl Plugin for DynamicResolutionPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<HikariDynamicResolution>()
+            .add_plugin(ExtractComponentPlugin::<HikariDynamicResolution>::default())
+            .add_system_to_stage(CoreStage::PostUpdate, dynamic_resolution_system);
+    }
+}
+
+/// Adjusts the upscale ratio of [`HikariSettings`] on a camera to keep the frame time within a budget.
+///
+/// The frame time is measured from [`Time`], which includes the GPU time only when the GPU is the bottleneck,
+/// so vsync should be disabled for the controller to lower the ratio again.
+/// The ratio changes in steps, at most once per `interval` frames, so that textures are not reallocated every frame.
+/// Has no effect with [`Upscale::None`].
+#[derive(Debug, Clone, Copy, Component, Reflect)]
+#[reflect(Component)]
+pub struct HikariDynamicResolution {
+    /// Frame time budget in seconds.
+    pub target_frame_time: f32,
+    /// Lowest upscale ratio, i.e., the highest render resolution.
+    pub min_ratio: f32,
+    /// Highest upscale ratio, i.e., the lowest render resolution; limited by [`Upscale::max_ratio`].
+    pub max_ratio: f32,
+    /// Granularity of the ratio, which is also the hysteresis of the controller.
+    pub step: f32,
+    /// Minimum number of frames between ratio changes.
+    pub interval: u32,
+    #[reflect(ignore)]
+    frame_time: f32,
+    #[reflect(ignore)]
+    frames_since_change: u32,
+    #[reflect(ignore)]
+    changed: bool,
+}
+
+impl Default for HikariDynamicResolution {
+    fn default() -> Self {
+        Self {
+            target_frame_time: 1.0 / 60.0,
+            min_ratio: 1.0,
+            max_ratio: 2.0,
+            step: 0.125,
+            interval: 30,
+            frame_time: 0.0,
+            frames_since_change: 0,
+            changed: false,
+        }
+    }
+}
+
+impl HikariDynamicResolution {
+    /// Weight of the latest frame in the smoothed frame time.
+    const SMOOTHING: f32 = 0.1;
+
+    /// Smoothed frame time in seconds.
+    pub fn frame_time(&self) -> f32 {
+        self.frame_time
+    }
+
+    /// Whether the ratio changed in this frame, which discards temporal history.
+    pub fn is_changed(&self) -> bool {
+        self.changed
+    }
+}
+
+impl ExtractComponent for HikariDynamicResolution {
+    type Query = &'static Self;
+    type Filter = ();
+
+    fn extract_component(item: QueryItem<Self::Query>) -> Self {
+        *item
+    }
+}
+
+fn dynamic_resolution_system(
+    time: Res<Time>,
+    mut cameras: Query<(&mut HikariSettings, &mut HikariDynamicResolution)>,
+) {
+    let delta = time.delta_seconds();
+    if delta <= 0.0 {
+        return;
+    }
+
+    for (mut settings, mut controller) in &mut cameras {
+        controller.changed = false;
+        controller.frame_time = match controller.frame_time > 0.0 {
+            true => {
+                let smoothing = HikariDynamicResolution::SMOOTHING;
+                (1.0 - smoothing) * controller.frame_time + smoothing * delta
+            }
+            false => delta,
+        };
+        controller.frames_since_change = controller.frames_since_change.saturating_add(1);
+
+        if matches!(settings.upscale, Upscale::None)
+            || settings.is_accumulating()
+            || controller.frames_since_change < controller.interval
+        {
+            continue;
+        }
+
+        let max_ratio = settings.upscale.max_ratio();
+        let min_ratio = controller.min_ratio.clamp(1.0, max_ratio);
+        let max_ratio = controller.max_ratio.clamp(min_ratio, max_ratio);
+        let step = controller.step.max(0.01);
+
+        // The cost of a frame is roughly proportional to the number of rendered pixels.
+        let ratio = settings.upscale.ratio();
+        let load = controller.frame_time / controller.target_frame_time.max(f32::EPSILON);
+        let target = (ratio * load.sqrt()).clamp(min_ratio, max_ratio);
+        if (target - ratio).abs() < step {
+            continue;
+        }
+
+        let target = ((target / step).round() * step).clamp(min_ratio, max_ratio);
+        if target != ratio {
+            settings.upscale.set_ratio(target);
+            controller.changed = true;
+            controller.frames_since_change = 0;
+        }
+    }
+}
@@ -0,0 +1,19 @@
+@group(0) @binding(0)
+var input_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var output_texture: texture_storage_2d<rgba32float, write>;
+
+@compute @workgroup_size(8, 8, 1)
+fn aov(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let size = textureDimensions(output_texture);
+    let coords = vec2<i32>(invocation_id.xy);
+    if (any(coords >= size)) {
+        return;
+    }
+
+    // Inputs are in either render or output resolution, so texels are picked by uv.
+    let input_size = textureDimensions(input_texture);
+    let uv = (vec2<f32>(coords) + 0.5) / vec2<f32>(size);
+    let input_coords = min(vec2<i32>(uv * vec2<f32>(input_size)), input_size - 1);
+    textureStore(output_texture, coords, textureLoad(input_texture, input_coords, 0));
+}
@@ -0,0 +1,70 @@
+@group(0) @binding(0)
+var output_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var direct_render_texture: texture_2d<f32>;
+@group(0) @binding(2)
+var emissive_render_texture: texture_2d<f32>;
+@group(0) @binding(3)
+var indirect_render_texture: texture_2d<f32>;
+@group(0) @binding(4)
+var linear_sampler: sampler;
+@group(0) @binding(5)
+var capture_texture: texture_storage_2d<rgba32float, write>;
+
+@group(1) @binding(0)
+var albedo_texture: texture_2d<f32>;
+@group(1) @binding(1)
+var normal_texture: texture_2d<f32>;
+@group(1) @binding(2)
+var albedo_guide_texture: texture_storage_2d<rgba32float, write>;
+@group(1) @binding(3)
+var normal_guide_texture: texture_storage_2d<rgba32float, write>;
+
+@compute @workgroup_size(8, 8, 1)
+fn capture_output(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let size = textureDimensions(capture_texture);
+    let coords = vec2<i32>(invocation_id.xy);
+    if (any(coords >= size)) {
+        return;
+    }
+
+    // The output is resampled to the target size, the same way the overlay pass does.
+    let uv = (vec2<f32>(coords) + 0.5) / vec2<f32>(size);
+    let color = textureSampleLevel(output_texture, linear_sampler, uv, 0.0);
+    textureStore(capture_texture, coords, color);
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn capture_hdr(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let size = textureDimensions(capture_texture);
+    let coords = vec2<i32>(invocation_id.xy);
+    if (any(coords >= size)) {
+        return;
+    }
+
+    var color = textureLoad(direct_render_texture, coords, 0);
+    color += textureLoad(emissive_render_texture, coords, 0);
+    color += textureLoad(indirect_render_texture, coords, 0);
+    textureStore(capture_texture, coords, color);
+}
+
+// Albedo and normal of the primary surfaces, read back for external denoisers.
+@compute @workgroup_size(8, 8, 1)
+fn capture_guides(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let size = textureDimensions(albedo_guide_texture);
+    let coords = vec2<i32>(invocation_id.xy);
+    if (any(coords >= size)) {
+        return;
+    }
+
+    let input_size = textureDimensions(albedo_texture);
+    let input_coords = (coords * input_size) / size;
+
+    let albedo = textureLoad(albedo_texture, input_coords, 0).rgb;
+    var normal = textureLoad(normal_texture, input_coords, 0).xyz;
+    if (dot(normal, normal) > 0.0) {
+        normal = normalize(normal);
+    }
+    textureStore(albedo_guide_texture, coords, vec4<f32>(albedo, 1.0));
+    textureStore(normal_guide_texture, coords, vec4<f32>(normal, 1.0));
+}
@@ -0,0 +1,148 @@
+#import bevy_core_pipeline::tonemapping
+#import bevy_hikari::mesh_view_bindings
+#import bevy_hikari::deferred_bindings
+#import bevy_hikari::utils
+
+// 64 Bytes
+struct PackedReservoir {
+    radiance: vec2<u32>,            // RGBA16F
+    random: vec2<u32>,              // RGBA16F
+    visible_position: vec4<f32>,    // RGBA32F
+    sample_position: vec4<f32>,     // RGBA32F
+    visible_normal: u32,            // RGBA8SN
+    sample_normal: u32,             // RGBA8SN
+    reservoir: vec2<u32>,           // RGBA16F
+};
+
+struct Reservoirs {
+    data: array<PackedReservoir>,
+};
+
+@group(2) @binding(0)
+var render_texture: texture_2d<f32>;
+@group(2) @binding(1)
+var variance_texture: texture_2d<f32>;
+@group(2) @binding(2)
+var<storage, read> reservoir_buffer: Reservoirs;
+
+@group(3) @binding(0)
+var output_texture: texture_storage_2d<rgba16float, write>;
+
+let F32_EPSILON: f32 = 1.1920929E-7;
+let MAX_TRAVERSAL_LOG2: f32 = 12.0;
+let MAX_VARIANCE: f32 = 10.0;
+let MAX_VELOCITY_PIXELS: f32 = 16.0;
+
+// Maps a value from 0 to 1 to a blue-green-red heat color.
+fn heatmap(t: f32) -> vec3<f32> {
+    let t = 4.0 * clamp(t, 0.0, 1.0);
+    return clamp(vec3<f32>(1.5) - abs(vec3<f32>(t) - vec3<f32>(3.0, 2.0, 1.0)), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+fn id_color(id: u32) -> vec3<f32> {
+    let h = hash(id);
+    return vec3<f32>(vec3<u32>(h, h >> 8u, h >> 16u) & vec3<u32>(0xFFu)) / 255.0;
+}
+
+// Maps output coordinates to the corresponding texel of an input of another size.
+fn input_coords(coords: vec2<i32>, input_size: vec2<i32>) -> vec2<i32> {
+    let uv = coords_to_uv(coords, textureDimensions(output_texture));
+    return min(vec2<i32>(uv * vec2<f32>(input_size)), input_size - 1);
+}
+
+fn deferred_coords(coords: vec2<i32>) -> vec2<i32> {
+    return input_coords(coords, textureDimensions(position_texture));
+}
+
+fn render_coords(coords: vec2<i32>) -> vec2<i32> {
+    return input_coords(coords, textureDimensions(render_texture));
+}
+
+fn is_miss(coords: vec2<i32>) -> bool {
+    return textureLoad(position_texture, deferred_coords(coords), 0).w < F32_EPSILON;
+}
+
+fn load_reservoir(coords: vec2<i32>) -> PackedReservoir {
+    let render_size = textureDimensions(render_texture);
+    let render_coords = render_coords(coords);
+    return reservoir_buffer.data[render_coords.x + render_size.x * render_coords.y];
+}
+
+fn store_output(coords: vec2<i32>, color: vec3<f32>) {
+    if (any(coords >= textureDimensions(output_texture))) {
+        return;
+    }
+    textureStore(output_texture, coords, vec4<f32>(color, 1.0));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn debug_traversal(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let coords = vec2<i32>(invocation_id.xy);
+    let count = textureLoad(render_texture, render_coords(coords), 0).x;
+    store_output(coords, heatmap(log2(1.0 + count) / MAX_TRAVERSAL_LOG2));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn debug_instance(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let coords = vec2<i32>(invocation_id.xy);
+    let instance = u32(textureLoad(instance_material_texture, deferred_coords(coords), 0).x);
+    store_output(coords, select(id_color(instance), vec3<f32>(0.0), is_miss(coords)));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn debug_material(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let coords = vec2<i32>(invocation_id.xy);
+    let material = u32(textureLoad(instance_material_texture, deferred_coords(coords), 0).y);
+    store_output(coords, select(id_color(material), vec3<f32>(0.0), is_miss(coords)));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn debug_reservoir_count(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let coords = vec2<i32>(invocation_id.xy);
+    let count = unpack2x16float(load_reservoir(coords).reservoir.x).x;
+    let t = count / f32(max(frame.max_temporal_reuse_count, 1u));
+    store_output(coords, select(heatmap(t), vec3<f32>(0.0), is_miss(coords)));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn debug_reservoir_lifetime(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let coords = vec2<i32>(invocation_id.xy);
+    let lifetime = 127.0 * (1.0 + unpack4x8snorm(load_reservoir(coords).visible_normal).w);
+    let t = lifetime / max(frame.max_reservoir_lifetime, 1.0);
+    store_output(coords, select(heatmap(t), vec3<f32>(0.0), is_miss(coords)));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn debug_variance(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let coords = vec2<i32>(invocation_id.xy);
+    let variance_size = textureDimensions(variance_texture);
+    let variance = textureLoad(variance_texture, input_coords(coords, variance_size), 0).x;
+    store_output(coords, heatmap(sqrt(variance / MAX_VARIANCE)));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn debug_depth_gradient(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let coords = vec2<i32>(invocation_id.xy);
+    let deferred_coords = deferred_coords(coords);
+    let depth = textureLoad(position_texture, deferred_coords, 0).w;
+    let gradient = textureLoad(depth_gradient_texture, deferred_coords, 0).xy;
+
+    // Relative to the depth, so that the gradient of a plane doesn't fade with the distance.
+    let color = vec3<f32>(abs(gradient) / max(depth, F32_EPSILON), 0.0);
+    store_output(coords, select(clamp(color, vec3<f32>(0.0), vec3<f32>(1.0)), vec3<f32>(0.0), is_miss(coords)));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn debug_velocity(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let coords = vec2<i32>(invocation_id.xy);
+    let velocity = textureLoad(velocity_uv_texture, deferred_coords(coords), 0).xy;
+    let pixels = velocity * vec2<f32>(textureDimensions(output_texture)) / MAX_VELOCITY_PIXELS;
+    store_output(coords, vec3<f32>(0.5 + 0.5 * clamp(pixels, vec2<f32>(-1.0), vec2<f32>(1.0)), 0.5));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn debug_radiance(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let coords = vec2<i32>(invocation_id.xy);
+    let radiance = textureLoad(render_texture, render_coords(coords), 0).rgb;
+    store_output(coords, reinhard_luminance(max(radiance, vec3<f32>(0.0))));
+}
@@ -27,6 +27,31 @@ var render_texture: texture_2d<f32>;
 @group(4) @binding(3)
 var output_texture: texture_storage_2d<rgba16float, read_write>;
 
+struct DenoiseSettings {
+    iterations: u32,
+    kernel_radius: u32,
+    temporal_accumulation: u32,
+    specular: u32,
+    normal_sigma: f32,
+    depth_sigma: f32,
+    luminance_sigma: f32,
+};
+@group(4) @binding(4)
+var<uniform> denoise_settings: DenoiseSettings;
+@group(4) @binding(5)
+var specular_albedo_texture: texture_2d<f32>;
+@group(4) @binding(6)
+var specular_render_texture: texture_2d<f32>;
+
+@group(5) @binding(0)
+var previous_history_texture: texture_2d<f32>;
+@group(5) @binding(1)
+var previous_moments_texture: texture_2d<f32>;
+@group(5) @binding(2)
+var history_texture: texture_storage_2d<rgba16float, read_write>;
+@group(5) @binding(3)
+var moments_texture: texture_storage_2d<rgba16float, read_write>;
+
 let TAU: f32 = 6.283185307;
 let GOLDEN_RATIO: f32 = 1.618033989;
 
@@ -34,30 +59,68 @@ let F32_EPSILON: f32 = 1.1920929E-7;
 let F32_MAX: f32 = 3.402823466E+38;
 let U32_MAX: u32 = 0xFFFFFFFFu;
 
+// Lowest blending weight of the current frame, i.e., the longest effective history.
+let TEMPORAL_ALPHA: f32 = 0.2;
+let MAX_HISTORY_LENGTH: f32 = 32.0;
+// History length below which the variance is estimated from the light pass instead of the temporal moments,
+// and the luminance weight is relaxed.
+let MIN_HISTORY_LENGTH: f32 = 4.0;
+// Shortest history of perfectly smooth specular surfaces, whose reflections can't be reprojected with the surface.
+let MIN_SPECULAR_HISTORY_LENGTH: f32 = 2.0;
+// Roughness below which the specular normal weight doesn't get stricter.
+let MIN_ROUGHNESS: f32 = 0.05;
+let ROUGHNESS_SIGMA: f32 = 10.0;
+// Radius in pixels of the specular filter on fully rough surfaces.
+let SPECULAR_RADIUS: f32 = 16.0;
+// Relative distance between the current and previous positions to be considered disoccluded.
+let DISOCCLUSION_THRESHOLD: f32 = 0.05;
+// Cosine of the largest angle between the current and previous normals of the same surface.
+let NORMAL_THRESHOLD: f32 = 0.9;
+
 fn jittered_deferred_uv(uv: vec2<f32>) -> vec2<f32> {
     let texel_size = 1.0 / vec2<f32>(textureDimensions(position_texture));
+    if frame.temporal_upscale != 0u {
+        return uv + frame.upscale_jitter * texel_size;
+    }
     let ratio = frame.upscale_ratio - 1.0;
     return uv + select(0.5, -0.5, (frame.number & 1u) == 0u) * texel_size * ratio;
 }
 
 // Normal-weighting function (4.4.1)
 fn normal_weight(n0: vec3<f32>, n1: vec3<f32>) -> f32 {
-    let exponent = 16.0;
-    return pow(max(0.0, dot(n0, n1)), exponent);
+    return pow(max(0.0, dot(n0, n1)), denoise_settings.normal_sigma);
 }
 
 // Depth-weighting function (4.4.2)
 fn depth_weight(d0: f32, d1: f32, gradient: vec2<f32>, offset: vec2<f32>) -> f32 {
     let eps = 0.01;
-    return exp((-abs(d0 - d1)) / (abs(dot(gradient, offset)) + eps));
+    return exp((-abs(d0 - d1)) / (denoise_settings.depth_sigma * abs(dot(gradient, offset)) + eps));
 }
 
 // Luminance-weighting function (4.4.3)
-fn luminance_weight(l0: f32, l1: f32, variance: f32) -> f32 {
-    let strictness = 4.0;
+fn luminance_weight(l0: f32, l1: f32, variance: f32, sigma: f32) -> f32 {
     let exponent = 0.25;
     let eps = 0.001;
-    return exp((-abs(l0 - l1)) / (strictness * pow(variance, exponent) + eps));
+    return exp((-abs(l0 - l1)) / (sigma * pow(variance, exponent) + eps));
+}
+
+// 1D B-spline kernel: (1/4, 1/2, 1/4) with radius 1, (1/16, 1/4, 3/8, 1/4, 1/16) with radius 2.
+fn kernel_weight(offset: i32) -> f32 {
+    let x = abs(offset);
+    if denoise_settings.kernel_radius > 1u {
+        return select(select(0.0625, 0.25, x == 1), 0.375, x == 0);
+    }
+    return select(0.25, 0.5, x == 0);
+}
+
+// Specular lobes narrow with the roughness, so smooth surfaces only accept samples with close normals and roughness,
+// within a radius proportional to the roughness.
+fn specular_weight(n0: vec3<f32>, n1: vec3<f32>, r0: f32, r1: f32, offset: vec2<f32>) -> f32 {
+    let w_normal = pow(max(0.0, dot(n0, n1)), denoise_settings.normal_sigma / max(r0, MIN_ROUGHNESS));
+    let w_roughness = exp(-abs(r0 - r1) * ROUGHNESS_SIGMA);
+    let radius = max(SPECULAR_RADIUS * r0, 0.01);
+    let w_radius = exp(-dot(offset, offset) / (2.0 * radius * radius));
+    return w_normal * w_roughness * w_radius;
 }
 
 fn instance_weight(i0: f32, i1: f32) -> f32 {
@@ -68,6 +131,36 @@ fn position_weight(p0: vec2<f32>, p1: vec2<f32>) -> f32 {
     return exp(-distance(p0, p1));
 }
 
+// Albedo of the part being denoised: the specular part of the environment BRDF, or the rest of it.
+// The specular render is not accumulated, so accumulated images are denoised as a whole in the diffuse part.
+fn sample_albedo(uv: vec2<f32>) -> vec3<f32> {
+    var specular_albedo = textureSampleLevel(specular_albedo_texture, nearest_sampler, uv, 0.0).rgb;
+    if frame.accumulate != 0u {
+        specular_albedo = vec3<f32>(0.0);
+    }
+    if denoise_settings.specular != 0u {
+        return specular_albedo;
+    }
+    let albedo = textureSampleLevel(albedo_texture, nearest_sampler, uv, 0.0).rgb;
+    return max(albedo - specular_albedo, vec3<f32>(0.0));
+}
+
+fn sample_render(uv: vec2<f32>) -> vec3<f32> {
+    var specular = textureSampleLevel(specular_render_texture, nearest_sampler, uv, 0.0).rgb;
+    if frame.accumulate != 0u {
+        specular = vec3<f32>(0.0);
+    }
+    if denoise_settings.specular != 0u {
+        return specular;
+    }
+    let render = textureSampleLevel(render_texture, nearest_sampler, uv, 0.0).rgb;
+    return max(render - specular, vec3<f32>(0.0));
+}
+
+fn sample_roughness(uv: vec2<f32>) -> f32 {
+    return textureSampleLevel(specular_albedo_texture, nearest_sampler, uv, 0.0).a;
+}
+
 fn load_input(coords: vec2<i32>) -> vec4<f32> {
 #ifdef DENOISE_LEVEL_0
     return textureLoad(internal_texture_0, coords);
@@ -98,6 +191,32 @@ fn store_output(coords: vec2<i32>, value: vec4<f32>) {
 #endif
 }
 
+// Fewer iterations skip the coarsest levels, so the filter starts at the internal texture of the first level run.
+fn store_demodulated(coords: vec2<i32>, value: vec4<f32>) {
+    let level = 4u - denoise_settings.iterations;
+    if level == 0u {
+        textureStore(internal_texture_0, coords, value);
+    } else if level == 1u {
+        textureStore(internal_texture_1, coords, value);
+    } else if level == 2u {
+        textureStore(internal_texture_2, coords, value);
+    } else {
+        textureStore(internal_texture_3, coords, value);
+    }
+}
+
+fn load_demodulated(coords: vec2<i32>) -> vec4<f32> {
+    let level = 4u - denoise_settings.iterations;
+    if level == 0u {
+        return textureLoad(internal_texture_0, coords);
+    } else if level == 1u {
+        return textureLoad(internal_texture_1, coords);
+    } else if level == 2u {
+        return textureLoad(internal_texture_2, coords);
+    }
+    return textureLoad(internal_texture_3, coords);
+}
+
 fn step_size() -> i32 {
 #ifdef DENOISE_LEVEL_0
     return 8;
@@ -141,12 +260,12 @@ fn demodulation(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
     let uv = coords_to_uv(coords, output_size);
     let deferred_uv = jittered_deferred_uv(uv);
 
-    let albedo = textureSampleLevel(albedo_texture, nearest_sampler, deferred_uv, 0.0).rgb;
-    var irradiance = textureSampleLevel(render_texture, nearest_sampler, uv, 0.0).rgb;
+    let albedo = sample_albedo(deferred_uv);
+    var irradiance = sample_render(uv);
     irradiance = select(irradiance / albedo, vec3<f32>(0.0), albedo < vec3<f32>(0.01));
 
     let color = vec4<f32>(irradiance, 1.0);
-    textureStore(internal_texture_0, coords, color);
+    store_demodulated(coords, color);
 
     var sum_variance = 0.0;
     accumulate_variance(uv, input_size, vec2<i32>(-1, -1), &sum_variance);
@@ -161,6 +280,120 @@ fn demodulation(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
     textureStore(internal_variance, coords, vec4<f32>(sum_variance));
 }
 
+fn deferred_coords(uv: vec2<f32>, size: vec2<i32>) -> vec2<i32> {
+    return clamp(vec2<i32>(uv * vec2<f32>(size)), vec2<i32>(0), size - 1);
+}
+
+// Whether the previous surface seen at the history pixel is the current one.
+fn is_history_consistent(
+    coords: vec2<i32>,
+    size: vec2<i32>,
+    position: vec3<f32>,
+    normal: vec3<f32>,
+    instance: f32,
+) -> bool {
+    if any(coords < vec2<i32>(0)) || any(coords >= size) {
+        return false;
+    }
+
+    let uv = coords_to_uv(coords, size);
+    let previous_coords = deferred_coords(uv, textureDimensions(previous_position_texture));
+    let previous_position = textureLoad(previous_position_texture, previous_coords, 0);
+    let previous_normal = normalize(textureLoad(previous_normal_texture, previous_coords, 0).xyz);
+    let previous_instance = f32(textureLoad(previous_instance_material_texture, previous_coords, 0).x);
+
+    let view_distance = distance(position, view.world_position);
+    let position_miss = previous_position.w == 0.0 || distance(position, previous_position.xyz) > DISOCCLUSION_THRESHOLD * view_distance;
+    let normal_miss = dot(normal, previous_normal) < NORMAL_THRESHOLD;
+    let instance_miss = abs(instance - previous_instance) > 0.5;
+    return !(position_miss || normal_miss || instance_miss);
+}
+
+// Temporal filtering (4.1) and variance estimation (4.2)
+@compute @workgroup_size(8, 8, 1)
+fn temporal_accumulation(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let output_size = textureDimensions(output_texture);
+    let coords = vec2<i32>(invocation_id.xy);
+    if any(coords >= output_size) {
+        return;
+    }
+
+    let uv = coords_to_uv(coords, output_size);
+    let deferred_uv = jittered_deferred_uv(uv);
+    let current_coords = deferred_coords(deferred_uv, textureDimensions(position_texture));
+
+    let irradiance = load_demodulated(coords).rgb;
+    let lum = luminance(irradiance);
+    let moments = vec2<f32>(lum, lum * lum);
+
+    let position = textureLoad(position_texture, current_coords, 0);
+    if position.w == 0.0 {
+        textureStore(history_texture, coords, vec4<f32>(irradiance, 0.0));
+        textureStore(moments_texture, coords, vec4<f32>(moments, 0.0, 0.0));
+        return;
+    }
+
+    let normal = normalize(textureLoad(normal_texture, current_coords, 0).xyz);
+    let instance = textureLoad(instance_material_texture, current_coords, 0).x;
+    let velocity = textureLoad(velocity_uv_texture, current_coords, 0).xy;
+    let previous_uv = deferred_uv - velocity;
+
+    // Bilinear filtering of the history, dropping the taps that fail the consistency tests.
+    let previous_pixel = previous_uv * vec2<f32>(output_size) - 0.5;
+    let base_coords = vec2<i32>(floor(previous_pixel));
+    let f = fract(previous_pixel);
+
+    var previous_irradiance = vec3<f32>(0.0);
+    var previous_moments = vec2<f32>(0.0);
+    var previous_length = 0.0;
+    var sum_w = 0.0;
+    for (var i = 0u; i < 4u; i += 1u) {
+        let offset = vec2<u32>(i & 1u, i >> 1u);
+        let sample_coords = base_coords + vec2<i32>(offset);
+        if !is_history_consistent(sample_coords, output_size, position.xyz, normal, instance) {
+            continue;
+        }
+
+        let bilinear = select(1.0 - f, f, offset == vec2<u32>(1u));
+        let w = bilinear.x * bilinear.y;
+        let history = textureLoad(previous_history_texture, sample_coords, 0);
+        previous_irradiance += w * history.rgb;
+        previous_moments += w * textureLoad(previous_moments_texture, sample_coords, 0).xy;
+        previous_length += w * history.a;
+        sum_w += w;
+    }
+
+    var history_length = 0.0;
+    if sum_w > 0.01 && frame.history_reset == 0u {
+        previous_irradiance /= sum_w;
+        previous_moments /= sum_w;
+        history_length = previous_length / sum_w;
+    }
+    if any_is_nan_vec3(previous_irradiance) {
+        history_length = 0.0;
+    }
+    var max_length = MAX_HISTORY_LENGTH;
+    if denoise_settings.specular != 0u {
+        max_length = mix(MIN_SPECULAR_HISTORY_LENGTH, MAX_HISTORY_LENGTH, sample_roughness(deferred_uv));
+    }
+    history_length = min(history_length + 1.0, max_length);
+
+    // The first frames are averaged uniformly, then blended exponentially.
+    let alpha = max(1.0 / history_length, TEMPORAL_ALPHA);
+    let accumulated_irradiance = mix(previous_irradiance, irradiance, alpha);
+    let accumulated_moments = mix(previous_moments, moments, alpha);
+
+    // Short histories keep the spatial estimate of the demodulation pass.
+    if history_length >= MIN_HISTORY_LENGTH {
+        let variance = max(accumulated_moments.y - accumulated_moments.x * accumulated_moments.x, 0.0);
+        textureStore(internal_variance, coords, vec4<f32>(variance));
+    }
+
+    store_demodulated(coords, vec4<f32>(accumulated_irradiance, 1.0));
+    textureStore(history_texture, coords, vec4<f32>(accumulated_irradiance, history_length));
+    textureStore(moments_texture, coords, vec4<f32>(accumulated_moments, 0.0, 0.0));
+}
+
 fn accumulate_irradiance(
     output_size: vec2<i32>,
     coords: vec2<i32>,
@@ -169,8 +402,10 @@ fn accumulate_irradiance(
     depth: f32,
     depth_gradient: vec2<f32>,
     instance: f32,
+    roughness: f32,
     lum: f32,
     variance: f32,
+    luminance_sigma: f32,
     sum_irradiance: ptr<function, vec3<f32>>,
     sum_w: ptr<function, f32>,
 #ifdef FIREFLY_FILTERING
@@ -196,12 +431,16 @@ fn accumulate_irradiance(
     let sample_instance = textureSampleLevel(instance_material_texture, nearest_sampler, sample_deferred_uv, 0.0).x;
     let sample_luminance = luminance(irradiance);
 
-    let w_normal = normal_weight(normal, sample_normal);
+    var w_normal = normal_weight(normal, sample_normal);
+    if denoise_settings.specular != 0u {
+        let neighbor_roughness = sample_roughness(sample_deferred_uv);
+        w_normal = specular_weight(normal, sample_normal, roughness, neighbor_roughness, vec2<f32>(offset * step_size()));
+    }
     let w_depth = depth_weight(depth, sample_depth, depth_gradient, vec2<f32>(offset));
     let w_instance = instance_weight(instance, sample_instance);
-    let w_luminance = luminance_weight(lum, sample_luminance, variance);
+    let w_luminance = luminance_weight(lum, sample_luminance, variance, luminance_sigma);
 
-    let w = clamp(w_normal * w_depth * w_instance * w_luminance, 0.0, 1.0) * frame.kernel[offset.y + 1][offset.x + 1];
+    let w = clamp(w_normal * w_depth * w_instance * w_luminance, 0.0, 1.0) * kernel_weight(offset.x) * kernel_weight(offset.y);
     *sum_irradiance += irradiance * w;
     *sum_w += w;
 
@@ -224,17 +463,27 @@ fn denoise(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
     let depth_gradient = textureSampleLevel(depth_gradient_texture, nearest_sampler, deferred_uv, 0.0).xy;
     let normal = normalize(textureSampleLevel(normal_texture, nearest_sampler, deferred_uv, 0.0).xyz);
     let instance = textureSampleLevel(instance_material_texture, nearest_sampler, deferred_uv, 0.0).x;
+    let roughness = sample_roughness(deferred_uv);
 
+    // There is no surface to filter with, but area lights seen by the camera are passed through.
     if depth < F32_EPSILON {
-        store_output(coords, vec4<f32>(0.0));
+        var color = load_input(coords);
+#ifdef DENOISE_LEVEL_3
+        color = vec4<f32>(color.rgb * sample_albedo(deferred_uv), color.a);
+        if denoise_settings.specular != 0u {
+            color += vec4<f32>(textureLoad(output_texture, coords).rgb, 0.0);
+        }
+#endif
+        store_output(coords, color);
         return;
     }
 
     let variance = textureLoad(internal_variance, coords).x;
     var irradiance = load_input(coords).rgb;
 
-    var sum_irradiance = irradiance * frame.kernel[1][1];
-    var sum_w = frame.kernel[1][1];
+    let center_weight = kernel_weight(0) * kernel_weight(0);
+    var sum_irradiance = irradiance * center_weight;
+    var sum_w = center_weight;
 
     if any_is_nan_vec3(irradiance) || any(irradiance > vec3<f32>(F32_MAX)) {
         irradiance = vec3<f32>(0.0);
@@ -244,29 +493,32 @@ fn denoise(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
 
     let lum = luminance(irradiance);
 
+    // Newly revealed surfaces have few samples accumulated, so they are filtered wider.
+    var history_length = MAX_HISTORY_LENGTH;
+    if denoise_settings.temporal_accumulation != 0u {
+        history_length = max(textureLoad(history_texture, coords).a, 1.0);
+    }
+    let luminance_sigma = denoise_settings.luminance_sigma * max(MIN_HISTORY_LENGTH / history_length, 1.0);
+
 #ifdef FIREFLY_FILTERING
     var ff_moment_1 = 0.0;
     var ff_moment_2 = 0.0;
     var ff_count = 0.0;
+#endif
 
-    accumulate_irradiance(output_size, coords, vec2<i32>(-1, -1), normal, depth, depth_gradient, instance, lum, variance, &sum_irradiance, &sum_w, &ff_moment_1, &ff_moment_2, &ff_count);
-    accumulate_irradiance(output_size, coords, vec2<i32>(0, -1), normal, depth, depth_gradient, instance, lum, variance, &sum_irradiance, &sum_w, &ff_moment_1, &ff_moment_2, &ff_count);
-    accumulate_irradiance(output_size, coords, vec2<i32>(1, -1), normal, depth, depth_gradient, instance, lum, variance, &sum_irradiance, &sum_w, &ff_moment_1, &ff_moment_2, &ff_count);
-    accumulate_irradiance(output_size, coords, vec2<i32>(-1, 0), normal, depth, depth_gradient, instance, lum, variance, &sum_irradiance, &sum_w, &ff_moment_1, &ff_moment_2, &ff_count);
-    accumulate_irradiance(output_size, coords, vec2<i32>(1, 0), normal, depth, depth_gradient, instance, lum, variance, &sum_irradiance, &sum_w, &ff_moment_1, &ff_moment_2, &ff_count);
-    accumulate_irradiance(output_size, coords, vec2<i32>(-1, 1), normal, depth, depth_gradient, instance, lum, variance, &sum_irradiance, &sum_w, &ff_moment_1, &ff_moment_2, &ff_count);
-    accumulate_irradiance(output_size, coords, vec2<i32>(0, 1), normal, depth, depth_gradient, instance, lum, variance, &sum_irradiance, &sum_w, &ff_moment_1, &ff_moment_2, &ff_count);
-    accumulate_irradiance(output_size, coords, vec2<i32>(1, 1), normal, depth, depth_gradient, instance, lum, variance, &sum_irradiance, &sum_w, &ff_moment_1, &ff_moment_2, &ff_count);
+    let radius = i32(denoise_settings.kernel_radius);
+    for (var y = -radius; y <= radius; y += 1) {
+        for (var x = -radius; x <= radius; x += 1) {
+            if x == 0 && y == 0 {
+                continue;
+            }
+#ifdef FIREFLY_FILTERING
+            accumulate_irradiance(output_size, coords, vec2<i32>(x, y), normal, depth, depth_gradient, instance, roughness, lum, variance, luminance_sigma, &sum_irradiance, &sum_w, &ff_moment_1, &ff_moment_2, &ff_count);
 #else
-    accumulate_irradiance(output_size, coords, vec2<i32>(-1, -1), normal, depth, depth_gradient, instance, lum, variance, &sum_irradiance, &sum_w);
-    accumulate_irradiance(output_size, coords, vec2<i32>(0, -1), normal, depth, depth_gradient, instance, lum, variance, &sum_irradiance, &sum_w);
-    accumulate_irradiance(output_size, coords, vec2<i32>(1, -1), normal, depth, depth_gradient, instance, lum, variance, &sum_irradiance, &sum_w);
-    accumulate_irradiance(output_size, coords, vec2<i32>(-1, 0), normal, depth, depth_gradient, instance, lum, variance, &sum_irradiance, &sum_w);
-    accumulate_irradiance(output_size, coords, vec2<i32>(1, 0), normal, depth, depth_gradient, instance, lum, variance, &sum_irradiance, &sum_w);
-    accumulate_irradiance(output_size, coords, vec2<i32>(-1, 1), normal, depth, depth_gradient, instance, lum, variance, &sum_irradiance, &sum_w);
-    accumulate_irradiance(output_size, coords, vec2<i32>(0, 1), normal, depth, depth_gradient, instance, lum, variance, &sum_irradiance, &sum_w);
-    accumulate_irradiance(output_size, coords, vec2<i32>(1, 1), normal, depth, depth_gradient, instance, lum, variance, &sum_irradiance, &sum_w);
+            accumulate_irradiance(output_size, coords, vec2<i32>(x, y), normal, depth, depth_gradient, instance, roughness, lum, variance, luminance_sigma, &sum_irradiance, &sum_w);
 #endif
+        }
+    }
 
     irradiance = select(sum_irradiance / sum_w, vec3<f32>(0.0), sum_w < 0.0001);
 
@@ -311,8 +563,11 @@ fn denoise(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
     // color = select(mixed_color, color, any_is_nan_vec4(mixed_color) || previous_color.a == 0.0);
     // textureStore(radiance_texture, coords, color);
 
-    let albedo = textureSampleLevel(albedo_texture, nearest_sampler, deferred_uv, 0.0);
-    color *= albedo;
+    let albedo = sample_albedo(deferred_uv);
+    color = vec4<f32>(color.rgb * albedo, color.a);
+    if denoise_settings.specular != 0u {
+        color += vec4<f32>(textureLoad(output_texture, coords).rgb, 0.0);
+    }
 #endif
 
     store_output(coords, color);
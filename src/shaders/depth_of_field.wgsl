@@ -0,0 +1,106 @@
+#import bevy_hikari::mesh_view_bindings
+#import bevy_hikari::deferred_bindings
+#import bevy_hikari::utils
+
+@group(2) @binding(0)
+var nearest_sampler: sampler;
+@group(2) @binding(1)
+var linear_sampler: sampler;
+
+@group(3) @binding(0)
+var input_texture: texture_2d<f32>;
+
+@group(4) @binding(0)
+var output_texture: texture_storage_2d<rgba16float, read_write>;
+
+let PI: f32 = 3.141592653589793;
+let TAU: f32 = 6.283185307;
+let GOLDEN_ANGLE: f32 = 2.399963230;
+let MISS_DEPTH: f32 = 1.0e10;
+
+// Radius in pixels of the largest circle of confusion.
+let MAX_COC: f32 = 16.0;
+// Spacing of the gather spiral; the tap count grows with (MAX_COC / RADIUS_SCALE)^2.
+let RADIUS_SCALE: f32 = 1.0;
+
+fn view_depth(coords: vec2<i32>) -> f32 {
+    let size = textureDimensions(input_texture);
+    let deferred_size = textureDimensions(position_texture);
+    let deferred_coords = min(vec2<i32>(coords_to_uv(coords, size) * vec2<f32>(deferred_size)), deferred_size - 1);
+    let position = textureLoad(position_texture, deferred_coords, 0);
+    if (position.w == 0.0) {
+        return MISS_DEPTH;
+    }
+    return -(view.inverse_view * vec4<f32>(position.xyz, 1.0)).z;
+}
+
+// Signed radius in pixels of the circle of confusion of a thin lens: negative in front of the focus plane.
+fn circle_of_confusion(depth: f32) -> f32 {
+    let pixels_per_unit = 0.5 * view.projection[1][1] * f32(textureDimensions(input_texture).y);
+    let focus_distance = max(frame.focus_distance, 0.0001);
+    let coc = frame.aperture * (depth - focus_distance) / (max(depth, 0.0001) * focus_distance);
+    return clamp(coc * pixels_per_unit, -MAX_COC, MAX_COC);
+}
+
+// Scales a unit disk sample at the angle onto the boundary of the aperture polygon.
+fn aperture_shape(angle: f32) -> f32 {
+    if (frame.aperture_blades < 3u) {
+        return 1.0;
+    }
+    let segment = TAU / f32(frame.aperture_blades);
+    let theta = fract((angle - frame.aperture_rotation) / segment) * segment;
+    return cos(0.5 * segment) / cos(theta - 0.5 * segment);
+}
+
+// Scatter-as-gather on a golden angle spiral.
+// https://blog.voxagon.se/2018/05/04/bokeh-depth-of-field-in-single-pass.html
+@compute @workgroup_size(8, 8, 1)
+fn depth_of_field(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let size = textureDimensions(output_texture);
+    let coords = vec2<i32>(invocation_id.xy);
+    if (any(coords >= size)) {
+        return;
+    }
+
+    let center = textureLoad(input_texture, coords, 0);
+    let center_depth = view_depth(coords);
+    let center_size = abs(circle_of_confusion(center_depth));
+
+    // Rotate and offset the spiral with the sub-pixel jitter, so that the lens is sampled temporally.
+    let index = frame.number & 15u;
+    let halton = frame.halton[index >> 1u];
+    let jitter = select(halton.zw, halton.xy, (index & 1u) == 0u);
+
+    var color = inverse_reversible_tone_map(center.rgb);
+    var total = 1.0;
+    var angle = TAU * jitter.x;
+    var radius = RADIUS_SCALE * (0.5 + jitter.y);
+
+    loop {
+        if (radius >= MAX_COC) {
+            break;
+        }
+
+        let distance = radius * aperture_shape(angle);
+        let offset = vec2<i32>(round(distance * vec2<f32>(cos(angle), sin(angle))));
+        let sample_coords = clamp(coords + offset, vec2<i32>(0), size - 1);
+        let sample_color = inverse_reversible_tone_map(textureLoad(input_texture, sample_coords, 0).rgb);
+
+        // Background samples don't bleed over sharper foreground.
+        let sample_depth = view_depth(sample_coords);
+        var sample_size = abs(circle_of_confusion(sample_depth));
+        if (sample_depth > center_depth) {
+            sample_size = min(sample_size, 2.0 * center_size);
+        }
+
+        let weight = smoothstep(distance - 0.5, distance + 0.5, sample_size);
+        color += mix(color / total, sample_color, weight);
+        total += 1.0;
+
+        radius += RADIUS_SCALE / radius;
+        angle += GOLDEN_ANGLE;
+    }
+
+    color /= total;
+    textureStore(output_texture, coords, vec4<f32>(reversible_tone_map(color), center.a));
+}
@@ -6,6 +6,12 @@
 #import bevy_hikari::mesh_material_bindings
 #import bevy_hikari::deferred_bindings
 
+#ifdef TEXTURE_ATLAS
+@group(3) @binding(0)
+var textures: texture_2d<f32>;
+@group(3) @binding(1)
+var samplers: sampler;
+#else
 #ifdef NO_TEXTURE
 @group(3) @binding(0)
 var textures: texture_2d<f32>;
@@ -17,9 +23,10 @@ var textures: binding_array<texture_2d<f32>>;
 @group(3) @binding(1)
 var samplers: binding_array<sampler>;
 #endif
+#endif
 
 @group(4) @binding(0)
-var noise_texture: binding_array<texture_2d<f32>>;
+var noise_texture: texture_2d_array<f32>;
 @group(4) @binding(1)
 var noise_sampler: sampler;
 
@@ -29,6 +36,14 @@ var albedo_texture: texture_storage_2d<rgba16float, read_write>;
 var variance_texture: texture_storage_2d<r32float, read_write>;
 @group(5) @binding(2)
 var render_texture: texture_storage_2d<rgba16float, read_write>;
+@group(5) @binding(3)
+var accumulation_texture: texture_storage_2d<rgba32float, read_write>;
+@group(5) @binding(4)
+var specular_albedo_texture: texture_storage_2d<rgba16float, read_write>;
+@group(5) @binding(5)
+var specular_texture: texture_storage_2d<rgba16float, read_write>;
+@group(5) @binding(6)
+var area_light_texture: texture_storage_2d<r32uint, read_write>;
 
 // -------- RESERVOIR   --------
 // 64 Bytes
@@ -223,6 +238,30 @@ fn store_spatial_reservoir(index: i32, r: Reservoir) {
 }
 // -------- RESERVOIR   --------
 
+#ifdef DEBUG_TRAVERSAL
+// Number of BVH nodes visited by all rays traced in the current invocation.
+var<private> traversal_count: u32;
+#endif
+
+// In accumulation mode, adds the radiance to the running sum and returns the average of all samples so far.
+fn accumulate_radiance(coords: vec2<i32>, radiance: vec4<f32>) -> vec4<f32> {
+#ifdef DEBUG_TRAVERSAL
+    // The traversal cost is rendered instead of the radiance.
+    return vec4<f32>(f32(traversal_count), 0.0, 0.0, 1.0);
+#else
+    if frame.accumulate == 0u {
+        return radiance;
+    }
+
+    var sum = radiance;
+    if frame.accumulated_samples > 0u {
+        sum += textureLoad(accumulation_texture, coords);
+    }
+    textureStore(accumulation_texture, coords, sum);
+    return sum / f32(frame.accumulated_samples + 1u);
+#endif
+}
+
 let TAU: f32 = 6.283185307;
 let INV_TAU: f32 = 0.159154943;
 
@@ -238,11 +277,21 @@ let GOLDEN_RATIO: f32 = 1.618033989;
 let POSITION_MISS_THRESHOLD: f32 = 0.5;
 let MAX_VARIANCE: f32 = 10.0;
 
+// Primary surfaces sample the finest mip, as the prepass does; this is the LOD of a ray without a cone.
+let PRIMARY_LOD: f32 = -3.402823466E+38;
+
 let DONT_EXCLUDE: u32 = 0xFFFFFFFFu;
 let DONT_SAMPLE_DIRECTIONAL_LIGHT: u32 = 0xFFFFFFFFu;
 let DONT_SAMPLE_EMISSIVE: u32 = 0x80000000u;
 let SAMPLE_ALL_EMISSIVE: u32 = 0xFFFFFFFFu;
 
+// Emissive indices with this flag refer to area lights instead of instances.
+let AREA_LIGHT_FLAG: u32 = 0x40000000u;
+let AREA_LIGHT_RECT: u32 = 0u;
+let AREA_LIGHT_DISK: u32 = 1u;
+let AREA_LIGHT_SPHERE: u32 = 2u;
+let AREA_LIGHT_TUBE: u32 = 3u;
+
 #ifdef EMISSIVE_LIT
 let SPATIAL_REUSE_COUNT: u32 = 8u;
 let SPATIAL_REUSE_RANGE: f32 = 10.0;
@@ -252,6 +301,10 @@ let SPATIAL_REUSE_RANGE: f32 = 20.0;
 #endif
 let SPATIAL_REUSE_TAPS: u32 = 4u;
 
+// Bounds of the probability of sampling the specular lobe, so that both lobes are always sampled.
+let MIN_SPECULAR_PROBABILITY: f32 = 0.1;
+let MAX_SPECULAR_PROBABILITY: f32 = 0.9;
+
 let DIRECT_VALIDATION_FRAME_SAMPLE_THRESHOLD: u32 = 4u;
 let SPATIAL_VARIANCE_SAMPLE_THRESHOLD: u32 = 4u;
 
@@ -260,6 +313,9 @@ struct Ray {
     origin: vec3<f32>,
     direction: vec3<f32>,
     inv_direction: vec3<f32>,
+    // Width of the ray cone at the origin, and its spread angle.
+    // A zero cone disables texture LOD selection.
+    cone: vec2<f32>,
 };
 
 struct Aabb {
@@ -293,6 +349,8 @@ struct HitInfo {
     uv: vec2<f32>,
     instance_index: u32,
     material_index: u32,
+    // Texture LOD at the hit, excluding the texture resolution term.
+    lod: f32,
 };
 
 struct LightCandidate {
@@ -401,6 +459,9 @@ fn traverse_bottom(hit: ptr<function, Hit>, ray: Ray, mesh: MeshIndex, early_dis
     var intersected = false;
     var index = 0u;
     for (; index < mesh.node.y;) {
+#ifdef DEBUG_TRAVERSAL
+        traversal_count += 1u;
+#endif
         let node_index = mesh.node.x + index;
         let node = asset_node_buffer.data[node_index];
         var aabb: Aabb;
@@ -447,6 +508,9 @@ fn traverse_top(ray: Ray, max_distance: f32, early_distance: f32, exclude_instan
 
     var index = 0u;
     for (; index < instance_node_buffer.count;) {
+#ifdef DEBUG_TRAVERSAL
+        traversal_count += 1u;
+#endif
         let node = instance_node_buffer.data[index];
         var aabb: Aabb;
 
@@ -490,13 +554,29 @@ fn empty_hit_info(position: vec3<f32>, direction: vec3<f32>) -> HitInfo {
     info.instance_index = U32_MAX;
     info.material_index = U32_MAX;
     info.position = vec4<f32>(position + direction * DISTANCE_MAX, 0.0);
+    info.lod = -F32_MAX;
     return info;
 }
 
+// Ray cone texture LOD, see "Improved Shader and Texture Level of Detail Using Ray Cones", Akenine-Möller et al.
+fn ray_cone_lod(ray: Ray, distance: f32, p0: vec3<f32>, p1: vec3<f32>, p2: vec3<f32>, uv0: vec2<f32>, uv1: vec2<f32>, uv2: vec2<f32>) -> f32 {
+    let width = ray.cone.x + ray.cone.y * distance;
+    let world_normal = cross(p1 - p0, p2 - p0);
+    let world_area = length(world_normal);
+    let uv_area = abs((uv1.x - uv0.x) * (uv2.y - uv0.y) - (uv2.x - uv0.x) * (uv1.y - uv0.y));
+    if width <= 0.0 || world_area < F32_EPSILON || uv_area < F32_EPSILON {
+        return -F32_MAX;
+    }
+
+    let cos_theta = max(abs(dot(ray.direction, world_normal / world_area)), 0.01);
+    return 0.5 * log2(uv_area / world_area) + log2(width / cos_theta);
+}
+
 fn hit_info(ray: Ray, hit: Hit) -> HitInfo {
     var info: HitInfo;
     info.instance_index = hit.instance_index;
     info.material_index = U32_MAX;
+    info.lod = -F32_MAX;
 
     if hit.instance_index != U32_MAX {
         let instance = instance_buffer[hit.instance_index];
@@ -515,6 +595,13 @@ fn hit_info(ray: Ray, hit: Hit) -> HitInfo {
 
         info.position = vec4<f32>(ray.origin + ray.direction * hit.intersection.distance, 1.0);
         info.material_index = instance.material;
+
+        if any(ray.cone > vec2<f32>(0.0)) {
+            let p0 = instance_position_local_to_world(instance, v0.position);
+            let p1 = instance_position_local_to_world(instance, v1.position);
+            let p2 = instance_position_local_to_world(instance, v2.position);
+            info.lod = ray_cone_lod(ray, hit.intersection.distance, p0, p1, p2, uv0, uv1, uv2);
+        }
     } else {
         info.position = vec4<f32>(ray.origin + ray.direction * DISTANCE_MAX, 0.0);
     }
@@ -531,6 +618,123 @@ fn occlude_hit_info(ray: Ray, hit: Hit, info: ptr<function, HitInfo>) {
         (*info).normal = vec3<f32>(0.0);
     }
 }
+
+fn intersects_sphere(origin: vec3<f32>, direction: vec3<f32>, center: vec3<f32>, radius: f32) -> f32 {
+    let oc = origin - center;
+    let b = dot(direction, oc);
+    let h = b * b - dot(oc, oc) + radius * radius;
+    if h < 0.0 {
+        return F32_MAX;
+    }
+
+    let t = -b - sqrt(h);
+    return select(F32_MAX, t, t > 0.0);
+}
+
+// The capsule is the union of a cylinder clipped at the caps and the spheres at both ends.
+fn intersects_capsule(origin: vec3<f32>, direction: vec3<f32>, a: vec3<f32>, b: vec3<f32>, radius: f32) -> f32 {
+    var t = min(intersects_sphere(origin, direction, a, radius), intersects_sphere(origin, direction, b, radius));
+
+    let ba = b - a;
+    let oa = origin - a;
+    let baba = dot(ba, ba);
+    let bard = dot(ba, direction);
+    let baoa = dot(ba, oa);
+    let k2 = baba - bard * bard;
+    let k1 = baba * dot(direction, oa) - baoa * bard;
+    let k0 = baba * dot(oa, oa) - baoa * baoa - radius * radius * baba;
+    let h = k1 * k1 - k2 * k0;
+    if h >= 0.0 && k2 > 0.0 {
+        let t_cylinder = (-k1 - sqrt(h)) / k2;
+        let y = baoa + t_cylinder * bard;
+        if y > 0.0 && y < baba && t_cylinder > 0.0 {
+            t = min(t, t_cylinder);
+        }
+    }
+    return t;
+}
+
+// Distance to the emitting surface of an area light along the ray, `F32_MAX` if missed.
+// Rectangles and disks are only hit from the front.
+fn intersects_area_light(origin: vec3<f32>, direction: vec3<f32>, light: AreaLight) -> f32 {
+    if light.kind == AREA_LIGHT_SPHERE {
+        return intersects_sphere(origin, direction, light.position, light.width);
+    }
+    if light.kind == AREA_LIGHT_TUBE {
+        let a = light.position - light.height * light.tangent;
+        let b = light.position + light.height * light.tangent;
+        return intersects_capsule(origin, direction, a, b, light.width);
+    }
+
+    let normal = cross(light.bitangent, light.tangent);
+    let cos_theta = dot(direction, normal);
+    if cos_theta >= 0.0 {
+        return F32_MAX;
+    }
+
+    let t = dot(light.position - origin, normal) / cos_theta;
+    let p = origin + t * direction - light.position;
+    let x = dot(p, light.tangent);
+    let y = dot(p, light.bitangent);
+    var inside = abs(x) <= light.width && abs(y) <= light.height;
+    if light.kind == AREA_LIGHT_DISK {
+        inside = x * x + y * y <= light.width * light.width;
+    }
+    return select(F32_MAX, t, inside && t > 0.0);
+}
+
+fn area_light_normal(light: AreaLight, p: vec3<f32>) -> vec3<f32> {
+    if light.kind == AREA_LIGHT_SPHERE {
+        return normalize(p - light.position);
+    }
+    if light.kind == AREA_LIGHT_TUBE {
+        let x = clamp(dot(p - light.position, light.tangent), -light.height, light.height);
+        return normalize(p - light.position - x * light.tangent);
+    }
+    return cross(light.bitangent, light.tangent);
+}
+
+// Index of the nearest area light visible to the camera ray through the deferred pixel,
+// which is in front of the surface there. Returns `U32_MAX` if there is none.
+// This scans all area lights, so it only runs once per pixel in the albedo pass, see `camera_area_light`.
+fn trace_camera_area_light(deferred_coords: vec2<i32>, position_depth: vec4<f32>) -> u32 {
+    let uv = coords_to_uv(deferred_coords, textureDimensions(position_texture));
+    let ndc = vec2<f32>(2.0 * uv.x - 1.0, 1.0 - 2.0 * uv.y);
+    let near = view.inverse_view_proj * vec4<f32>(ndc, 1.0, 1.0);
+    let origin = near.xyz / near.w;
+
+    var direction: vec3<f32>;
+    if view.projection[3].w == 1.0 {
+        direction = -normalize(view.view[2].xyz);
+    } else {
+        direction = normalize(origin - view.world_position.xyz);
+    }
+
+    var max_distance = F32_MAX;
+    if position_depth.w > F32_EPSILON {
+        max_distance = distance(position_depth.xyz, origin);
+    }
+
+    var light_index = U32_MAX;
+    for (var i = 0u; i < area_light_buffer.count; i += 1u) {
+        let light = area_light_buffer.data[i];
+        if light.visible == 0u {
+            continue;
+        }
+
+        let t = intersects_area_light(origin, direction, light);
+        if t < max_distance {
+            max_distance = t;
+            light_index = i;
+        }
+    }
+    return light_index;
+}
+
+// Index of the area light seen by the camera at the deferred pixel, as resolved by the albedo pass.
+fn camera_area_light(deferred_coords: vec2<i32>) -> u32 {
+    return textureLoad(area_light_texture, deferred_coords).x;
+}
 // -------- TRACING     --------
 
 // -------- SAMPLING    --------
@@ -548,6 +752,40 @@ fn sample_cosine_hemisphere(rand: vec2<f32>) -> vec4<f32> {
     return vec4<f32>(direction, pdf);
 }
 
+// Samples a half vector of the GGX distribution of visible normals in tangent space.
+// https://jcgt.org/published/0007/04/01/
+fn sample_ggx_vndf(rand: vec2<f32>, V: vec3<f32>, alpha: f32) -> vec3<f32> {
+    let vh = normalize(vec3<f32>(alpha * V.x, alpha * V.y, V.z));
+    let lensq = vh.x * vh.x + vh.y * vh.y;
+    var t1 = vec3<f32>(1.0, 0.0, 0.0);
+    if lensq > 0.0 {
+        t1 = vec3<f32>(-vh.y, vh.x, 0.0) / sqrt(lensq);
+    }
+    let t2 = cross(vh, t1);
+
+    let r = sqrt(rand.x);
+    let phi = TAU * rand.y;
+    let p1 = r * cos(phi);
+    let s = 0.5 * (1.0 + vh.z);
+    let p2 = (1.0 - s) * sqrt(1.0 - p1 * p1) + s * r * sin(phi);
+
+    let nh = p1 * t1 + p2 * t2 + sqrt(max(0.0, 1.0 - p1 * p1 - p2 * p2)) * vh;
+    return normalize(vec3<f32>(alpha * nh.x, alpha * nh.y, max(0.0, nh.z)));
+}
+
+// Pdf of reflecting `V` into `L` about a half vector sampled from the GGX distribution of visible normals.
+fn ggx_vndf_pdf(N: vec3<f32>, V: vec3<f32>, L: vec3<f32>, alpha: f32) -> f32 {
+    let H = normalize(L + V);
+    let NoV = max(dot(N, V), 0.0001);
+    let NoH = saturate(dot(N, H));
+
+    let a2 = alpha * alpha;
+    let d = NoH * NoH * (a2 - 1.0) + 1.0;
+    let D = a2 / (PI * d * d);
+    let G1 = 2.0 * NoV / (NoV + sqrt(a2 + (1.0 - a2) * NoV * NoV));
+    return G1 * D / (4.0 * NoV);
+}
+
 // Samples a random direction in a cone with given half apex, also returns pdf
 fn sample_uniform_cone(rand: vec2<f32>, cos_angle: f32) -> vec4<f32> {
     let z = 1.0 - (1.0 - cos_angle) * rand.x;  // [cos(angle), 1.0]
@@ -592,7 +830,245 @@ fn compute_emissive_cone(
 }
 
 fn compute_emissive_radiance(emissive: vec4<f32>) -> vec3<f32> {
+#ifdef LEGACY_LIGHT_UNITS
     return 255.0 * emissive.a * emissive.rgb;
+#else
+    return emissive.rgb;
+#endif
+}
+
+// Uniformly samples the solid angle subtended by a rectangle with corner `s` and edges `ex` and `ey`.
+// Returns the point on the rectangle and the solid angle pdf, which is zero if the rectangle is degenerate.
+// https://dl.acm.org/doi/10.1111/cgf.12151
+fn sample_spherical_rectangle(rand: vec2<f32>, o: vec3<f32>, s: vec3<f32>, ex: vec3<f32>, ey: vec3<f32>) -> vec4<f32> {
+    let exl = length(ex);
+    let eyl = length(ey);
+    let x = ex / exl;
+    let y = ey / eyl;
+    var z = cross(x, y);
+
+    let d = s - o;
+    var z0 = dot(d, z);
+    if z0 > 0.0 {
+        z = -z;
+        z0 = -z0;
+    }
+    let x0 = dot(d, x);
+    let y0 = dot(d, y);
+    let x1 = x0 + exl;
+    let y1 = y0 + eyl;
+
+    let v00 = vec3<f32>(x0, y0, z0);
+    let v01 = vec3<f32>(x0, y1, z0);
+    let v10 = vec3<f32>(x1, y0, z0);
+    let v11 = vec3<f32>(x1, y1, z0);
+    let n0 = normalize(cross(v00, v10));
+    let n1 = normalize(cross(v10, v11));
+    let n2 = normalize(cross(v11, v01));
+    let n3 = normalize(cross(v01, v00));
+
+    let g0 = acos(clamp(-dot(n0, n1), -1.0, 1.0));
+    let g1 = acos(clamp(-dot(n1, n2), -1.0, 1.0));
+    let g2 = acos(clamp(-dot(n2, n3), -1.0, 1.0));
+    let g3 = acos(clamp(-dot(n3, n0), -1.0, 1.0));
+    let b0 = n0.z;
+    let b1 = n2.z;
+    let k = TAU - g2 - g3;
+    let solid_angle = g0 + g1 - k;
+    if !(solid_angle > F32_EPSILON) || z0 > -F32_EPSILON {
+        return vec4<f32>(0.0);
+    }
+
+    let au = rand.x * solid_angle + k;
+    let fu = (cos(au) * b0 - b1) / sin(au);
+    let cu = clamp(select(-1.0, 1.0, fu > 0.0) / sqrt(fu * fu + b0 * b0), -1.0, 1.0);
+    let xu = clamp(-(cu * z0) / max(sqrt(1.0 - cu * cu), F32_EPSILON), x0, x1);
+
+    let dd = sqrt(xu * xu + z0 * z0);
+    let h0 = y0 / sqrt(dd * dd + y0 * y0);
+    let h1 = y1 / sqrt(dd * dd + y1 * y1);
+    let hv = h0 + rand.y * (h1 - h0);
+    let hv2 = hv * hv;
+    let yv = select(y1, hv * dd / sqrt(1.0 - hv2), hv2 < 1.0 - F32_EPSILON);
+
+    return vec4<f32>(o + xu * x + yv * y + z0 * z, 1.0 / solid_angle);
+}
+
+struct AreaLightSample {
+    position: vec3<f32>,
+    normal: vec3<f32>,
+    // Solid angle pdf, zero if the sample misses the light.
+    pdf: f32,
+};
+
+// Samples a point on an area light by the solid angle it subtends from the position.
+fn sample_area_light(rand: vec2<f32>, light: AreaLight, position: vec3<f32>) -> AreaLightSample {
+    var light_sample: AreaLightSample;
+    light_sample.pdf = 0.0;
+
+    let delta = light.position - position;
+    var direction: vec4<f32>;
+
+    if light.kind == AREA_LIGHT_RECT || light.kind == AREA_LIGHT_DISK {
+        // Only the front emits. Disks are sampled in their bounding squares, rejecting the corners.
+        let normal = cross(light.bitangent, light.tangent);
+        if dot(delta, normal) >= 0.0 {
+            return light_sample;
+        }
+
+        let height = select(light.height, light.width, light.kind == AREA_LIGHT_DISK);
+        let corner = light.position - light.width * light.tangent - height * light.bitangent;
+        let rectangle_sample = sample_spherical_rectangle(rand, position, corner, 2.0 * light.width * light.tangent, 2.0 * height * light.bitangent);
+        let offset = rectangle_sample.xyz - light.position;
+        if light.kind == AREA_LIGHT_DISK && dot(offset, offset) > light.width * light.width {
+            return light_sample;
+        }
+
+        light_sample.position = rectangle_sample.xyz;
+        light_sample.normal = normal;
+        light_sample.pdf = rectangle_sample.w;
+        return light_sample;
+    }
+
+    // Tubes seen from the side are bounded by a rectangle through their axis, facing the position.
+    // Elsewhere, tubes and spheres are bounded by the cone of their bounding spheres.
+    let along = -dot(delta, light.tangent);
+    let radial = -delta - along * light.tangent;
+    let radial_distance = length(radial);
+    if light.kind == AREA_LIGHT_TUBE && radial_distance > 1.01 * light.width {
+        let w = radial / radial_distance;
+        let b = cross(light.tangent, w);
+        let near_scale = radial_distance / (radial_distance - light.width);
+        let far_scale = radial_distance / (radial_distance + light.width);
+
+        // Project the bounding box of the tube onto the plane from the position.
+        let extent = light.height + light.width;
+        let x0 = along + min((-extent - along) * near_scale, (-extent - along) * far_scale);
+        let x1 = along + max((extent - along) * near_scale, (extent - along) * far_scale);
+        let y = light.width * near_scale;
+
+        let corner = light.position + x0 * light.tangent - y * b;
+        let rectangle_sample = sample_spherical_rectangle(rand, position, corner, (x1 - x0) * light.tangent, 2.0 * y * b);
+        direction = vec4<f32>(normalize(rectangle_sample.xyz - position), rectangle_sample.w);
+    } else {
+        let radius = select(light.width, light.height + light.width, light.kind == AREA_LIGHT_TUBE);
+        let d2 = dot(delta, delta);
+        if d2 <= radius * radius {
+            return light_sample;
+        }
+
+        // 1 - cos of the cone apex, without cancellation for small lights
+        let sin2 = radius * radius / d2;
+        let cone_height = sin2 / (1.0 + sqrt(1.0 - sin2));
+        let z = 1.0 - cone_height * rand.x;
+        let r = sqrt(max(1.0 - z * z, 0.0));
+        let theta = TAU * rand.y;
+        let local = vec3<f32>(r * cos(theta), r * sin(theta), z);
+        direction = vec4<f32>(normal_basis(delta / sqrt(d2)) * local, INV_TAU / cone_height);
+    }
+
+    let t = intersects_area_light(position, direction.xyz, light);
+    if t == F32_MAX || !(direction.w > 0.0) {
+        return light_sample;
+    }
+
+    light_sample.position = position + t * direction.xyz;
+    light_sample.normal = area_light_normal(light, light_sample.position);
+    light_sample.pdf = direction.w;
+    return light_sample;
+}
+
+struct LightSelection {
+    emissive: u32,
+    // Probability of selecting the emissive, zero if none is selected.
+    p: f32,
+    // The random number rescaled to [0, 1) after the last decision.
+    rand: f32,
+};
+
+// Importance of the emitters below a light tree node to a shading point.
+// https://dl.acm.org/doi/10.1145/3233305
+fn light_node_importance(node: LightNode, position: vec3<f32>, normal: vec3<f32>) -> f32 {
+    let center = 0.5 * (node.min + node.max);
+    let radius = 0.5 * distance(node.max, node.min);
+    let delta = position - center;
+    let d2 = dot(delta, delta);
+
+    // The point is inside the bounding sphere, so no orientation bound applies.
+    if d2 <= radius * radius {
+        return node.power / max(radius * radius, F32_EPSILON);
+    }
+
+    let d = sqrt(d2);
+    let direction = delta / d;
+    let theta_u = asin(radius / d);
+
+    // For two-sided emitters, only the angle to the nearer side of the normal cone counts.
+    var cos_theta = clamp(dot(node.axis, direction), -1.0, 1.0);
+    if node.two_sided != 0u {
+        cos_theta = abs(cos_theta);
+    }
+    let theta = acos(cos_theta);
+    let theta_o = acos(clamp(node.cos_theta_o, -1.0, 1.0));
+    let theta_emitter = max(theta - theta_o - theta_u, 0.0);
+    if theta_emitter >= 0.5 * PI {
+        return 0.0;
+    }
+
+    let theta_i = acos(clamp(-dot(normal, direction), -1.0, 1.0));
+    let theta_receiver = max(theta_i - theta_u, 0.0);
+    if theta_receiver >= 0.5 * PI {
+        return 0.0;
+    }
+
+    return node.power * cos(theta_emitter) * cos(theta_receiver) / d2;
+}
+
+// Stochastically traverse the light tree, choosing children in proportion to their importance.
+fn select_light_tree(rand: f32, position: vec3<f32>, normal: vec3<f32>) -> LightSelection {
+    var selection: LightSelection;
+    selection.emissive = U32_MAX;
+    selection.p = 0.0;
+    selection.rand = rand;
+
+    if light_node_buffer.count == 0u {
+        return selection;
+    }
+
+    var p = 1.0;
+    var index = 0u;
+    var node = light_node_buffer.data[0];
+    for (; node.child < BVH_LEAF_FLAG;) {
+        let left_index = index + 1u;
+        let right_index = node.child;
+        let left = light_node_buffer.data[left_index];
+        let right = light_node_buffer.data[right_index];
+
+        let left_importance = light_node_importance(left, position, normal);
+        let right_importance = light_node_importance(right, position, normal);
+        let total_importance = left_importance + right_importance;
+        if total_importance <= 0.0 {
+            return selection;
+        }
+
+        let p_left = left_importance / total_importance;
+        if selection.rand < p_left {
+            index = left_index;
+            node = left;
+            p *= p_left;
+            selection.rand = selection.rand / p_left;
+        } else {
+            index = right_index;
+            node = right;
+            p *= 1.0 - p_left;
+            selection.rand = (selection.rand - p_left) / (1.0 - p_left);
+        }
+        selection.rand = min(selection.rand, 0.99999994);
+    }
+
+    selection.emissive = node.child - BVH_LEAF_FLAG;
+    selection.p = p;
+    return selection;
 }
 
 // Choose a light source based on luminance
@@ -620,46 +1096,41 @@ fn select_light_candidate(
         return candidate;
     }
 
-    // Traverse the LBVH to pick one emissive within range
-    var emissive: Emissive;
-    var count = 0.0;
-    var index = 0u;
-    var rand_1d = rand.x;
-    for (; index < emissive_node_buffer.count;) {
-        let node = emissive_node_buffer.data[index];
-        var aabb: Aabb;
-
-        if node.entry_index >= BVH_LEAF_FLAG {
-            let emissive_index = node.entry_index - BVH_LEAF_FLAG;
-            let current_emissive = emissive_buffer[emissive_index];
-            aabb.min = current_emissive.position - current_emissive.radius;
-            aabb.max = current_emissive.position + current_emissive.radius;
-
-            if instance != current_emissive.instance && inside_aabb(position, aabb) {
-                rand_1d = fract(rand_1d + GOLDEN_RATIO);
-                count += 1.0;
-                if rand_1d < 1.0 / count {
-                    candidate.emissive_instance = current_emissive.instance;
-                    emissive = current_emissive;
-                }
-            }
+    // Traverse the light tree to pick one emissive by importance
+    let selection = select_light_tree(rand.x, position, normal);
+
+    if selection.p > 0.0 && (selection.emissive & AREA_LIGHT_FLAG) != 0u {
+        // Area lights are sampled analytically, and traced against the scene only for occlusion.
+        let light_index = selection.emissive - AREA_LIGHT_FLAG;
+        let light_sample = sample_area_light(rand.zw, area_light_buffer.data[light_index], position);
+        let delta = light_sample.position - position;
+        if light_sample.pdf > 0.0 && dot(delta, normal) > 0.0 {
+            let distance = length(delta);
+            candidate.direction = delta / distance;
+            candidate.emissive_instance = AREA_LIGHT_FLAG | light_index;
+            candidate.max_distance = distance - RAY_BIAS;
+            candidate.min_distance = candidate.max_distance;
+            candidate.p = light_sample.pdf * selection.p;
+
+            (*info).instance_index = candidate.emissive_instance;
+            (*info).position = vec4<f32>(light_sample.position, 1.0);
+            (*info).normal = light_sample.normal;
+        }
+        return candidate;
+    }
 
-            index = node.exit_index;
-        } else {
-            aabb.min = node.min;
-            aabb.max = node.max;
-            index = select(
-                node.exit_index,
-                node.entry_index,
-                inside_aabb(position, aabb)
-            );
+    var emissive: Emissive;
+    if selection.p > 0.0 {
+        emissive = emissive_buffer[selection.emissive];
+        if instance != emissive.instance {
+            candidate.emissive_instance = emissive.instance;
         }
     }
 
     if candidate.emissive_instance != DONT_SAMPLE_EMISSIVE {
         // Sample a point on the instance's surface
         // Select a primitive based using the alias table
-        let alias_index = min(u32(rand.x * f32(emissive.alias_table.y)), emissive.alias_table.y - 1u);
+        let alias_index = min(u32(selection.rand * f32(emissive.alias_table.y)), emissive.alias_table.y - 1u);
         let alias_entry = alias_table_buffer[emissive.alias_table.x + alias_index];
         let primitive_index = select(alias_index, alias_entry.index, rand.y < alias_entry.prob);
 
@@ -692,8 +1163,10 @@ fn select_light_candidate(
             candidate.min_distance = hit.intersection.distance - 0.1;
             let delta = (*info).position.xyz - position;
 
-            candidate.p = dot(delta, delta) / (abs(dot(ray.direction, (*info).normal) * emissive.surface_area));
-            candidate.p = candidate.p / count;
+            // The area pdf of the hit primitive, which weights its area by its emissive luminance.
+            let pdf = alias_table_buffer[emissive.alias_table.x + hit.primitive_index - emissive_instance.mesh.primitive].pdf;
+            candidate.p = dot(delta, delta) * pdf / abs(dot(ray.direction, (*info).normal));
+            candidate.p = candidate.p * selection.p;
         } else {
             // Fallback to sample directional        
             *info = empty_hit_info(ray.origin, ray.direction);
@@ -726,8 +1199,19 @@ fn calculate_view(
     return V;
 }
 
+// Ray cone of a ray leaving a primary surface: the pixel footprint at the position, and the pixel spread angle.
+fn primary_ray_cone(world_position: vec3<f32>, is_orthographic: bool) -> vec2<f32> {
+    let pixel_size = 2.0 / (view.projection[1][1] * view.viewport.w);
+    if is_orthographic {
+        return vec2<f32>(pixel_size, 0.0);
+    }
+
+    let spread = atan(pixel_size);
+    return vec2<f32>(spread * distance(world_position, view.world_position.xyz), spread);
+}
+
 #ifdef NO_TEXTURE
-fn retreive_surface(material_index: u32, uv: vec2<f32>) -> Surface {
+fn retreive_surface(material_index: u32, uv: vec2<f32>, lod: f32) -> Surface {
     var surface: Surface;
     let material = material_buffer[material_index];
 
@@ -741,37 +1225,67 @@ fn retreive_surface(material_index: u32, uv: vec2<f32>) -> Surface {
     return surface;
 }
 
-fn retreive_emissive(material_index: u32, uv: vec2<f32>) -> vec4<f32> {
+fn retreive_emissive(material_index: u32, uv: vec2<f32>, lod: f32) -> vec4<f32> {
     var emissive = material_buffer[material_index].emissive;
     return emissive;
 }
 #else
-fn retreive_surface(material_index: u32, uv: vec2<f32>) -> Surface {
+#ifdef TEXTURE_ATLAS
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let lower = color / 12.92;
+    let higher = pow((color + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(higher, lower, color <= vec3<f32>(0.04045));
+}
+
+// The atlas has no mip chain, so the LOD is ignored.
+fn sample_texture(id: u32, rect: vec4<f32>, srgb: bool, uv: vec2<f32>, lod: f32) -> vec4<f32> {
+    // Wrap the uv and keep the footprint inside the texture's rect.
+    let half_texel = 0.5 / vec2<f32>(textureDimensions(textures));
+    let atlas_uv = rect.xy + clamp(fract(uv) * rect.zw, half_texel, rect.zw - half_texel);
+    var color = textureSampleLevel(textures, samplers, atlas_uv, 0.0);
+    if srgb {
+        color = vec4<f32>(srgb_to_linear(color.rgb), color.a);
+    }
+    return color;
+}
+#else
+fn sample_texture(id: u32, rect: vec4<f32>, srgb: bool, uv: vec2<f32>, lod: f32) -> vec4<f32> {
+    let size = vec2<f32>(textureDimensions(textures[id]));
+    let level = max(lod + 0.5 * log2(size.x * size.y), 0.0);
+    return textureSampleLevel(textures[id], samplers[id], uv, level);
+}
+#endif
+
+fn is_srgb_texture(material: Material, slot: u32) -> bool {
+    return (material.srgb_texture_mask & (1u << slot)) != 0u;
+}
+
+fn retreive_surface(material_index: u32, uv: vec2<f32>, lod: f32) -> Surface {
     var surface: Surface;
     let material = material_buffer[material_index];
 
     surface.base_color = material.base_color;
     var id = material.base_color_texture;
     if id != U32_MAX {
-        surface.base_color *= textureSampleLevel(textures[id], samplers[id], uv, 0.0);
+        surface.base_color *= sample_texture(id, material.base_color_texture_rect, is_srgb_texture(material, 0u), uv, lod);
     }
 
     surface.emissive = material.emissive;
     id = material.emissive_texture;
     if id != U32_MAX {
-        surface.emissive *= textureSampleLevel(textures[id], samplers[id], uv, 0.0);
+        surface.emissive *= sample_texture(id, material.emissive_texture_rect, is_srgb_texture(material, 1u), uv, lod);
     }
 
     surface.metallic = material.metallic;
     id = material.metallic_roughness_texture;
     if id != U32_MAX {
-        surface.metallic *= textureSampleLevel(textures[id], samplers[id], uv, 0.0).r;
+        surface.metallic *= sample_texture(id, material.metallic_roughness_texture_rect, is_srgb_texture(material, 2u), uv, lod).r;
     }
 
     surface.occlusion = 1.0;
     id = material.occlusion_texture;
     if id != U32_MAX {
-        surface.occlusion = textureSampleLevel(textures[id], samplers[id], uv, 0.0).r;
+        surface.occlusion = sample_texture(id, material.occlusion_texture_rect, is_srgb_texture(material, 4u), uv, lod).r;
     }
 
     surface.roughness = perceptualRoughnessToRoughness(material.perceptual_roughness);
@@ -780,13 +1294,13 @@ fn retreive_surface(material_index: u32, uv: vec2<f32>) -> Surface {
     return surface;
 }
 
-fn retreive_emissive(material_index: u32, uv: vec2<f32>) -> vec4<f32> {
+fn retreive_emissive(material_index: u32, uv: vec2<f32>, lod: f32) -> vec4<f32> {
     let material = material_buffer[material_index];
 
     var emissive = material.emissive;
     let id = material.emissive_texture;
     if id != U32_MAX {
-        emissive *= textureSampleLevel(textures[id], samplers[id], uv, 0.0);
+        emissive *= sample_texture(id, material.emissive_texture_rect, is_srgb_texture(material, 1u), uv, lod);
     }
 
     return emissive;
@@ -857,8 +1371,10 @@ fn input_radiance(
         }
     } else {
         // Input radiance is emissive, but bounced radiance is not added here
-        if sample_emissive == info.instance_index {
-            let emissive = retreive_emissive(info.material_index, info.uv);
+        if sample_emissive == info.instance_index && (sample_emissive & AREA_LIGHT_FLAG) != 0u {
+            radiance = area_light_buffer.data[sample_emissive - AREA_LIGHT_FLAG].radiance;
+        } else if sample_emissive == info.instance_index {
+            let emissive = retreive_emissive(info.material_index, info.uv, info.lod);
             radiance = compute_emissive_radiance(emissive);
         }
     }
@@ -887,6 +1403,33 @@ fn shading(
     return mix(lit_radiance, ambient_radiance, 1.0 - input_radiance.a);
 }
 
+// Specular part of `shading`, which is denoised separately from the diffuse part.
+fn specular_shading(
+    V: vec3<f32>,
+    N: vec3<f32>,
+    L: vec3<f32>,
+    surface: Surface,
+    input_radiance: vec4<f32>,
+) -> vec3<f32> {
+    let base_color = surface.base_color.rgb;
+    let reflectance = surface.reflectance;
+    let roughness = surface.roughness;
+    let metallic = surface.metallic;
+    let occlusion = surface.occlusion;
+
+    let F0 = 0.16 * reflectance * reflectance * (1.0 - metallic) + base_color * metallic;
+
+    let H = normalize(L + V);
+    let NoL = saturate(dot(N, L));
+    let NoH = saturate(dot(N, H));
+    let LoH = saturate(dot(L, H));
+    let NdotV = max(dot(N, V), 0.0001);
+
+    let lit_radiance = specular(F0, roughness, H, NdotV, NoL, NoH, LoH, 1.0) * input_radiance.rgb * NoL;
+    let ambient_radiance = occlusion * EnvBRDFApprox(F0, roughness, NdotV) * lights.ambient_color.rgb;
+    return mix(lit_radiance, ambient_radiance, 1.0 - input_radiance.a);
+}
+
 fn env_brdf(
     V: vec3<f32>,
     N: vec3<f32>,
@@ -906,6 +1449,61 @@ fn env_brdf(
     let specular_ambient = EnvBRDFApprox(F0, roughness, NdotV);
     return occlusion * (diffuse_ambient + specular_ambient);
 }
+
+// Specular part of `env_brdf`, which demodulates the specular signal.
+fn specular_env_brdf(
+    V: vec3<f32>,
+    N: vec3<f32>,
+    surface: Surface,
+) -> vec3<f32> {
+    let base_color = surface.base_color.rgb;
+    let reflectance = surface.reflectance;
+    let metallic = surface.metallic;
+
+    let NdotV = max(dot(N, V), 0.0001);
+    let F0 = 0.16 * reflectance * reflectance * (1.0 - metallic) + base_color * metallic;
+    return surface.occlusion * EnvBRDFApprox(F0, surface.roughness, NdotV);
+}
+
+// Probability of sampling the specular lobe, from its share of the environment BRDF.
+fn specular_probability(V: vec3<f32>, N: vec3<f32>, surface: Surface) -> f32 {
+    let total = luminance(env_brdf(V, N, surface));
+    let specular_albedo = luminance(specular_env_brdf(V, N, surface));
+    let p = select(0.0, specular_albedo / total, total > 0.0);
+    return clamp(p, MIN_SPECULAR_PROBABILITY, MAX_SPECULAR_PROBABILITY);
+}
+
+// Pdf of sampling `L` from the mixture of the diffuse and specular lobes.
+// This is the one-sample MIS estimator with the balance heuristic.
+fn brdf_pdf(V: vec3<f32>, N: vec3<f32>, L: vec3<f32>, surface: Surface, p_specular: f32) -> f32 {
+    let NoL = dot(N, L);
+    if NoL <= 0.0 {
+        return 0.0;
+    }
+    let diffuse_pdf = 2.0 * INV_TAU * NoL;
+    let specular_pdf = ggx_vndf_pdf(N, V, L, surface.roughness);
+    return mix(diffuse_pdf, specular_pdf, p_specular);
+}
+
+// Samples a world space direction from the cosine hemisphere or the GGX distribution of visible normals,
+// with the pdf of the mixture of both lobes. The pdf is zero if the direction is below the surface.
+fn sample_brdf(rand: vec2<f32>, V: vec3<f32>, N: vec3<f32>, surface: Surface) -> vec4<f32> {
+    let basis = normal_basis(N);
+    let p_specular = specular_probability(V, N, surface);
+
+    // The first random number both selects the lobe and is reused, rescaled, to sample it.
+    var direction: vec3<f32>;
+    if rand.x < p_specular {
+        let lobe_rand = vec2<f32>(rand.x / p_specular, rand.y);
+        let H = basis * sample_ggx_vndf(lobe_rand, transpose(basis) * V, surface.roughness);
+        direction = reflect(-V, H);
+    } else {
+        let lobe_rand = vec2<f32>((rand.x - p_specular) / (1.0 - p_specular), rand.y);
+        direction = basis * sample_cosine_hemisphere(lobe_rand).xyz;
+    }
+
+    return vec4<f32>(direction, brdf_pdf(V, N, direction, surface, p_specular));
+}
 // -------- SHADING     --------
 
 // -------- RESTIR      --------
@@ -951,6 +1549,83 @@ fn temporal_restir(
     }
 }
 
+// Whether the sample is above the surface and not occluded from the position.
+// Samples off any surface, i.e., directional or ambient, are tested along their direction.
+fn is_sample_visible(position: vec3<f32>, normal: vec3<f32>, sample_position: vec4<f32>) -> bool {
+    if dot(sample_position.xyz - position, normal) <= 0.0 {
+        return false;
+    }
+
+    var ray: Ray;
+    ray.origin = position + normal * RAY_BIAS;
+    let offset = sample_position.xyz - ray.origin;
+    let sample_distance = length(offset);
+    ray.direction = offset / sample_distance;
+    ray.inv_direction = 1.0 / ray.direction;
+
+    var max_distance = F32_MAX;
+    if sample_position.w > 0.5 {
+        max_distance = sample_distance - RAY_BIAS;
+    }
+    if max_distance <= 0.0 {
+        return true;
+    }
+
+    let hit = traverse_top(ray, max_distance, max_distance, DONT_EXCLUDE);
+    return hit.instance_index == U32_MAX;
+}
+
+// Unbiased temporal reuse: the previous reservoir is merged after the new sample,
+// with its sample weighted by `p_hat`, the target function of this pixel including visibility.
+// Returns the fraction of the sample count whose domains contain the selected sample:
+// the previous reservoir doesn't if the new sample is occluded from its surface.
+fn unbiased_temporal_restir(
+    r: ptr<function, Reservoir>,
+    s: Sample,
+    w_new: f32,
+    p_hat: f32,
+    max_sample_count: u32
+) -> f32 {
+    let previous = *r;
+    set_reservoir(r, s, w_new);
+    (*r).lifetime = previous.lifetime;
+
+    var support = 1.0;
+    if previous.count > 0.0 {
+        merge_reservoir(r, previous, p_hat);
+        let count = (*r).count;
+        if any((*r).s.sample_position != previous.s.sample_position)
+            && !is_sample_visible(previous.s.visible_position.xyz, previous.s.visible_normal, (*r).s.sample_position) {
+            support = (count - previous.count) / count;
+        }
+    }
+
+    // Clamp...
+    let m = f32(max_sample_count);
+    if (*r).count > m {
+        (*r).w_sum *= m / (*r).count;
+        (*r).w2_sum *= m / (*r).count;
+        (*r).count = m;
+    }
+    return support;
+}
+
+// Jacobian of the reconnection shift of a neighbor's sample to the visible position,
+// i.e., the ratio of the solid angles the sample subtends from the visible position and from the neighbor.
+fn reconnection_jacobian(neighbor_sample: Sample, visible_position: vec3<f32>) -> f32 {
+    let offset_q = neighbor_sample.visible_position.xyz - neighbor_sample.sample_position.xyz;
+    let offset_r = visible_position - neighbor_sample.sample_position.xyz;
+    let distance_q = dot(offset_q, offset_q);
+    let distance_r = dot(offset_r, offset_r);
+    if distance_q <= 0.0 || distance_r <= 0.0 {
+        return 0.0;
+    }
+
+    let cos_q = abs(dot(offset_q, neighbor_sample.sample_normal)) / sqrt(distance_q);
+    let cos_r = abs(dot(offset_r, neighbor_sample.sample_normal)) / sqrt(distance_r);
+    return select(cos_r * distance_q / (cos_q * distance_r), 0.0, cos_q < F32_EPSILON);
+}
+
 fn compute_inv_jacobian(current_sample: Sample, neighbor_sample: Sample) -> f32 {
     var offset_b: vec3<f32> = neighbor_sample.sample_position.xyz - neighbor_sample.visible_position.xyz;
     var offset_a: vec3<f32> = neighbor_sample.sample_position.xyz - current_sample.visible_position.xyz;
@@ -1006,6 +1681,9 @@ fn compute_jacobian(q: Sample, r: Sample) -> f32 {
 
 fn jittered_deferred_uv(uv: vec2<f32>) -> vec2<f32> {
     let texel_size = 1.0 / vec2<f32>(textureDimensions(position_texture));
+    if frame.temporal_upscale != 0u {
+        return uv + frame.upscale_jitter * texel_size;
+    }
     let ratio = frame.upscale_ratio - 1.0;
     return uv + select(0.25, -0.25, (frame.number & 1u) == 0u) * texel_size * ratio;
 }
@@ -1016,6 +1694,18 @@ fn jittered_deferred_coords(uv: vec2<f32>) -> vec2<i32> {
     return vec2<i32>(deferred_uv * size);
 }
 
+// Stores the specular part of the radiance of the reservoir sample.
+fn store_specular(coords: vec2<i32>, view_direction: vec3<f32>, surface: Surface, r: Reservoir) {
+    let specular_radiance = specular_shading(
+        view_direction,
+        r.s.visible_normal,
+        normalize(r.s.sample_position.xyz - r.s.visible_position.xyz),
+        surface,
+        r.s.radiance
+    );
+    textureStore(specular_texture, coords, vec4<f32>(r.w * specular_radiance, 1.0));
+}
+
 @compute @workgroup_size(8, 8, 1)
 fn full_screen_albedo(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
     let render_size = textureDimensions(albedo_texture);
@@ -1027,8 +1717,18 @@ fn full_screen_albedo(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
     let position = vec4<f32>(position_depth.xyz, 1.0);
     let depth = position_depth.w;
 
+    // Area lights seen by the camera are not modulated by any surface, and are reactive as emissive surfaces are.
+    let area_light = trace_camera_area_light(coords, position_depth);
+    textureStore(area_light_texture, coords, vec4<u32>(area_light));
+    if area_light != U32_MAX {
+        textureStore(albedo_texture, coords, vec4<f32>(1.0));
+        textureStore(specular_albedo_texture, coords, vec4<f32>(0.0));
+        return;
+    }
+
     if depth < F32_EPSILON {
         textureStore(albedo_texture, coords, vec4<f32>(0.0));
+        textureStore(specular_albedo_texture, coords, vec4<f32>(0.0));
         return;
     }
 
@@ -1036,9 +1736,14 @@ fn full_screen_albedo(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
     let instance_material = vec2<u32>(textureLoad(instance_material_texture, coords, 0).xy);
     let velocity_uv = textureLoad(velocity_uv_texture, coords, 0);
 
-    let surface = retreive_surface(instance_material.y, velocity_uv.zw);
+    let surface = retreive_surface(instance_material.y, velocity_uv.zw, PRIMARY_LOD);
     let view_direction = calculate_view(position, view.projection[3].w == 1.0);
-    textureStore(albedo_texture, coords, vec4<f32>(env_brdf(view_direction, normal, surface), 1.0));
+
+    // Emissive surfaces are marked reactive, so temporal upscaling doesn't keep their stale history.
+    let emissive = compute_emissive_radiance(surface.emissive);
+    let reactive = clamp(max(emissive.r, max(emissive.g, emissive.b)), 0.0, 1.0);
+    textureStore(albedo_texture, coords, vec4<f32>(env_brdf(view_direction, normal, surface), reactive));
+    textureStore(specular_albedo_texture, coords, vec4<f32>(specular_env_brdf(view_direction, normal, surface), surface.roughness));
 }
 
 @compute @workgroup_size(8, 8, 1)
@@ -1055,15 +1760,24 @@ fn direct_lit(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
     let position = vec4<f32>(position_depth.xyz, 1.0);
     let depth = position_depth.w;
 
-    if depth < F32_EPSILON {
+    let area_light = camera_area_light(deferred_coords);
+    if depth < F32_EPSILON || area_light != U32_MAX {
         var r: Reservoir;
         set_reservoir(&r, s, 0.0);
         store_reservoir(coords.x + render_size.x * coords.y, r);
         store_spatial_reservoir(coords.x + render_size.x * coords.y, r);
         store_previous_spatial_reservoir(coords.x + render_size.x * coords.y, r);
 
+        var out_color = vec4<f32>(0.0);
+#ifdef RENDER_EMISSIVE
+        if area_light != U32_MAX {
+            out_color = vec4<f32>(area_light_buffer.data[area_light].radiance, 1.0);
+        }
+#endif
+
         textureStore(variance_texture, coords, vec4<f32>(0.0));
-        textureStore(render_texture, coords, vec4<f32>(0.0));
+        textureStore(render_texture, coords, accumulate_radiance(coords, out_color));
+        textureStore(specular_texture, coords, vec4<f32>(0.0));
 
         return;
     }
@@ -1073,9 +1787,9 @@ fn direct_lit(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
     let velocity_uv = textureLoad(velocity_uv_texture, deferred_coords, 0);
 
     let noise_id = frame.number % NOISE_TEXTURE_COUNT;
-    let noise_size = textureDimensions(noise_texture[noise_id]);
+    let noise_size = textureDimensions(noise_texture);
     let noise_uv = (vec2<f32>(coords) + f32(frame.number) + 0.5) / vec2<f32>(noise_size);
-    s.random = textureSampleLevel(noise_texture[noise_id], noise_sampler, noise_uv, 0.0);
+    s.random = textureSampleLevel(noise_texture, noise_sampler, noise_uv, i32(noise_id), 0.0);
     s.random = fract(s.random + f32(frame.number) * GOLDEN_RATIO);
 
     s.visible_position = vec4<f32>(position.xyz, depth);
@@ -1086,26 +1800,37 @@ fn direct_lit(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
     var hit: Hit;
     var info: HitInfo;
 
+    // Accumulation starts every frame with an empty reservoir, so that samples are independent.
     let previous_uv = jittered_deferred_uv(uv) - velocity_uv.xy;
-    var r: Reservoir = load_previous_reservoir(previous_uv, render_size);
+    // Previous reservoirs are laid out for the previous render size, so they are dropped when the history is reset.
+    var r: Reservoir;
+    var support = 1.0;
+    if frame.accumulate == 0u && frame.history_reset == 0u {
+        r = load_previous_reservoir(previous_uv, render_size);
 
-    if !check_previous_reservoir(&r, s) && all(abs(previous_uv - 0.5) <= vec2<f32>(0.5)) {
-        let previous_coords = vec2<i32>(previous_uv * vec2<f32>(render_size));
-        store_previous_spatial_reservoir(previous_coords.x + render_size.x * previous_coords.y, r);
+        if !check_previous_reservoir(&r, s) && all(abs(previous_uv - 0.5) <= vec2<f32>(0.5)) {
+            let previous_coords = vec2<i32>(previous_uv * vec2<f32>(render_size));
+            store_previous_spatial_reservoir(previous_coords.x + render_size.x * previous_coords.y, r);
+        }
     }
 
 #ifdef EMISSIVE_LIT
     let validate_interval = frame.emissive_validate_interval;
     let select_light_instance = instance_material.x;
     let sample_directional = false;
+    let unbiased = frame.emissive_unbiased_reuse != 0u;
 #else
     let validate_interval = frame.direct_validate_interval;
     let select_light_instance = DONT_SAMPLE_EMISSIVE;
     let sample_directional = true;
+    let unbiased = frame.direct_unbiased_reuse != 0u;
 #endif
 
+    // Unbiased reuse traces the previous sample every frame, so it needs no validation.
+    let validation_frame = frame.accumulate == 0u && frame.number % validate_interval == 0u && !unbiased;
+
     // Non-validation frame, or sample count too low
-    if frame.number % validate_interval != 0u || r.count < f32(DIRECT_VALIDATION_FRAME_SAMPLE_THRESHOLD) {
+    if !validation_frame || r.count < f32(DIRECT_VALIDATION_FRAME_SAMPLE_THRESHOLD) {
         let candidate = select_light_candidate(
             s.random,
             s.visible_position.xyz,
@@ -1149,11 +1874,19 @@ fn direct_lit(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
         //     s.radiance
         // );
         let w_new = select(0.0, luminance(s.radiance.rgb) / candidate.p, candidate.p > 0.0);
-        temporal_restir(&r, s, w_new, frame.max_temporal_reuse_count);
+        if unbiased {
+            var p_hat = luminance(r.s.radiance.rgb);
+            if r.count > 0.0 && !is_sample_visible(s.visible_position.xyz, s.visible_normal, r.s.sample_position) {
+                p_hat = 0.0;
+            }
+            support = unbiased_temporal_restir(&r, s, w_new, p_hat, frame.max_temporal_reuse_count);
+        } else {
+            temporal_restir(&r, s, w_new, frame.max_temporal_reuse_count);
+        }
     }
 
     // Validation frame
-    if frame.number % validate_interval == 0u {
+    if validation_frame {
         let candidate = select_light_candidate(
             r.s.random,
             r.s.visible_position.xyz,
@@ -1213,7 +1946,7 @@ fn direct_lit(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
         }
     }
 
-    let total_lum = r.count * luminance(r.s.radiance.rgb);
+    let total_lum = r.count * support * luminance(r.s.radiance.rgb);
     r.w = select(0.0, r.w_sum / total_lum, total_lum > 0.0);
 
     r.s.visible_position = s.visible_position;
@@ -1230,7 +1963,7 @@ fn direct_lit(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
         store_reservoir(coords.x + render_size.x * coords.y, r);
     }
 
-    let surface = retreive_surface(instance_material.y, velocity_uv.zw);
+    let surface = retreive_surface(instance_material.y, velocity_uv.zw, PRIMARY_LOD);
     let view_direction = calculate_view(position, view.projection[3].w == 1.0);
 
     // if frame.enable_spatial_reuse == 0u {
@@ -1244,7 +1977,8 @@ fn direct_lit(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
     );
     out_radiance *= r.w;
     let out_color = out_radiance + compute_emissive_radiance(surface.emissive);
-    textureStore(render_texture, coords, vec4<f32>(out_color, 1.0));
+    textureStore(render_texture, coords, accumulate_radiance(coords, vec4<f32>(out_color, 1.0)));
+    store_specular(coords, view_direction, surface, r);
 #else
     var out_radiance = shading(
         view_direction,
@@ -1255,7 +1989,8 @@ fn direct_lit(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
     );
     out_radiance *= r.w;
     let out_color = out_radiance;
-    textureStore(render_texture, coords, vec4<f32>(out_color, 1.0));
+    textureStore(render_texture, coords, accumulate_radiance(coords, vec4<f32>(out_color, 1.0)));
+    store_specular(coords, view_direction, surface, r);
 #endif
     // }
 }
@@ -1276,13 +2011,14 @@ fn indirect_lit_ambient(@builtin(global_invocation_id) invocation_id: vec3<u32>)
     var s: Sample;
     var r: Reservoir;
 
-    if frame.indirect_bounces == 0u || depth < F32_EPSILON {
+    if frame.indirect_bounces == 0u || depth < F32_EPSILON || camera_area_light(deferred_coords) != U32_MAX {
         store_reservoir(coords.x + render_size.x * coords.y, r);
         store_spatial_reservoir(coords.x + render_size.x * coords.y, r);
         store_previous_spatial_reservoir(coords.x + render_size.x * coords.y, r);
 
         textureStore(variance_texture, coords, vec4<f32>(0.0));
-        textureStore(render_texture, coords, vec4<f32>(0.0));
+        textureStore(render_texture, coords, accumulate_radiance(coords, vec4<f32>(0.0)));
+        textureStore(specular_texture, coords, vec4<f32>(0.0));
         return;
     }
 
@@ -1291,9 +2027,9 @@ fn indirect_lit_ambient(@builtin(global_invocation_id) invocation_id: vec3<u32>)
     let velocity_uv = textureLoad(velocity_uv_texture, deferred_coords, 0);
 
     let noise_id = frame.number % NOISE_TEXTURE_COUNT;
-    let noise_size = textureDimensions(noise_texture[noise_id]);
+    let noise_size = textureDimensions(noise_texture);
     let noise_uv = (vec2<f32>(coords) + f32(frame.number) + 0.5) / vec2<f32>(noise_size);
-    s.random = textureSampleLevel(noise_texture[noise_id], noise_sampler, noise_uv, 0.0);
+    s.random = textureSampleLevel(noise_texture, noise_sampler, noise_uv, i32(noise_id), 0.0);
     s.random = fract(s.random + f32(frame.number) * GOLDEN_RATIO);
 
     s.visible_position = vec4<f32>(position.xyz, depth);
@@ -1304,20 +2040,33 @@ fn indirect_lit_ambient(@builtin(global_invocation_id) invocation_id: vec3<u32>)
     var hit: Hit;
     var info: HitInfo;
     var pdf: f32;
+
+    let view_direction = calculate_view(position, view.projection[3].w == 1.0);
+    let visible_surface = retreive_surface(instance_material.y, velocity_uv.zw, PRIMARY_LOD);
     var surface: Surface;
 
 #ifdef MULTIPLE_BOUNCES
     var bounce_sample = s;
     var color_transport = vec3<f32>(1.0);
+    var cone = primary_ray_cone(s.visible_position.xyz, view.projection[3].w == 1.0);
 
     for (var n = 0u; n < frame.indirect_bounces && any(color_transport > vec3<f32>(0.01)); n += 1u) {
+        // The first bounce samples the BRDF of the visible surface, and the rest are treated as diffuse.
+        // Samples below the surface have a zero pdf, so they are rejected by the reservoir.
         var rand_sample = sample_cosine_hemisphere(bounce_sample.random.xy);
+        if n == 0u {
+            rand_sample = sample_brdf(s.random.xy, view_direction, s.visible_normal, visible_surface);
+            ray.direction = rand_sample.xyz;
+        } else {
+            ray.direction = normal_basis(bounce_sample.visible_normal) * rand_sample.xyz;
+        }
         ray.origin = bounce_sample.visible_position.xyz + bounce_sample.visible_normal * RAY_BIAS;
-        ray.direction = normal_basis(bounce_sample.visible_normal) * rand_sample.xyz;
         ray.inv_direction = 1.0 / ray.direction;
+        ray.cone = cone;
 
         hit = traverse_top(ray, F32_MAX, 0.0, DONT_EXCLUDE);
         info = hit_info(ray, hit);
+        cone.x += cone.y * hit.intersection.distance;
 
         if n == 0u {
             s.sample_position = info.position;
@@ -1332,7 +2081,7 @@ fn indirect_lit_ambient(@builtin(global_invocation_id) invocation_id: vec3<u32>)
         if hit.instance_index != U32_MAX {
             var out_radiance = vec3<f32>(0.0);
 
-            surface = retreive_surface(info.material_index, info.uv);
+            surface = retreive_surface(info.material_index, info.uv, info.lod);
             surface.roughness = 1.0;
 
             let candidate = select_light_candidate(
@@ -1393,14 +2142,16 @@ fn indirect_lit_ambient(@builtin(global_invocation_id) invocation_id: vec3<u32>)
         }
     }
 #else
-    var rand_sample = sample_cosine_hemisphere(s.random.xy);
+    let rand_sample = sample_brdf(s.random.xy, view_direction, s.visible_normal, visible_surface);
     ray.origin = s.visible_position.xyz + s.visible_normal * RAY_BIAS;
-    ray.direction = normal_basis(s.visible_normal) * rand_sample.xyz;
+    ray.direction = rand_sample.xyz;
     ray.inv_direction = 1.0 / ray.direction;
+    ray.cone = primary_ray_cone(s.visible_position.xyz, view.projection[3].w == 1.0);
 
     hit = traverse_top(ray, F32_MAX, 0.0, DONT_EXCLUDE);
     info = hit_info(ray, hit);
 
+    // Samples below the surface have a zero pdf, so they are rejected by the reservoir.
     s.sample_position = info.position;
     s.sample_normal = info.normal;
     pdf = rand_sample.w;
@@ -1408,7 +2159,7 @@ fn indirect_lit_ambient(@builtin(global_invocation_id) invocation_id: vec3<u32>)
     if hit.instance_index != U32_MAX {
         var out_radiance = vec3<f32>(0.0);
 
-        surface = retreive_surface(info.material_index, info.uv);
+        surface = retreive_surface(info.material_index, info.uv, info.lod);
         surface.roughness = 1.0;
 
         let candidate = select_light_candidate(
@@ -1451,15 +2202,16 @@ fn indirect_lit_ambient(@builtin(global_invocation_id) invocation_id: vec3<u32>)
 
     // ReSTIR: Temporal
     let previous_uv = jittered_deferred_uv(uv) - velocity_uv.xy;
-    r = load_previous_reservoir(previous_uv, render_size);
+    if frame.accumulate == 0u && frame.history_reset == 0u {
+        r = load_previous_reservoir(previous_uv, render_size);
 
-    if !check_previous_reservoir(&r, s) && all(abs(previous_uv - 0.5) <= vec2<f32>(0.5)) {
-        let previous_coords = vec2<i32>(previous_uv * vec2<f32>(render_size));
-        store_previous_spatial_reservoir(previous_coords.x + render_size.x * previous_coords.y, r);
+        if !check_previous_reservoir(&r, s) && all(abs(previous_uv - 0.5) <= vec2<f32>(0.5)) {
+            let previous_coords = vec2<i32>(previous_uv * vec2<f32>(render_size));
+            store_previous_spatial_reservoir(previous_coords.x + render_size.x * previous_coords.y, r);
+        }
     }
 
-    surface = retreive_surface(instance_material.y, velocity_uv.zw);
-    let view_direction = calculate_view(position, view.projection[3].w == 1.0);
+    surface = visible_surface;
     let sample_radiance = shading(
         view_direction,
         s.visible_normal,
@@ -1468,7 +2220,23 @@ fn indirect_lit_ambient(@builtin(global_invocation_id) invocation_id: vec3<u32>)
         s.radiance
     );
     let w_new = select(0.0, luminance(sample_radiance) / pdf, pdf > 0.0);
-    temporal_restir(&r, s, w_new, frame.max_temporal_reuse_count);
+    var support = 1.0;
+    if frame.indirect_unbiased_reuse != 0u {
+        var p_hat = 0.0;
+        if r.count > 0.0 && is_sample_visible(s.visible_position.xyz, s.visible_normal, r.s.sample_position) {
+            let previous_radiance = shading(
+                view_direction,
+                s.visible_normal,
+                normalize(r.s.sample_position.xyz - s.visible_position.xyz),
+                surface,
+                r.s.radiance
+            );
+            p_hat = luminance(previous_radiance);
+        }
+        support = unbiased_temporal_restir(&r, s, w_new, p_hat, frame.max_temporal_reuse_count);
+    } else {
+        temporal_restir(&r, s, w_new, frame.max_temporal_reuse_count);
+    }
 
     let out_radiance = shading(
         view_direction,
@@ -1477,7 +2245,7 @@ fn indirect_lit_ambient(@builtin(global_invocation_id) invocation_id: vec3<u32>)
         surface,
         r.s.radiance
     );
-    let total_lum = r.count * luminance(out_radiance);
+    let total_lum = r.count * support * luminance(out_radiance);
     r.w = select(0.0, r.w_sum / total_lum, total_lum > 0.0);
 
     r.s.visible_position = s.visible_position;
@@ -1494,12 +2262,99 @@ fn indirect_lit_ambient(@builtin(global_invocation_id) invocation_id: vec3<u32>)
         store_reservoir(coords.x + render_size.x * coords.y, r);
     }
 
-    textureStore(render_texture, coords, vec4<f32>(out_radiance * r.w, 1.0));
+    textureStore(render_texture, coords, accumulate_radiance(coords, vec4<f32>(out_radiance * r.w, 1.0)));
+    store_specular(coords, view_direction, surface, r);
 }
 
 var<workgroup> shared_reservoir: array<array<Reservoir, 8u>, 8u>;
 var<workgroup> shared_depth: array<array<f32, 8u>, 8u>;
 
+// Loads the reservoir of the `i`-th neighbor of spatial reuse.
+// Returns false if the neighbor is off screen, on another surface, or occluded in screen space,
+// which only depends on the pixels, so that unbiased reuse can find the same neighbors again.
+fn load_spatial_neighbor(
+    i: u32,
+    coords: vec2<i32>,
+    workgroup_id: vec3<u32>,
+    depth: f32,
+    s: Sample,
+    q: ptr<function, Reservoir>,
+) -> bool {
+    let render_size = textureDimensions(render_texture);
+    let uv = coords_to_uv(coords, render_size);
+
+    // Fibonacci spiral: http://extremelearning.com.au/how-to-evenly-distribute-points-on-a-sphere-more-effectively-than-the-canonical-fibonacci-lattice/
+    let polar_offset = vec2<f32>(
+        TAU * fract(f32(i) * GOLDEN_RATIO + dot(s.random, vec4<f32>(1.0)) + random_float(frame.number)),
+        sqrt(f32(i) / f32(SPATIAL_REUSE_COUNT)) * SPATIAL_REUSE_RANGE
+    );
+    let offset = polar_offset.y * vec2<f32>(cos(polar_offset.x), sin(polar_offset.x));
+
+    let sample_coords = vec2<i32>(offset + vec2<f32>(coords));
+    let sample_uv = coords_to_uv(sample_coords, render_size);
+    let sample_deferred_coords = jittered_deferred_coords(sample_uv);
+    if any(sample_uv < vec2<f32>(0.0)) || any(sample_uv > vec2<f32>(1.0)) {
+        return false;
+    }
+
+    var sample_depth: f32;
+
+    // Check if the sample location is in the shared memory
+    if all(sample_coords / 8 == vec2<i32>(workgroup_id.xy)) {
+        let local_sample_coords = sample_coords % 8;
+        sample_depth = shared_depth[local_sample_coords.y][local_sample_coords.x];
+        *q = shared_reservoir[local_sample_coords.y][local_sample_coords.x];
+    } else {
+        sample_depth = textureLoad(position_texture, sample_deferred_coords, 0).w;
+        *q = load_reservoir(sample_coords.x + render_size.x * sample_coords.y);
+    }
+
+    let depth_ratio = depth / sample_depth;
+    if depth_ratio < 0.9 || depth_ratio > 1.1 {
+        return false;
+    }
+
+    let normal_miss = dot(s.visible_normal, (*q).s.visible_normal) < 0.866;
+    if (*q).count < F32_EPSILON || normal_miss {
+        return false;
+    }
+
+    // Perform screen-space ray-marching the depth to reject samples
+    let tap_interval = max(1.0, polar_offset.y / f32(SPATIAL_REUSE_TAPS + 1u));
+    let tap_count = u32(polar_offset.y / tap_interval);
+    for (var j = 1u; j <= tap_count; j += 1u) {
+        let tap_dist = f32(j) * tap_interval;
+        let tap_offset = tap_dist * normalize(offset);
+
+        let tap_uv = uv + tap_offset / vec2<f32>(render_size);
+        let tap_deferred_coords = jittered_deferred_coords(tap_uv);
+        let tap_depth = textureLoad(position_texture, tap_deferred_coords, 0).w;
+
+        let ref_depth = mix(depth, sample_depth, f32(j) / f32(tap_count + 1u));
+        if tap_depth > ref_depth + 0.00001 {
+            return false;
+        }
+    }
+
+    return true;
+}
+
+// Target function of spatial reuse for the sample of `other` at the visible point of `s`.
+fn spatial_target(view_direction: vec3<f32>, s: Sample, surface: Surface, other: Sample) -> f32 {
+#ifdef EMISSIVE_LIT
+    return luminance(other.radiance.rgb);
+#else
+    let out_radiance = shading(
+        view_direction,
+        s.visible_normal,
+        normalize(other.sample_position.xyz - s.visible_position.xyz),
+        surface,
+        other.radiance
+    );
+    return luminance(out_radiance);
+#endif
+}
+
 @compute @workgroup_size(8, 8, 1)
 fn spatial_reuse(
     @builtin(global_invocation_id) invocation_id: vec3<u32>,
@@ -1523,30 +2378,59 @@ fn spatial_reuse(
     shared_reservoir[local_id.y][local_id.x] = r;
     workgroupBarrier();
 
-    if depth < F32_EPSILON {
+    if depth < F32_EPSILON || camera_area_light(deferred_coords) != U32_MAX {
         store_spatial_reservoir(coords.x + render_size.x * coords.y, r);
         textureStore(render_texture, coords, vec4<f32>(0.0));
+        textureStore(specular_texture, coords, vec4<f32>(0.0));
         return;
     }
 
     let instance_material = vec2<u32>(textureLoad(instance_material_texture, deferred_coords, 0).xy);
     let velocity_uv = textureLoad(velocity_uv_texture, deferred_coords, 0);
 
-    let surface = retreive_surface(instance_material.y, velocity_uv.zw);
+    let surface = retreive_surface(instance_material.y, velocity_uv.zw, PRIMARY_LOD);
 
     let use_spatial_variance = r.count <= f32(SPATIAL_VARIANCE_SAMPLE_THRESHOLD);
 
     // ReSTIR: Spatial
     let previous_uv = jittered_deferred_uv(uv) - velocity_uv.xy;
 
+#ifdef EMISSIVE_LIT
+    let unbiased = frame.emissive_unbiased_reuse != 0u;
+#else
+    let unbiased = frame.indirect_unbiased_reuse != 0u;
+#endif
+
     var q = r;
     let s = q.s;
 
-    if r.lifetime <= reservoir_lifetime(r) {
+    let view_direction = calculate_view(position, view.projection[3].w == 1.0);
+
+    // The previous spatial reservoir merged into this one, if any.
+    var previous: Reservoir;
+    if frame.history_reset != 0u {
+        // The previous spatial reservoirs are laid out for the previous render size.
+        var empty: Reservoir;
+        r = empty;
+    } else if r.lifetime <= reservoir_lifetime(r) {
         r = load_previous_spatial_reservoir(previous_uv, render_size);
-    }
 
-    let view_direction = calculate_view(position, view.projection[3].w == 1.0);
+        // The previous spatial reservoir is re-weighted by the target function of this pixel, like a temporal one.
+        if unbiased && r.count > 0.0 {
+            previous = r;
+            var p_hat = 0.0;
+            if is_sample_visible(s.visible_position.xyz, s.visible_normal, previous.s.sample_position) {
+                p_hat = spatial_target(view_direction, s, surface, previous.s);
+            }
+            var empty: Reservoir;
+            r = empty;
+            merge_reservoir(&r, previous, p_hat);
+            r.lifetime = previous.lifetime;
+        }
+    } else if unbiased {
+        var empty: Reservoir;
+        r = empty;
+    }
 #ifdef EMISSIVE_LIT
     merge_reservoir(&r, q, luminance(q.s.radiance.rgb));
 #else
@@ -1564,39 +2448,19 @@ fn spatial_reuse(
     r.s.visible_normal = s.visible_normal;
 
     for (var i = 1u; i <= SPATIAL_REUSE_COUNT; i += 1u) {
-        // Fibonacci spiral: http://extremelearning.com.au/how-to-evenly-distribute-points-on-a-sphere-more-effectively-than-the-canonical-fibonacci-lattice/
-        let polar_offset = vec2<f32>(
-            TAU * fract(f32(i) * GOLDEN_RATIO + dot(s.random, vec4<f32>(1.0)) + random_float(frame.number)),
-            sqrt(f32(i) / f32(SPATIAL_REUSE_COUNT)) * SPATIAL_REUSE_RANGE
-        );
-        let offset = polar_offset.y * vec2<f32>(cos(polar_offset.x), sin(polar_offset.x));
-
-        let sample_coords = vec2<i32>(offset + vec2<f32>(coords));
-        let sample_uv = coords_to_uv(sample_coords, render_size);
-        let sample_deferred_coords = jittered_deferred_coords(sample_uv);
-        if any(sample_uv < vec2<f32>(0.0)) || any(sample_uv > vec2<f32>(1.0)) {
+        if !load_spatial_neighbor(i, coords, workgroup_id, depth, s, &q) {
             continue;
         }
 
-        var sample_depth: f32;
-
-        // Check if the sample location is in the shared memory
-        if all(sample_coords / 8 == vec2<i32>(workgroup_id.xy)) {
-            let local_sample_coords = sample_coords % 8;
-            sample_depth = shared_depth[local_sample_coords.y][local_sample_coords.x];
-            q = shared_reservoir[local_sample_coords.y][local_sample_coords.x];
-        } else {
-            sample_depth = textureLoad(position_texture, sample_deferred_coords, 0).w;
-            q = load_reservoir(sample_coords.x + render_size.x * sample_coords.y);
-        }
-
-        let depth_ratio = depth / sample_depth;
-        if depth_ratio < 0.9 || depth_ratio > 1.1 {
-            continue;
-        }
-
-        let normal_miss = dot(s.visible_normal, q.s.visible_normal) < 0.866;
-        if q.count < F32_EPSILON || normal_miss {
+        // Neighbor samples are traced for visibility and shifted with the exact Jacobian.
+        // Those below the surface are merged with a zero weight, so that their sample counts are kept.
+        if unbiased {
+            var p_hat = 0.0;
+            if is_sample_visible(s.visible_position.xyz, s.visible_normal, q.s.sample_position) {
+                let jacobian = select(1.0, reconnection_jacobian(q.s, s.visible_position.xyz), q.s.sample_position.w > 0.5);
+                p_hat = spatial_target(view_direction, s, surface, q.s) * jacobian;
+            }
+            merge_reservoir(&r, q, p_hat);
             continue;
         }
 
@@ -1605,28 +2469,6 @@ fn spatial_reuse(
             continue;
         }
 
-        // Perform screen-space ray-marching the depth to reject samples
-        let tap_interval = max(1.0, polar_offset.y / f32(SPATIAL_REUSE_TAPS + 1u));
-        let tap_count = u32(polar_offset.y / tap_interval);
-        var occluded = false;
-        for (var j = 1u; j <= tap_count; j += 1u) {
-            let tap_dist = f32(j) * tap_interval;
-            let tap_offset = tap_dist * normalize(offset);
-
-            let tap_uv = uv + tap_offset / vec2<f32>(render_size);
-            let tap_deferred_coords = jittered_deferred_coords(tap_uv);
-            let tap_depth = textureLoad(position_texture, tap_deferred_coords, 0).w;
-
-            let ref_depth = mix(depth, sample_depth, f32(j) / f32(tap_count + 1u));
-            if tap_depth > ref_depth + 0.00001 {
-                occluded = true;
-                break;
-            }
-        }
-        if occluded {
-            continue;
-        }
-
         let jacobian = select(1.0, compute_jacobian(q.s, s), q.s.sample_position.w > 0.5);
 #ifdef EMISSIVE_LIT
         merge_reservoir(&r, q, luminance(q.s.radiance.rgb) / jacobian);
@@ -1642,6 +2484,28 @@ fn spatial_reuse(
 #endif
     }
 
+    // Unbiased reuse normalizes the reservoir by the sample counts of the domains containing the selected sample,
+    // i.e., of the previous reservoir and the neighbors that see it from their own surfaces, instead of all of them.
+    // The pixel always does, since it can't select a sample it doesn't see.
+    var support = 1.0;
+    if unbiased && r.count > 0.0 {
+        var support_count = r.count;
+        if previous.count > 0.0
+            && any(r.s.sample_position != previous.s.sample_position)
+            && !is_sample_visible(previous.s.visible_position.xyz, previous.s.visible_normal, r.s.sample_position) {
+            support_count -= previous.count;
+        }
+        for (var i = 1u; i <= SPATIAL_REUSE_COUNT; i += 1u) {
+            if !load_spatial_neighbor(i, coords, workgroup_id, depth, s, &q) {
+                continue;
+            }
+            if !is_sample_visible(q.s.visible_position.xyz, q.s.visible_normal, r.s.sample_position) {
+                support_count -= q.count;
+            }
+        }
+        support = max(support_count, 0.0) / r.count;
+    }
+
     // Clamp...
     let m = f32(frame.max_spatial_reuse_count);
     if r.count > m {
@@ -1658,9 +2522,9 @@ fn spatial_reuse(
         r.s.radiance
     );
 #ifdef EMISSIVE_LIT
-    let total_lum = r.count * luminance(r.s.radiance.rgb);
+    let total_lum = r.count * support * luminance(r.s.radiance.rgb);
 #else
-    let total_lum = r.count * luminance(out_radiance);
+    let total_lum = r.count * support * luminance(out_radiance);
 #endif
     r.w = select(0.0, r.w_sum / total_lum, total_lum > 0.0);
 
@@ -1681,4 +2545,13 @@ fn spatial_reuse(
     let out_color = r.w * out_radiance;
 #endif
     textureStore(render_texture, coords, vec4<f32>(out_color, 1.0));
+
+    let specular_radiance = specular_shading(
+        view_direction,
+        s.visible_normal,
+        normalize(r.s.sample_position.xyz - s.visible_position.xyz),
+        surface,
+        r.s.radiance
+    );
+    textureStore(specular_texture, coords, vec4<f32>(r.w * specular_radiance, 1.0));
 }
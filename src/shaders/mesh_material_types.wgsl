@@ -53,13 +53,31 @@ struct Material {
 
     normal_map_texture: u32,
     occlusion_texture: u32,
+
+    base_color_texture_rect: vec4<f32>,
+    emissive_texture_rect: vec4<f32>,
+    metallic_roughness_texture_rect: vec4<f32>,
+    normal_map_texture_rect: vec4<f32>,
+    occlusion_texture_rect: vec4<f32>,
+    srgb_texture_mask: u32,
 };
 
 struct AliasEntry {
     prob: f32,
     index: u32,
+    pdf: f32,
 }
 
+struct LightNode {
+    min: vec3<f32>,
+    power: f32,
+    max: vec3<f32>,
+    child: u32,         // Right child, or emissive or area light index with the leaf flag
+    axis: vec3<f32>,
+    cos_theta_o: f32,
+    two_sided: u32,
+};
+
 struct Emissive {
     emissive: vec4<f32>,
     position: vec3<f32>,
@@ -70,6 +88,17 @@ struct Emissive {
     node_index: u32,
 };
 
+struct AreaLight {
+    position: vec3<f32>,
+    kind: u32,
+    radiance: vec3<f32>,
+    visible: u32,
+    tangent: vec3<f32>,
+    width: f32,         // Half width of rectangles, or radius of the others
+    bitangent: vec3<f32>,
+    height: f32,        // Half height of rectangles, or half length of tubes
+};
+
 type Vertices = array<Vertex>;
 type Primitives = array<Primitive>;
 type Instances = array<Instance>;
@@ -81,3 +110,13 @@ struct Nodes {
     count: u32,
     data: array<Node>,
 };
+
+struct LightNodes {
+    count: u32,
+    data: array<LightNode>,
+};
+
+struct AreaLights {
+    count: u32,
+    data: array<AreaLight>,
+};
@@ -11,12 +11,27 @@ struct Frame {
     temporal_reuse: u32,
     emissive_spatial_reuse: u32,
     indirect_spatial_reuse: u32,
+    direct_unbiased_reuse: u32,
+    emissive_unbiased_reuse: u32,
+    indirect_unbiased_reuse: u32,
     max_temporal_reuse_count: u32,
     max_spatial_reuse_count: u32,
     max_reservoir_lifetime: f32,
     solar_angle: f32,
     max_indirect_luminance: f32,
     upscale_ratio: f32,
+    accumulate: u32,
+    accumulated_samples: u32,
+    tone_mapping: u32,
+    aperture: f32,
+    focus_distance: f32,
+    aperture_blades: u32,
+    aperture_rotation: f32,
+    shutter: f32,
+    motion_blur_samples: u32,
+    history_reset: u32,
+    temporal_upscale: u32,
+    upscale_jitter: vec2<f32>,
 };
 
 struct PreviousView {
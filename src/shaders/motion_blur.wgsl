@@ -0,0 +1,164 @@
+#import bevy_hikari::mesh_view_bindings
+#import bevy_hikari::deferred_bindings
+#import bevy_hikari::utils
+
+@group(2) @binding(0)
+var nearest_sampler: sampler;
+@group(2) @binding(1)
+var linear_sampler: sampler;
+
+@group(3) @binding(0)
+var input_texture: texture_2d<f32>;
+@group(3) @binding(1)
+var velocity_tile_texture: texture_2d<f32>;
+
+@group(4) @binding(0)
+var output_texture: texture_storage_2d<rgba16float, read_write>;
+
+// Size of velocity tiles in pixels, which is also the largest blur radius.
+let TILE_SIZE: i32 = 16;
+// Relative depth difference over which samples are blended between foreground and background.
+let SOFT_DEPTH_EXTENT: f32 = 0.05;
+let MISS_DEPTH: f32 = 1.0e10;
+
+fn deferred_coords(coords: vec2<i32>) -> vec2<i32> {
+    let size = textureDimensions(input_texture);
+    let deferred_size = textureDimensions(position_texture);
+    return min(vec2<i32>(coords_to_uv(coords, size) * vec2<f32>(deferred_size)), deferred_size - 1);
+}
+
+fn view_depth(coords: vec2<i32>) -> f32 {
+    let position = textureLoad(position_texture, deferred_coords(coords), 0);
+    if (position.w == 0.0) {
+        return MISS_DEPTH;
+    }
+    return -(view.inverse_view * vec4<f32>(position.xyz, 1.0)).z;
+}
+
+// Half of the screen space motion while the shutter is open, in pixels.
+fn pixel_velocity(coords: vec2<i32>) -> vec2<f32> {
+    let size = textureDimensions(input_texture);
+    let uv = coords_to_uv(coords, size);
+    let deferred_coords = deferred_coords(coords);
+
+    var velocity = textureLoad(velocity_uv_texture, deferred_coords, 0).xy;
+    if (textureLoad(position_texture, deferred_coords, 0).w == 0.0) {
+        // The background only moves with the camera rotation.
+        let ndc = vec2<f32>(2.0 * uv.x - 1.0, 1.0 - 2.0 * uv.y);
+        let direction = view.inverse_view_proj * vec4<f32>(ndc, 0.0, 1.0);
+        velocity = uv - clip_to_uv(previous_view.view_proj * direction);
+    }
+
+    let pixels = 0.5 * frame.shutter * velocity * vec2<f32>(size);
+    let speed = length(pixels);
+    return select(pixels, pixels * f32(TILE_SIZE) / speed, speed > f32(TILE_SIZE));
+}
+
+fn select_max_velocity(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    return select(a, b, dot(b, b) > dot(a, a));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn motion_blur_tile_max(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let tile = vec2<i32>(invocation_id.xy);
+    if (any(tile >= textureDimensions(output_texture))) {
+        return;
+    }
+
+    let size = textureDimensions(input_texture);
+    var max_velocity = vec2<f32>(0.0);
+    for (var y = 0; y < TILE_SIZE; y += 1) {
+        for (var x = 0; x < TILE_SIZE; x += 1) {
+            let coords = tile * TILE_SIZE + vec2<i32>(x, y);
+            if (all(coords < size)) {
+                max_velocity = select_max_velocity(max_velocity, pixel_velocity(coords));
+            }
+        }
+    }
+    textureStore(output_texture, tile, vec4<f32>(max_velocity, 0.0, 1.0));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn motion_blur_neighbor_max(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let tile = vec2<i32>(invocation_id.xy);
+    let tile_count = textureDimensions(output_texture);
+    if (any(tile >= tile_count)) {
+        return;
+    }
+
+    var max_velocity = vec2<f32>(0.0);
+    for (var y = -1; y <= 1; y += 1) {
+        for (var x = -1; x <= 1; x += 1) {
+            let neighbor = clamp(tile + vec2<i32>(x, y), vec2<i32>(0), tile_count - 1);
+            let velocity = textureLoad(velocity_tile_texture, neighbor, 0).xy;
+            max_velocity = select_max_velocity(max_velocity, velocity);
+        }
+    }
+    textureStore(output_texture, tile, vec4<f32>(max_velocity, 0.0, 1.0));
+}
+
+// Weight of a sample in front of the other one.
+fn soft_depth_compare(a: f32, b: f32) -> f32 {
+    return clamp(1.0 - (a - b) / (SOFT_DEPTH_EXTENT * b), 0.0, 1.0);
+}
+
+fn cone(distance: f32, velocity: f32) -> f32 {
+    return clamp(1.0 - distance / max(velocity, 0.0001), 0.0, 1.0);
+}
+
+fn cylinder(distance: f32, velocity: f32) -> f32 {
+    return 1.0 - smoothstep(0.95 * velocity, 1.05 * velocity, distance);
+}
+
+// Reconstruction filter along the dominant velocity of the neighborhood.
+// https://casual-effects.com/research/McGuire2012Blur/index.html
+@compute @workgroup_size(8, 8, 1)
+fn motion_blur(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let size = textureDimensions(output_texture);
+    let coords = vec2<i32>(invocation_id.xy);
+    if (any(coords >= size)) {
+        return;
+    }
+
+    let center = textureLoad(input_texture, coords, 0);
+    let tile_count = textureDimensions(velocity_tile_texture);
+    let neighbor_velocity = textureLoad(velocity_tile_texture, min(coords / TILE_SIZE, tile_count - 1), 0).xy;
+    if (length(neighbor_velocity) < 0.5) {
+        textureStore(output_texture, coords, center);
+        return;
+    }
+
+    let center_depth = view_depth(coords);
+    let center_speed = max(length(pixel_velocity(coords)), 0.5);
+
+    // Offset the samples with the sub-pixel jitter, so that the shutter is sampled temporally.
+    let index = frame.number & 15u;
+    let halton = frame.halton[index >> 1u];
+    let jitter = select(halton.zw, halton.xy, (index & 1u) == 0u);
+
+    var total = 1.0 / center_speed;
+    var color = total * inverse_reversible_tone_map(center.rgb);
+
+    let count = max(frame.motion_blur_samples, 1u);
+    for (var i = 0u; i < count; i += 1u) {
+        let t = mix(-1.0, 1.0, (f32(i) + jitter.x) / f32(count));
+        let sample_coords = clamp(coords + vec2<i32>(round(t * neighbor_velocity)), vec2<i32>(0), size - 1);
+        let distance = length(vec2<f32>(sample_coords - coords));
+
+        let sample_depth = view_depth(sample_coords);
+        let sample_speed = length(pixel_velocity(sample_coords));
+
+        let foreground = soft_depth_compare(sample_depth, center_depth);
+        let background = soft_depth_compare(center_depth, sample_depth);
+        var weight = foreground * cone(distance, sample_speed);
+        weight += background * cone(distance, center_speed);
+        weight += 2.0 * cylinder(distance, sample_speed) * cylinder(distance, center_speed);
+
+        let sample_color = inverse_reversible_tone_map(textureLoad(input_texture, sample_coords, 0).rgb);
+        color += weight * sample_color;
+        total += weight;
+    }
+
+    color /= total;
+    textureStore(output_texture, coords, vec4<f32>(reversible_tone_map(color), center.a));
+}
@@ -1,4 +1,3 @@
-#import bevy_core_pipeline::tonemapping
 #import bevy_hikari::utils
 
 @group(0) @binding(0)
@@ -25,12 +24,6 @@ struct FragmentOutput {
     @location(0) color: vec4<f32>,
 }
 
-fn inverse_reintard_luminance(color: vec3<f32>) -> vec3<f32> {
-    let l_old = clamp(tonemapping_luminance(color), 0.0005, 0.995);
-    let l_new = l_old / (1.0 - l_old);
-    return tonemapping_change_luminance(color, l_new);
-}
-
 @fragment
 fn fragment(in: VertexOutput) -> FragmentOutput {
     var out: FragmentOutput;
@@ -39,11 +32,7 @@ fn fragment(in: VertexOutput) -> FragmentOutput {
     let albedo = textureSample(albedo_texture, linear_sampler, uv);
 
     out.color = textureSample(input_texture, linear_sampler, uv);
-    out.color = select(out.color, albedo, any_is_nan_vec4(out.color));
-
-#ifdef HDR
-    out.color = vec4<f32>(inverse_reintard_luminance(out.color.rgb), out.color.a);
-#endif
+    out.color = select(out.color, vec4<f32>(albedo.rgb, 1.0), any_is_nan_vec4(out.color));
 
     return out;
 }
\ No newline at end of file
@@ -132,6 +132,10 @@ fn smaa_tu4x(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
     let velocity = nearest_velocity(previous_output_uv);
     let previous_reprojected_uv = previous_output_uv - velocity;
     var previous_color = textureSampleLevel(previous_render_texture, nearest_sampler, previous_reprojected_uv, 0.0).rgb;
+    if frame.history_reset != 0u {
+        // The history was rendered at another resolution.
+        previous_color = current_color;
+    }
 
     let boundary_miss = any(abs(previous_reprojected_uv - 0.5) > vec2<f32>(0.5));
 
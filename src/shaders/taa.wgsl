@@ -121,6 +121,12 @@ fn taa_jasmine(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
         return;
     }
 
+    if frame.history_reset != 0u {
+        // The history was rendered at another resolution.
+        textureStore(output_texture, coords, original_color);
+        return;
+    }
+
     let previous_velocity = textureSampleLevel(previous_velocity_uv_texture, nearest_sampler, previous_uv, 0.0).xy;
     let velocity_miss = distance(velocity, previous_velocity) > 0.00005;
 
@@ -8,6 +8,12 @@ var nearest_sampler: sampler;
 @group(2) @binding(1)
 var linear_sampler: sampler;
 
+#ifdef COLOR_GRADING
+@group(3) @binding(0)
+var input_texture: texture_2d<f32>;
+@group(3) @binding(1)
+var lut_texture: texture_3d<f32>;
+#else
 @group(3) @binding(0)
 var direct_render_texture: texture_2d<f32>;
 @group(3) @binding(1)
@@ -15,18 +21,288 @@ var emissive_render_texture: texture_2d<f32>;
 @group(3) @binding(2)
 var indirect_render_texture: texture_2d<f32>;
 
+struct ExposureSettings {
+    ev100: f32,
+    auto_exposure: u32,
+    min_ev100: f32,
+    max_ev100: f32,
+    compensation: f32,
+    speed_brighten: f32,
+    speed_darken: f32,
+    delta_time: f32,
+};
+
+struct ExposureState {
+    ev100: f32,
+    initialized: u32,
+    histogram: array<atomic<u32>, 64>,
+};
+
+@group(5) @binding(0)
+var<uniform> exposure_settings: ExposureSettings;
+@group(5) @binding(1)
+var<storage, read_write> exposure_state: ExposureState;
+
+var<workgroup> local_histogram: array<atomic<u32>, 64>;
+#endif
+
 @group(4) @binding(0)
 var output_texture: texture_storage_2d<rgba16float, read_write>;
 
+let TONE_MAPPING_NONE: u32 = 0u;
+let TONE_MAPPING_REINHARD: u32 = 1u;
+let TONE_MAPPING_ACES_FITTED: u32 = 2u;
+let TONE_MAPPING_AGX: u32 = 3u;
+let TONE_MAPPING_TONY_MC_MAPFACE: u32 = 4u;
+
+// https://github.com/TheRealMJP/BakingLab/blob/master/BakingLab/ACES.hlsl
+fn aces_fitted(color: vec3<f32>) -> vec3<f32> {
+    let input_matrix = mat3x3<f32>(
+        vec3<f32>(0.59719, 0.07600, 0.02840),
+        vec3<f32>(0.35458, 0.90834, 0.13383),
+        vec3<f32>(0.04823, 0.01566, 0.83777),
+    );
+    let output_matrix = mat3x3<f32>(
+        vec3<f32>(1.60475, -0.10208, -0.00327),
+        vec3<f32>(-0.53108, 1.10813, -0.07276),
+        vec3<f32>(-0.07367, -0.00605, 1.07602),
+    );
+
+    let v = input_matrix * color;
+    let a = v * (v + 0.0245786) - 0.000090537;
+    let b = v * (0.983729 * v + 0.4329510) + 0.238081;
+    return clamp(output_matrix * (a / b), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// https://iolite-engine.com/blog_posts/minimal_agx_implementation
+fn agx(color: vec3<f32>) -> vec3<f32> {
+    let inset_matrix = mat3x3<f32>(
+        vec3<f32>(0.842479062253094, 0.0423282422610123, 0.0423756549057051),
+        vec3<f32>(0.0784335999999992, 0.878468636469772, 0.0784336),
+        vec3<f32>(0.0792237451477643, 0.0791661274605434, 0.879142973793104),
+    );
+    let outset_matrix = mat3x3<f32>(
+        vec3<f32>(1.19687900512017, -0.0528968517574562, -0.0529716355144438),
+        vec3<f32>(-0.0980208811401368, 1.15190312990417, -0.0980434501171241),
+        vec3<f32>(-0.0990297440797205, -0.0989611768448433, 1.15107367264116),
+    );
+    let min_ev = -12.47393;
+    let max_ev = 4.026069;
+
+    var v = inset_matrix * color;
+    v = clamp(log2(max(v, vec3<f32>(1e-10))), vec3<f32>(min_ev), vec3<f32>(max_ev));
+    v = (v - min_ev) / (max_ev - min_ev);
+
+    // Polynomial fit of the default contrast sigmoid.
+    let v2 = v * v;
+    let v4 = v2 * v2;
+    v = 15.5 * v4 * v2 - 40.14 * v4 * v + 31.96 * v4 - 6.868 * v2 * v + 0.4298 * v2 + 0.1191 * v - 0.00232;
+
+    v = outset_matrix * v;
+    return pow(clamp(v, vec3<f32>(0.0), vec3<f32>(1.0)), vec3<f32>(2.2));
+}
+
+// Compresses the peak channel with a soft shoulder so that hues are kept,
+// and blends towards white as the compression saturates.
+fn tony_mc_mapface(color: vec3<f32>) -> vec3<f32> {
+    let peak = max(color.r, max(color.g, color.b));
+    if (peak <= 0.0) {
+        return vec3<f32>(0.0);
+    }
+
+    let mapped_peak = 1.0 - exp(-peak);
+    let hue_preserved = color * (mapped_peak / peak);
+
+    let white = vec3<f32>(mapped_peak);
+    let bleach = smoothstep(0.6, 1.0, mapped_peak);
+    return mix(hue_preserved, white, bleach * bleach);
+}
+
+fn tone_map(color: vec3<f32>) -> vec3<f32> {
+    if (frame.tone_mapping == TONE_MAPPING_REINHARD) {
+        return reinhard_luminance(color);
+    }
+    if (frame.tone_mapping == TONE_MAPPING_ACES_FITTED) {
+        return aces_fitted(color);
+    }
+    if (frame.tone_mapping == TONE_MAPPING_AGX) {
+        return agx(color);
+    }
+    if (frame.tone_mapping == TONE_MAPPING_TONY_MC_MAPFACE) {
+        return tony_mc_mapface(color);
+    }
+    return color;
+}
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, color <= vec3<f32>(0.0031308));
+}
+
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let low = color / 12.92;
+    let high = pow((color + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, color <= vec3<f32>(0.04045));
+}
+
+#ifdef COLOR_GRADING
+// Samples the LUT at texel centers, so that the identity LUT maps colors to themselves.
+fn color_grade(color: vec3<f32>) -> vec3<f32> {
+    let size = vec3<f32>(textureDimensions(lut_texture));
+    let encoded = linear_to_srgb(clamp(color, vec3<f32>(0.0), vec3<f32>(1.0)));
+    let uvw = (encoded * (size - 1.0) + 0.5) / size;
+    return srgb_to_linear(textureSampleLevel(lut_texture, linear_sampler, uvw, 0.0).rgb);
+}
+
 @compute @workgroup_size(8, 8, 1)
-fn tone_mapping(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+fn color_grading(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let size = textureDimensions(output_texture);
     let coords = vec2<i32>(invocation_id.xy);
+    if (any(coords >= size)) {
+        return;
+    }
+
+    // The background keeps the exact clear color.
+    let deferred_size = textureDimensions(position_texture);
+    let deferred_coords = min(vec2<i32>(coords_to_uv(coords, size) * vec2<f32>(deferred_size)), deferred_size - 1);
+    if (textureLoad(position_texture, deferred_coords, 0).w == 0.0) {
+        textureStore(output_texture, coords, frame.clear_color);
+        return;
+    }
 
+    let input_size = textureDimensions(input_texture);
+    let input_coords = min(vec2<i32>(coords_to_uv(coords, size) * vec2<f32>(input_size)), input_size - 1);
+    let input = textureLoad(input_texture, input_coords, 0);
+
+    var color = inverse_reversible_tone_map(input.rgb);
+    if (frame.tone_mapping != TONE_MAPPING_NONE) {
+        color = color_grade(tone_map(color));
+    }
+    textureStore(output_texture, coords, vec4<f32>(color, input.a));
+}
+#else
+let HISTOGRAM_BINS: u32 = 64u;
+// Fractions of the darkest and brightest pixels ignored when metering.
+let METERING_LOW_PERCENTILE: f32 = 0.1;
+let METERING_HIGH_PERCENTILE: f32 = 0.95;
+// Offset from the log2 luminance of a pixel to the exposure value that maps it to middle gray.
+let METERING_EV_OFFSET: f32 = 3.0;
+// Radiance is pre-exposed at the fixed exposure of bevy's lights.
+let BEVY_EV100: f32 = 11.965784;
+
+fn load_radiance(coords: vec2<i32>) -> vec4<f32> {
     var color = textureLoad(direct_render_texture, coords, 0);
     color += textureLoad(emissive_render_texture, coords, 0);
     color += textureLoad(indirect_render_texture, coords, 0);
+    return color;
+}
+
+fn exposure_scale() -> f32 {
+    var ev100 = exposure_settings.ev100;
+    if (exposure_settings.auto_exposure != 0u && exposure_state.initialized != 0u) {
+        ev100 = exposure_state.ev100;
+    }
+    return exp2(BEVY_EV100 - ev100);
+}
+
+// Bin 0 holds black pixels, the others evenly cover the metering range.
+fn histogram_bin(luminance: f32) -> u32 {
+    if (luminance < 1e-6) {
+        return 0u;
+    }
+    let ev = log2(luminance) + BEVY_EV100 + METERING_EV_OFFSET;
+    let range = exposure_settings.max_ev100 - exposure_settings.min_ev100;
+    let t = clamp((ev - exposure_settings.min_ev100) / range, 0.0, 1.0);
+    return 1u + min(u32(t * f32(HISTOGRAM_BINS - 1u)), HISTOGRAM_BINS - 2u);
+}
+
+fn histogram_bin_ev(bin: u32) -> f32 {
+    let range = exposure_settings.max_ev100 - exposure_settings.min_ev100;
+    let t = max(f32(bin) - 0.5, 0.0) / f32(HISTOGRAM_BINS - 1u);
+    return exposure_settings.min_ev100 + t * range;
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn luminance_histogram(
+    @builtin(global_invocation_id) invocation_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+) {
+    atomicStore(&local_histogram[local_index], 0u);
+    workgroupBarrier();
+
+    let coords = vec2<i32>(invocation_id.xy);
+    if (all(coords < textureDimensions(direct_render_texture))) {
+        let color = load_radiance(coords);
+        if (color.a > 0.0) {
+            atomicAdd(&local_histogram[histogram_bin(tonemapping_luminance(max(color.rgb, vec3<f32>(0.0))))], 1u);
+        }
+    }
+    workgroupBarrier();
+
+    let count = atomicLoad(&local_histogram[local_index]);
+    if (count > 0u) {
+        atomicAdd(&exposure_state.histogram[local_index], count);
+    }
+}
+
+@compute @workgroup_size(64, 1, 1)
+fn exposure_adaptation(@builtin(local_invocation_index) local_index: u32) {
+    let count = atomicLoad(&exposure_state.histogram[local_index]);
+    atomicStore(&exposure_state.histogram[local_index], 0u);
+    atomicStore(&local_histogram[local_index], count);
+    workgroupBarrier();
+
+    if (local_index != 0u) {
+        return;
+    }
+
+    var total = 0u;
+    for (var bin = 0u; bin < HISTOGRAM_BINS; bin += 1u) {
+        total += atomicLoad(&local_histogram[bin]);
+    }
+    if (total == 0u) {
+        return;
+    }
+
+    // Average exposure value over the pixels between the low and high percentiles.
+    let low = METERING_LOW_PERCENTILE * f32(total);
+    let high = METERING_HIGH_PERCENTILE * f32(total);
+    var sum = 0.0;
+    var weight = 0.0;
+    var cumulative = 0.0;
+    for (var bin = 0u; bin < HISTOGRAM_BINS; bin += 1u) {
+        let count = f32(atomicLoad(&local_histogram[bin]));
+        let w = max(min(cumulative + count, high) - max(cumulative, low), 0.0);
+        sum += w * histogram_bin_ev(bin);
+        weight += w;
+        cumulative += count;
+    }
+
+    var target_ev100 = sum / max(weight, 1.0) - exposure_settings.compensation;
+    target_ev100 = clamp(target_ev100, exposure_settings.min_ev100, exposure_settings.max_ev100);
+
+    if (exposure_state.initialized == 0u) {
+        exposure_state.ev100 = target_ev100;
+        exposure_state.initialized = 1u;
+        return;
+    }
+
+    let current_ev100 = exposure_state.ev100;
+    let speed = select(exposure_settings.speed_darken, exposure_settings.speed_brighten, target_ev100 > current_ev100);
+    let factor = 1.0 - exp(-exposure_settings.delta_time * speed);
+    exposure_state.ev100 = mix(current_ev100, target_ev100, factor);
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn tone_mapping(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let coords = vec2<i32>(invocation_id.xy);
+
+    var color = load_radiance(coords);
+    color = vec4<f32>(color.rgb * exposure_scale(), color.a);
 
-    color = vec4<f32>(reinhard_luminance(max(color.rgb, vec3<f32>(0.0039))), color.a);
+    color = vec4<f32>(reversible_tone_map(max(color.rgb, vec3<f32>(0.0))), color.a);
     color = select(frame.clear_color, color, color.a > 0.0);
     textureStore(output_texture, coords, color);
 }
+#endif
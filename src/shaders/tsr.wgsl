@@ -0,0 +1,203 @@
+#import bevy_hikari::mesh_view_bindings
+#import bevy_hikari::deferred_bindings
+#import bevy_hikari::utils
+
+@group(2) @binding(0)
+var nearest_sampler: sampler;
+@group(2) @binding(1)
+var linear_sampler: sampler;
+
+@group(3) @binding(0)
+var previous_render_texture: texture_2d<f32>;
+@group(3) @binding(1)
+var render_texture: texture_2d<f32>;
+@group(3) @binding(2)
+var albedo_texture: texture_2d<f32>;
+@group(3) @binding(3)
+var previous_weight_texture: texture_2d<f32>;
+@group(3) @binding(4)
+var weight_texture: texture_storage_2d<rgba16float, read_write>;
+
+@group(4) @binding(0)
+var output_texture: texture_storage_2d<rgba16float, read_write>;
+
+// Upper bound of the accumulated sample weight, which sets the slowest response of the history.
+let MAX_HISTORY_WEIGHT: f32 = 16.0;
+// Upper bound of the accumulated sample weight of fully reactive pixels.
+let REACTIVE_HISTORY_WEIGHT: f32 = 1.0;
+// Scale of the standard deviation of the neighborhood that the history is clipped to.
+let CLIP_GAMMA: f32 = 1.25;
+// Relative distance between the current and reprojected positions to be considered disoccluded.
+let DISOCCLUSION_THRESHOLD: f32 = 0.05;
+
+fn RGB_to_YCoCg(rgb: vec3<f32>) -> vec3<f32> {
+    let y = (rgb.r / 4.0) + (rgb.g / 2.0) + (rgb.b / 4.0);
+    let co = (rgb.r / 2.0) - (rgb.b / 2.0);
+    let cg = (-rgb.r / 4.0) + (rgb.g / 2.0) - (rgb.b / 4.0);
+    return vec3<f32>(y, co, cg);
+}
+
+fn YCoCg_to_RGB(ycocg: vec3<f32>) -> vec3<f32> {
+    let r = ycocg.x + ycocg.y - ycocg.z;
+    let g = ycocg.x + ycocg.z;
+    let b = ycocg.x - ycocg.y - ycocg.z;
+    return clamp(vec3<f32>(r, g, b), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+fn clip_towards_aabb_center(previous_color: vec3<f32>, current_color: vec3<f32>, aabb_min: vec3<f32>, aabb_max: vec3<f32>) -> vec3<f32> {
+    let p_clip = 0.5 * (aabb_max + aabb_min);
+    let e_clip = max(0.5 * (aabb_max - aabb_min), vec3<f32>(0.0001));
+    let v_clip = previous_color - p_clip;
+    let v_unit = v_clip.xyz / e_clip;
+    let a_unit = abs(v_unit);
+    let ma_unit = max(a_unit.x, max(a_unit.y, a_unit.z));
+    return select(previous_color, p_clip + v_clip / ma_unit, ma_unit > 1.0);
+}
+
+// Sub-pixel jitter of the prepass, the same as in the prepass shader.
+fn prepass_jitter() -> vec2<f32> {
+    let index = frame.number & 15u;
+    let halton: vec4<f32> = frame.halton[index >> 1u];
+    return select(halton.zw, halton.xy, (index & 1u) == 0u);
+}
+
+// Position in output pixels of the scene that the render pixel shaded in this frame.
+fn sample_position(render_coords: vec2<i32>) -> vec2<f32> {
+    let output_size = vec2<f32>(textureDimensions(output_texture));
+    let uv = coords_to_uv(render_coords, textureDimensions(render_texture));
+    let deferred_coords = floor(uv * output_size + frame.upscale_jitter);
+    return deferred_coords + 0.5 - prepass_jitter();
+}
+
+// Velocity of the nearest surface in the 3x3 neighborhood, so that edges are reprojected with the foreground.
+fn nearest_velocity(coords: vec2<i32>) -> vec2<f32> {
+    let size = textureDimensions(position_texture);
+    var nearest_coords = coords;
+    var max_depth = 0.0;
+    for (var y = -1; y <= 1; y += 1) {
+        for (var x = -1; x <= 1; x += 1) {
+            let sample_coords = clamp(coords + vec2<i32>(x, y), vec2<i32>(0), size - 1);
+            let depth = textureLoad(position_texture, sample_coords, 0).w;
+            if depth > max_depth {
+                max_depth = depth;
+                nearest_coords = sample_coords;
+            }
+        }
+    }
+    return textureLoad(velocity_uv_texture, nearest_coords, 0).xy;
+}
+
+// 5-tap Catmull-Rom filtering of the history.
+// https://gist.github.com/TheRealMJP/c83b8c0f46b63f3a88a5986f4fa982b1
+fn sample_history(uv: vec2<f32>) -> vec3<f32> {
+    let size = vec2<f32>(textureDimensions(previous_render_texture));
+    let texel_size = 1.0 / size;
+
+    let sample_position = uv * size;
+    let texel_position_1 = floor(sample_position - 0.5) + 0.5;
+    let f = sample_position - texel_position_1;
+    let w0 = f * (-0.5 + f * (1.0 - 0.5 * f));
+    let w1 = 1.0 + f * f * (-2.5 + 1.5 * f);
+    let w2 = f * (0.5 + f * (2.0 - 1.5 * f));
+    let w3 = f * f * (-0.5 + 0.5 * f);
+    let w12 = w1 + w2;
+    let offset12 = w2 / w12;
+    let texel_position_0 = (texel_position_1 - 1.0) * texel_size;
+    let texel_position_3 = (texel_position_1 + 2.0) * texel_size;
+    let texel_position_12 = (texel_position_1 + offset12) * texel_size;
+
+    var color = vec3<f32>(0.0);
+    color += textureSampleLevel(previous_render_texture, linear_sampler, vec2<f32>(texel_position_12.x, texel_position_0.y), 0.0).rgb * w12.x * w0.y;
+    color += textureSampleLevel(previous_render_texture, linear_sampler, vec2<f32>(texel_position_0.x, texel_position_12.y), 0.0).rgb * w0.x * w12.y;
+    color += textureSampleLevel(previous_render_texture, linear_sampler, vec2<f32>(texel_position_12.x, texel_position_12.y), 0.0).rgb * w12.x * w12.y;
+    color += textureSampleLevel(previous_render_texture, linear_sampler, vec2<f32>(texel_position_3.x, texel_position_12.y), 0.0).rgb * w3.x * w12.y;
+    color += textureSampleLevel(previous_render_texture, linear_sampler, vec2<f32>(texel_position_12.x, texel_position_3.y), 0.0).rgb * w12.x * w3.y;
+    return clamp(color, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// Accumulates jittered samples of the low resolution render into a history at the output resolution.
+// Loosely follows https://gpuopen.com/fidelityfx-superresolution-2/
+@compute @workgroup_size(8, 8, 1)
+fn temporal_upscale(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let output_size = textureDimensions(output_texture);
+    let coords = vec2<i32>(invocation_id.xy);
+    if any(coords >= output_size) {
+        return;
+    }
+
+    let render_size = textureDimensions(render_texture);
+    let ratio = vec2<f32>(output_size) / vec2<f32>(render_size);
+    let position = vec2<f32>(coords) + 0.5;
+    let uv = position / vec2<f32>(output_size);
+
+    // Reconstruct the current frame from the 3x3 render pixels around, weighted by their distance in render pixels.
+    let center_coords = min(vec2<i32>(uv * vec2<f32>(render_size)), render_size - 1);
+    let center = textureLoad(render_texture, center_coords, 0);
+
+    var color_sum = vec3<f32>(0.0);
+    var weight_sum = 0.0;
+    var max_weight = 0.0;
+    var moment_1 = vec3<f32>(0.0);
+    var moment_2 = vec3<f32>(0.0);
+    for (var y = -1; y <= 1; y += 1) {
+        for (var x = -1; x <= 1; x += 1) {
+            let sample_coords = clamp(center_coords + vec2<i32>(x, y), vec2<i32>(0), render_size - 1);
+            let sample_color = textureLoad(render_texture, sample_coords, 0).rgb;
+            let offset = (sample_position(sample_coords) - position) / ratio;
+            let weight = exp(-2.0 * dot(offset, offset));
+
+            color_sum += weight * sample_color;
+            weight_sum += weight;
+            max_weight = max(max_weight, weight);
+
+            let ycocg = RGB_to_YCoCg(sample_color);
+            moment_1 += ycocg;
+            moment_2 += ycocg * ycocg;
+        }
+    }
+    let current_color = select(center.rgb, color_sum / weight_sum, weight_sum > 0.0001);
+
+    // Reactive pixels accumulate fewer samples: emissive surfaces marked by the light pass,
+    // and animated surfaces whose motion changed since the last frame.
+    let velocity = nearest_velocity(coords);
+    let previous_uv = uv - velocity;
+    let previous_velocity = textureSampleLevel(previous_velocity_uv_texture, nearest_sampler, previous_uv, 0.0).xy;
+    let acceleration = length((velocity - previous_velocity) * vec2<f32>(output_size));
+    var reactive = textureLoad(albedo_texture, coords, 0).a;
+    reactive = max(reactive, smoothstep(0.5, 2.0, acceleration));
+
+    var history_weight = textureSampleLevel(previous_weight_texture, nearest_sampler, previous_uv, 0.0).r;
+    history_weight = min(history_weight, mix(MAX_HISTORY_WEIGHT, REACTIVE_HISTORY_WEIGHT, reactive));
+
+    // Discard the history of pixels off screen or disoccluded.
+    let current_position = textureLoad(position_texture, coords, 0);
+    let previous_position = textureSampleLevel(previous_position_texture, nearest_sampler, previous_uv, 0.0);
+    let view_distance = distance(current_position.xyz, view.world_position);
+    let current_instance = textureLoad(instance_material_texture, coords, 0).x;
+    let previous_coords = clamp(vec2<i32>(previous_uv * vec2<f32>(output_size)), vec2<i32>(0), output_size - 1);
+    let previous_instance = f32(textureLoad(previous_instance_material_texture, previous_coords, 0).x);
+    let boundary_miss = any(abs(previous_uv - 0.5) > vec2<f32>(0.5));
+    let depth_miss = (current_position.w == 0.0) != (previous_position.w == 0.0);
+    let position_miss = current_position.w > 0.0 && distance(current_position.xyz, previous_position.xyz) > DISOCCLUSION_THRESHOLD * view_distance;
+    let instance_miss = current_position.w > 0.0 && abs(current_instance - previous_instance) > 0.5;
+    let velocity_miss = acceleration > 0.5;
+    if boundary_miss || depth_miss || instance_miss || (position_miss && velocity_miss) {
+        history_weight = 0.0;
+    }
+
+    var previous_color = sample_history(previous_uv);
+    let mean = moment_1 / 9.0;
+    let deviation = sqrt(max(moment_2 / 9.0 - mean * mean, vec3<f32>(0.0)));
+    let gamma = mix(CLIP_GAMMA, 0.5 * CLIP_GAMMA, reactive);
+    previous_color = RGB_to_YCoCg(previous_color);
+    previous_color = clip_towards_aabb_center(previous_color, mean, mean - gamma * deviation, mean + gamma * deviation);
+    previous_color = YCoCg_to_RGB(previous_color);
+
+    // Samples far from the pixel center contribute less, but the first one is always taken.
+    let sample_weight = max(max_weight, select(0.0, 1.0, history_weight == 0.0));
+    let total_weight = history_weight + sample_weight;
+    let output = mix(previous_color, current_color, sample_weight / total_weight);
+
+    textureStore(output_texture, coords, vec4<f32>(output, center.a));
+    textureStore(weight_texture, coords, vec4<f32>(min(total_weight, MAX_HISTORY_WEIGHT)));
+}
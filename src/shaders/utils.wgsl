@@ -63,3 +63,13 @@ fn halton(base: u32, index: u32) -> f32 {
 fn luminance(v: vec3<f32>) -> f32 {
     return dot(v, vec3<f32>(0.2126, 0.7152, 0.0722));
 }
+
+// Invertible compression of radiance into [0, 1), so that anti-aliasing and upscaling work on bounded colors.
+// http://graphicrants.blogspot.com/2013/12/tone-mapping.html
+fn reversible_tone_map(color: vec3<f32>) -> vec3<f32> {
+    return color / (1.0 + max(color.r, max(color.g, color.b)));
+}
+
+fn inverse_reversible_tone_map(color: vec3<f32>) -> vec3<f32> {
+    return color / max(1.0 - max(color.r, max(color.g, color.b)), 0.0001);
+}
@@ -0,0 +1,152 @@
+use crate::{
+    light::{
+        ACCUMULATION_TEXTURE_FORMAT, ALBEDO_TEXTURE_FORMAT, AREA_LIGHT_TEXTURE_FORMAT,
+        RENDER_TEXTURE_FORMAT, VARIANCE_TEXTURE_FORMAT,
+    },
+    post_process::HDR_TEXTURE_FORMAT,
+};
+use bevy::{
+    core_pipeline::core_3d,
+    prelude::*,
+    render::{
+        camera::CameraRenderGraph,
+        render_resource::*,
+        renderer::{RenderAdapter, RenderDevice},
+        RenderApp,
+    },
+};
+use itertools::Itertools;
+use wgpu::TextureFormatFeatureFlags;
+
+/// Minimal number of bind groups used by the light pipelines.
+pub const REQUIRED_BIND_GROUPS: u32 = 7;
+/// Minimal number of storage buffers per shader stage used by the light pipelines.
+pub const REQUIRED_STORAGE_BUFFERS: u32 = 14;
+/// Minimal number of storage textures per shader stage used by the denoise pipelines.
+pub const REQUIRED_STORAGE_TEXTURES: u32 = 8;
+
+/// A device limit that is lower than required.
+#[derive(Debug, Clone)]
+pub struct MissingLimit {
+    pub name: &'static str,
+    pub required: u32,
+    pub supported: u32,
+}
+
+/// Capabilities of the render device, probed at startup.
+/// If any requirement is missing, cameras fall back to the core 3d render graph.
+#[derive(Debug, Clone, Resource)]
+pub struct HikariSupport {
+    /// Required device features that are not enabled.
+    pub missing_features: WgpuFeatures,
+    /// Device limits that are lower than required.
+    pub missing_limits: Vec<MissingLimit>,
+    /// Texture formats that don't support read-write storage access.
+    pub missing_read_write_formats: Vec<TextureFormat>,
+    /// Whether material textures can be bound as a binding array.
+    /// Otherwise, material textures are packed into an atlas.
+    pub texture_binding_array: bool,
+}
+
+impl HikariSupport {
+    /// Whether the path tracer can run on this device.
+    pub fn is_supported(&self) -> bool {
+        self.missing_features.is_empty()
+            && self.missing_limits.is_empty()
+            && self.missing_read_write_formats.is_empty()
+    }
+}
+
+impl FromWorld for HikariSupport {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+        let adapter = world.resource::<RenderAdapter>();
+        let features = render_device.features();
+        let limits = render_device.limits();
+
+        let mut missing_features = WgpuFeatures::empty();
+        if !features.contains(WgpuFeatures::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES) {
+            missing_features |= WgpuFeatures::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES;
+        }
+
+        let missing_limits = [
+            (
+                "max_bind_groups",
+                REQUIRED_BIND_GROUPS,
+                limits.max_bind_groups,
+            ),
+            (
+                "max_storage_buffers_per_shader_stage",
+                REQUIRED_STORAGE_BUFFERS,
+                limits.max_storage_buffers_per_shader_stage,
+            ),
+            (
+                "max_storage_textures_per_shader_stage",
+                REQUIRED_STORAGE_TEXTURES,
+                limits.max_storage_textures_per_shader_stage,
+            ),
+        ]
+        .into_iter()
+        .filter(|(_, required, supported)| supported < required)
+        .map(|(name, required, supported)| MissingLimit {
+            name,
+            required,
+            supported,
+        })
+        .collect();
+
+        let missing_read_write_formats = [
+            ALBEDO_TEXTURE_FORMAT,
+            VARIANCE_TEXTURE_FORMAT,
+            RENDER_TEXTURE_FORMAT,
+            ACCUMULATION_TEXTURE_FORMAT,
+            AREA_LIGHT_TEXTURE_FORMAT,
+            HDR_TEXTURE_FORMAT,
+        ]
+        .into_iter()
+        .unique()
+        .filter(|format| {
+            !missing_features.is_empty()
+                || !adapter
+                    .get_texture_format_features(*format)
+                    .flags
+                    .contains(TextureFormatFeatureFlags::STORAGE_READ_WRITE)
+        })
+        .collect();
+
+        let texture_binding_array = features.contains(
+            WgpuFeatures::TEXTURE_BINDING_ARRAY
+                | WgpuFeatures::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING,
+        );
+
+        Self {
+            missing_features,
+            missing_limits,
+            missing_read_write_formats,
+            texture_binding_array,
+        }
+    }
+}
+
+/// Probes the device capabilities once, and shares the result with the render app.
+/// Returns whether the path tracer can run, which is always true if there is no render app.
+pub(crate) fn probe_support(app: &mut App) -> bool {
+    if app.get_sub_app(RenderApp).is_err() {
+        return true;
+    }
+
+    app.world.init_resource::<HikariSupport>();
+    let support = app.world.resource::<HikariSupport>().clone();
+    let supported = support.is_supported();
+    app.sub_app_mut(RenderApp).insert_resource(support);
+    supported
+}
+
+/// Switches cameras using the `bevy_hikari` render graph to the core 3d render graph.
+pub(crate) fn fallback_render_graph_system(mut cameras: Query<&mut CameraRenderGraph>) {
+    for mut render_graph in &mut cameras {
+        if **render_graph == crate::graph::NAME {
+            render_graph.set(core_3d::graph::NAME);
+        }
+    }
+}
@@ -1,4 +1,8 @@
-use crate::{transform::GlobalTransformQueue, HikariSettings};
+use crate::{
+    accumulation::HikariAccumulationProgress, resolution::HikariDynamicResolution,
+    transform::GlobalTransformQueue, HikariLens, HikariMotionBlur, HikariSettings,
+    HikariToneMapping,
+};
 use bevy::{
     ecs::query::QueryItem,
     prelude::*,
@@ -114,12 +118,29 @@ pub struct FrameUniform {
     pub temporal_reuse: u32,
     pub emissive_spatial_reuse: u32,
     pub indirect_spatial_reuse: u32,
+    pub direct_unbiased_reuse: u32,
+    pub emissive_unbiased_reuse: u32,
+    pub indirect_unbiased_reuse: u32,
     pub max_temporal_reuse_count: u32,
     pub max_spatial_reuse_count: u32,
     pub max_reservoir_lifetime: f32,
     pub solar_angle: f32,
     pub max_indirect_luminance: f32,
     pub upscale_ratio: f32,
+    pub accumulate: u32,
+    pub accumulated_samples: u32,
+    pub tone_mapping: u32,
+    pub aperture: f32,
+    pub focus_distance: f32,
+    pub aperture_blades: u32,
+    pub aperture_rotation: f32,
+    pub shutter: f32,
+    pub motion_blur_samples: u32,
+    /// Set in frames where the render resolution changed, so temporal history is discarded.
+    pub history_reset: u32,
+    pub temporal_upscale: u32,
+    /// Offset in output pixels of the G-buffer samples shaded in this frame, with temporal upscaling.
+    pub upscale_jitter: Vec2,
 }
 
 const KERNEL: Mat3 = Mat3 {
@@ -139,10 +160,22 @@ const HALTON: [Vec4; 8] = [
 ];
 
 impl ExtractComponent for FrameUniform {
-    type Query = (&'static HikariSettings, &'static FrameCounter);
+    type Query = (
+        &'static HikariSettings,
+        &'static FrameCounter,
+        Option<&'static HikariAccumulationProgress>,
+        Option<&'static HikariLens>,
+        Option<&'static HikariMotionBlur>,
+        Option<&'static HikariDynamicResolution>,
+        Option<&'static Camera>,
+    );
     type Filter = ();
 
-    fn extract_component((settings, counter): QueryItem<Self::Query>) -> Self {
+    fn extract_component(
+        (settings, counter, progress, lens, motion_blur, dynamic_resolution, camera): QueryItem<
+            Self::Query,
+        >,
+    ) -> Self {
         let HikariSettings {
             direct_validate_interval,
             emissive_validate_interval,
@@ -156,6 +189,9 @@ impl ExtractComponent for FrameUniform {
             temporal_reuse,
             emissive_spatial_reuse,
             indirect_spatial_reuse,
+            direct_unbiased_reuse,
+            emissive_unbiased_reuse,
+            indirect_unbiased_reuse,
             ..
         } = settings.clone();
 
@@ -169,7 +205,49 @@ impl ExtractComponent for FrameUniform {
         let temporal_reuse = temporal_reuse.into();
         let emissive_spatial_reuse = emissive_spatial_reuse.into();
         let indirect_spatial_reuse = indirect_spatial_reuse.into();
+        let direct_unbiased_reuse = direct_unbiased_reuse.into();
+        let emissive_unbiased_reuse = emissive_unbiased_reuse.into();
+        let indirect_unbiased_reuse = indirect_unbiased_reuse.into();
         let upscale_ratio = settings.upscale.ratio();
+        let temporal_upscale = settings.upscale.is_temporal().into();
+        let upscale_jitter = match settings.upscale.is_temporal() {
+            true => upscale_jitter(counter.0, upscale_ratio),
+            false => Vec2::ZERO,
+        };
+
+        // HDR output is tone mapped by bevy, so it stays linear unless an operator is picked.
+        let hdr = matches!(camera, Some(camera) if camera.hdr);
+        let tone_mapping = match settings.tone_mapping {
+            Some(tone_mapping) => tone_mapping,
+            None if hdr => HikariToneMapping::None,
+            None => HikariToneMapping::default(),
+        } as u32;
+
+        let HikariLens {
+            aperture,
+            focus_distance,
+            blades: aperture_blades,
+            blade_rotation: aperture_rotation,
+        } = lens.copied().unwrap_or(HikariLens {
+            aperture: 0.0,
+            ..Default::default()
+        });
+
+        let (shutter, motion_blur_samples) = motion_blur.map_or((0.0, 0), |motion_blur| {
+            (motion_blur.shutter_angle / 360.0, motion_blur.samples)
+        });
+
+        let history_reset =
+            matches!(dynamic_resolution, Some(controller) if controller.is_changed());
+        let history_reset = history_reset.into();
+
+        // Accumulation takes one unbiased sample per frame, so radiance is not clamped either.
+        let (accumulate, accumulated_samples, max_indirect_luminance) = match progress {
+            Some(progress) if settings.is_accumulating() => {
+                (1, progress.samples.saturating_sub(1) as u32, f32::MAX)
+            }
+            _ => (0, 0, max_indirect_luminance),
+        };
 
         Self {
             kernel: KERNEL,
@@ -182,12 +260,47 @@ impl ExtractComponent for FrameUniform {
             temporal_reuse,
             emissive_spatial_reuse,
             indirect_spatial_reuse,
+            direct_unbiased_reuse,
+            emissive_unbiased_reuse,
+            indirect_unbiased_reuse,
             max_temporal_reuse_count,
             max_spatial_reuse_count,
             max_reservoir_lifetime,
             solar_angle,
             max_indirect_luminance,
             upscale_ratio,
+            accumulate,
+            accumulated_samples,
+            tone_mapping,
+            aperture,
+            focus_distance,
+            aperture_blades,
+            aperture_rotation,
+            shutter,
+            motion_blur_samples,
+            history_reset,
+            temporal_upscale,
+            upscale_jitter,
         }
     }
 }
+
+/// Jitter within a low resolution pixel, cycling through enough Halton phases to cover all output pixels in it.
+fn upscale_jitter(frame: usize, ratio: f32) -> Vec2 {
+    let phases = (8.0 * ratio * ratio).ceil() as usize;
+    let index = frame % phases + 1;
+    let jitter = Vec2::new(halton(2, index), halton(3, index));
+    (jitter - 0.5) * ratio
+}
+
+fn halton(base: usize, index: usize) -> f32 {
+    let mut result = 0.0;
+    let mut f = 1.0;
+    let mut index = index;
+    while index > 0 {
+        f /= base as f32;
+        result += f * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
@@ -0,0 +1,108 @@
+use crate::{light::CachedLightPipelines, post_process::CachedPostProcessPipelines};
+use bevy::{
+    prelude::*,
+    render::{
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        render_resource::*,
+        RenderApp, RenderStage,
+    },
+};
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc,
+};
+
+pub struct WarmUpPlugin;
+impl Plugin for WarmUpPlugin {
+    fn build(&self, app: &mut App) {
+        let ready = HikariPipelinesReady::default();
+
+        app.register_type::<HikariWarmUp>()
+            .init_resource::<HikariWarmUp>()
+            .insert_resource(ready.clone())
+            .add_plugin(ExtractResourcePlugin::<HikariWarmUp>::default());
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .insert_resource(ready)
+                .add_system_to_stage(RenderStage::Cleanup, pipelines_ready_system);
+        }
+    }
+}
+
+/// Pipelines to compile ahead of time, besides the ones needed by the current scene.
+#[derive(Debug, Default, Clone, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct HikariWarmUp {
+    /// Light pipelines are specialized for all texture capacities up to this number of material textures.
+    pub max_texture_count: usize,
+}
+
+impl ExtractResource for HikariWarmUp {
+    type Source = Self;
+
+    fn extract_resource(source: &Self::Source) -> Self {
+        source.clone()
+    }
+}
+
+#[derive(Debug, Default)]
+struct PipelinesReadyState {
+    queued: AtomicBool,
+    ready: AtomicUsize,
+    total: AtomicUsize,
+}
+
+/// Compilation progress of all queued pipelines, shared between the main and the render world.
+#[derive(Debug, Default, Clone, Resource)]
+pub struct HikariPipelinesReady(Arc<PipelinesReadyState>);
+
+impl HikariPipelinesReady {
+    /// Whether all queued pipelines have been compiled.
+    pub fn is_ready(&self) -> bool {
+        self.0.queued.load(Ordering::Acquire) && self.ready() == self.total()
+    }
+
+    /// Number of compiled pipelines.
+    pub fn ready(&self) -> usize {
+        self.0.ready.load(Ordering::Acquire)
+    }
+
+    /// Number of queued pipelines.
+    pub fn total(&self) -> usize {
+        self.0.total.load(Ordering::Acquire)
+    }
+
+    /// Marks the pipelines as ready when there is nothing to compile, e.g., when falling back to core 3d.
+    pub(crate) fn set_empty(&self) {
+        self.set(0, 0);
+    }
+
+    fn set(&self, ready: usize, total: usize) {
+        self.0.ready.store(ready, Ordering::Release);
+        self.0.total.store(total, Ordering::Release);
+        self.0.queued.store(true, Ordering::Release);
+    }
+}
+
+fn pipelines_ready_system(
+    ready: Res<HikariPipelinesReady>,
+    pipeline_cache: Res<PipelineCache>,
+    light_pipelines: Option<Res<CachedLightPipelines>>,
+    post_process_pipelines: Option<Res<CachedPostProcessPipelines>>,
+) {
+    if let (Some(light_pipelines), Some(post_process_pipelines)) =
+        (light_pipelines, post_process_pipelines)
+    {
+        let ids: Vec<_> = light_pipelines
+            .ids()
+            .into_iter()
+            .chain(post_process_pipelines.ids())
+            .collect();
+        let ready_count = ids
+            .iter()
+            .filter(|id| pipeline_cache.get_compute_pipeline(**id).is_some())
+            .count();
+        ready.set(ready_count, ids.len());
+    }
+}